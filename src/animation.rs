@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use crate::player::MMOPlayer;
+
+// below this the player counts as stood still
+const IDLE_SPEED: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AnimationClip {
+    Idle,
+    Walk,
+    Run,
+    Jump,
+    Fall,
+}
+
+const CLIPS: [AnimationClip; 5] = [
+    AnimationClip::Idle,
+    AnimationClip::Walk,
+    AnimationClip::Run,
+    AnimationClip::Jump,
+    AnimationClip::Fall,
+];
+
+// how much of each clip the player's model should be showing, always adding up to 1.
+// bevy 0.1 can't play skeletal animation and the player is a static obj, so nothing
+// plays these yet, they're worked out for when the model and the engine can
+pub struct AnimationState {
+    pub clip: AnimationClip,
+    pub weights: [f32; 5],
+    // per second, how quickly the weights move towards the clip the speed calls for
+    pub blend_speed: f32,
+}
+
+impl Default for AnimationState {
+    fn default() -> Self {
+        AnimationState {
+            clip: AnimationClip::Idle,
+            weights: [1., 0., 0., 0., 0.],
+            blend_speed: 8.,
+        }
+    }
+}
+
+impl AnimationState {
+    pub fn weight(&self, clip: AnimationClip) -> f32 {
+        self.weights[clip as usize]
+    }
+}
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(add_animation_state.system())
+            .add_system(update_animation_state.system());
+    }
+}
+
+fn add_animation_state(mut commands: Commands, mut player_query: Query<Without<AnimationState, With<MMOPlayer, Entity>>>) {
+    for entity in &mut player_query.iter() {
+        commands.insert_one(entity, AnimationState::default());
+    }
+}
+
+// on the ground, idle blends into walk up to the walking speed and walk into run up to
+// the sprinting speed. in the air it's all jump on the way up and fall on the way down
+fn target_weights(player: &MMOPlayer) -> [f32; 5] {
+    let mut weights = [0.; 5];
+    if !player.grounded && !player.swimming {
+        let clip = if player.vertical_velocity > 0. { AnimationClip::Jump } else { AnimationClip::Fall };
+        weights[clip as usize] = 1.;
+        return weights;
+    }
+
+    let speed = player.velocity.length();
+    let walk_speed = player.move_speed.max(IDLE_SPEED);
+    let run_speed = (player.move_speed * player.sprint_multiplier).max(walk_speed + IDLE_SPEED);
+    if speed <= walk_speed {
+        let walk = ((speed - IDLE_SPEED) / (walk_speed - IDLE_SPEED)).clamp(0., 1.);
+        weights[AnimationClip::Idle as usize] = 1. - walk;
+        weights[AnimationClip::Walk as usize] = walk;
+    } else {
+        let run = ((speed - walk_speed) / (run_speed - walk_speed)).clamp(0., 1.);
+        weights[AnimationClip::Walk as usize] = 1. - run;
+        weights[AnimationClip::Run as usize] = run;
+    }
+    weights
+}
+
+fn update_animation_state(time: Res<Time>, mut player_query: Query<(&MMOPlayer, &mut AnimationState)>) {
+    for (player, mut state) in &mut player_query.iter() {
+        let target = target_weights(player);
+        let blend = 1. - (-state.blend_speed * time.delta_seconds).exp();
+        for (weight, target) in state.weights.iter_mut().zip(target.iter()) {
+            *weight += (target - *weight) * blend;
+        }
+
+        let mut clip = AnimationClip::Idle;
+        for &candidate in CLIPS.iter() {
+            if state.weight(candidate) > state.weight(clip) { clip = candidate; }
+        }
+        state.clip = clip;
+    }
+}
@@ -0,0 +1,139 @@
+use bevy::{
+    prelude::*,
+    app::AppExit,
+};
+use crate::game_state::{GameStarted, GameState};
+use crate::player::{self, InputSource, MMOPlayer, PlayerAppearance};
+
+// `--bench N` spawns N extra players that walk between random points, times every frame
+// for a while and prints a summary before exiting
+pub const BENCH_FLAG: &str = "--bench";
+const WARMUP_SECONDS: f32 = 2.;
+const MEASURE_SECONDS: f32 = 20.;
+const ROAM_RADIUS: f32 = 60.;
+
+pub struct BenchPlugin {
+    pub bots: usize,
+}
+
+// asks for a new move target every time the last one is reached
+struct BenchBot {
+    seed: u32,
+}
+
+impl BenchBot {
+    // xorshift, the points only need to be spread out
+    fn next_point(&mut self) -> Vec3 {
+        let mut next_unit = || {
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 17;
+            self.seed ^= self.seed << 5;
+            (self.seed as f32 / u32::MAX as f32) * 2. - 1.
+        };
+        Vec3::new(next_unit(), 0., next_unit()) * ROAM_RADIUS
+    }
+}
+
+struct BenchConfig {
+    bots: usize,
+}
+
+#[derive(Default)]
+struct FrameTimes {
+    elapsed: f32,
+    samples: Vec<f32>,
+}
+
+impl Plugin for BenchPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // straight past the main menu, the loading screen starts the game once the player mesh is in
+        app.add_resource(GameState::Loading)
+            .add_resource(BenchConfig { bots: self.bots })
+            .init_resource::<FrameTimes>()
+            .add_system(spawn_bench_bots.system())
+            .add_system(drive_bench_bots.system())
+            .add_system(record_frame_times.system());
+    }
+}
+
+pub fn parse_bench_flag() -> Option<usize> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == BENCH_FLAG)?;
+    match args.get(index + 1).map(|count| count.parse::<usize>()) {
+        Some(Ok(count)) => Some(count),
+        _ => {
+            eprintln!("{} needs a player count, e.g. {} 500", BENCH_FLAG, BENCH_FLAG);
+            None
+        }
+    }
+}
+
+fn spawn_bench_bots(
+    mut commands: Commands,
+    mut game_started_reader: Local<EventReader<GameStarted>>,
+    game_started_events: Res<Events<GameStarted>>,
+    config: Res<BenchConfig>,
+    appearance: Res<PlayerAppearance>,
+) {
+    if game_started_reader.iter(&game_started_events).last().is_none() { return; }
+
+    // a square grid around the origin so nobody starts inside anybody else
+    let columns = (config.bots as f32).sqrt().ceil().max(1.) as usize;
+    for index in 0..config.bots {
+        let position = Vec3::new((index % columns) as f32, 0., (index / columns) as f32) * 3.
+            - Vec3::new(columns as f32 * 1.5, 0., columns as f32 * 1.5);
+        let bot = player::spawn_player(
+            &mut commands,
+            appearance.mesh,
+            appearance.material,
+            position,
+            None,
+            MMOPlayer {
+                input_source: InputSource::None,
+                ..Default::default()
+            },
+        );
+        commands.insert_one(bot, BenchBot { seed: (index as u32).wrapping_mul(0x9e37_79b9) | 1 });
+    }
+}
+
+fn drive_bench_bots(mut query: Query<(&mut BenchBot, &mut MMOPlayer)>) {
+    for (mut bot, mut player) in &mut query.iter() {
+        if player.move_target.is_none() { player.move_target = Some(bot.next_point()); }
+    }
+}
+
+fn record_frame_times(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    config: Res<BenchConfig>,
+    mut frame_times: ResMut<FrameTimes>,
+    mut exit_events: ResMut<Events<AppExit>>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    frame_times.elapsed += time.delta_seconds;
+    if frame_times.elapsed < WARMUP_SECONDS { return; }
+    if frame_times.elapsed < WARMUP_SECONDS + MEASURE_SECONDS {
+        frame_times.samples.push(time.delta_seconds * 1000.);
+        return;
+    }
+
+    let mut samples = std::mem::take(&mut frame_times.samples);
+    if samples.is_empty() { return; }
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let percentile = |p: f32| samples[((samples.len() - 1) as f32 * p).round() as usize];
+    let average = samples.iter().sum::<f32>() / samples.len() as f32;
+    println!(
+        "bench: {} players, {} frames over {}s: avg {:.2}ms, p50 {:.2}ms, p95 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+        config.bots,
+        samples.len(),
+        MEASURE_SECONDS,
+        average,
+        percentile(0.5),
+        percentile(0.95),
+        percentile(0.99),
+        samples[samples.len() - 1],
+    );
+    exit_events.send(AppExit);
+}
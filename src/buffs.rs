@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use crate::game_state::GameState;
+use crate::speed_modifiers::{SpeedModifierKind, SpeedModifiers};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BuffKind {
+    Haste,
+    Slow,
+}
+
+impl BuffKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "haste" => Some(BuffKind::Haste),
+            "slow" => Some(BuffKind::Slow),
+            _ => None,
+        }
+    }
+
+    // each kind gets its own source, so expiring one never takes off anything else's modifier
+    fn source(self) -> &'static str {
+        match self {
+            BuffKind::Haste => "buff:haste",
+            BuffKind::Slow => "buff:slow",
+        }
+    }
+
+    fn speed_modifier(self) -> SpeedModifierKind {
+        match self {
+            BuffKind::Haste => SpeedModifierKind::Multiply(1.3),
+            BuffKind::Slow => SpeedModifierKind::Multiply(0.5),
+        }
+    }
+}
+
+pub struct Buff {
+    pub kind: BuffKind,
+    // seconds left
+    pub remaining: f32,
+    applied: bool,
+}
+
+#[derive(Default)]
+pub struct Buffs {
+    pub active: Vec<Buff>,
+}
+
+impl Buffs {
+    // reapplying a buff that is already running just refreshes how long it has left
+    pub fn apply(&mut self, kind: BuffKind, seconds: f32) {
+        match self.active.iter_mut().find(|buff| buff.kind == kind) {
+            Some(buff) => buff.remaining = buff.remaining.max(seconds),
+            None => self.active.push(Buff { kind, remaining: seconds, applied: false }),
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub struct BuffApplied {
+    pub entity: Entity,
+    pub kind: BuffKind,
+}
+
+#[allow(dead_code)]
+pub struct BuffExpired {
+    pub entity: Entity,
+    pub kind: BuffKind,
+}
+
+pub struct BuffPlugin;
+
+impl Plugin for BuffPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<BuffApplied>()
+            .add_event::<BuffExpired>()
+            .add_system(update_buffs.system());
+    }
+}
+
+fn update_buffs(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    mut applied_events: ResMut<Events<BuffApplied>>,
+    mut expired_events: ResMut<Events<BuffExpired>>,
+    mut query: Query<(Entity, &mut Buffs, &mut SpeedModifiers)>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    for (entity, mut buffs, mut modifiers) in &mut query.iter() {
+        for buff in &mut buffs.active {
+            if !buff.applied {
+                buff.applied = true;
+                modifiers.add(buff.kind.source(), buff.kind.speed_modifier());
+                applied_events.send(BuffApplied { entity, kind: buff.kind });
+            }
+
+            buff.remaining -= time.delta_seconds;
+            if buff.remaining <= 0. {
+                modifiers.remove(buff.kind.source());
+                expired_events.send(BuffExpired { entity, kind: buff.kind });
+            }
+        }
+        buffs.active.retain(|buff| buff.remaining > 0.);
+    }
+}
@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use crate::combat::Hit;
+use crate::game_state::GameState;
+use crate::health::Damaged;
+use crate::network::RemotePlayer;
+use crate::player::MMOPlayer;
+
+// shake sizes, in world units of camera offset at the start of the shake
+const HURT_SHAKE_PER_DAMAGE: f32 = 0.02;
+const HURT_SHAKE_MAX: f32 = 0.8;
+const HURT_SHAKE_DURATION: f32 = 0.35;
+const HIT_SHAKE: f32 = 0.15;
+const HIT_SHAKE_DURATION: f32 = 0.15;
+
+// shakes the local cameras. a shake weaker than the one already running is dropped
+pub struct ShakeCamera {
+    pub intensity: f32,
+    pub duration: f32,
+}
+
+// the offset is recalculated every frame and fades out linearly with the remaining time,
+// so it is exactly zero once the shake is over
+pub struct CameraShake {
+    pub remaining: f32,
+    pub duration: f32,
+    pub magnitude: f32,
+    pub offset: Vec3,
+    seed: u32,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        CameraShake {
+            remaining: 0.,
+            duration: 0.,
+            magnitude: 0.,
+            offset: Vec3::zero(),
+            seed: 0x9e37_79b9,
+        }
+    }
+}
+
+impl CameraShake {
+    pub fn start(&mut self, intensity: f32, duration: f32) {
+        if duration <= 0. || intensity <= 0. { return; }
+        if self.current_magnitude() > intensity { return; }
+        self.magnitude = intensity;
+        self.duration = duration;
+        self.remaining = duration;
+    }
+
+    pub fn tick(&mut self, dt: f32) {
+        self.remaining = (self.remaining - dt).max(0.);
+        if self.remaining <= 0. {
+            self.offset = Vec3::zero();
+            return;
+        }
+        let magnitude = self.current_magnitude();
+        self.offset = Vec3::new(self.next_unit(), self.next_unit(), self.next_unit()) * magnitude;
+    }
+
+    fn current_magnitude(&self) -> f32 {
+        if self.duration <= 0. { return 0.; }
+        self.magnitude * self.remaining / self.duration
+    }
+
+    // xorshift, only needs to look random
+    fn next_unit(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed as f32 / u32::MAX as f32) * 2. - 1.
+    }
+}
+
+pub struct CameraShakePlugin;
+
+impl Plugin for CameraShakePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<ShakeCamera>()
+            .init_resource::<CameraShake>()
+            .add_system(shake_on_combat.system())
+            .add_system(update_camera_shake.system());
+    }
+}
+
+fn shake_on_combat(
+    mut damaged_reader: Local<EventReader<Damaged>>,
+    damaged_events: Res<Events<Damaged>>,
+    mut hit_reader: Local<EventReader<Hit>>,
+    hit_events: Res<Events<Hit>>,
+    mut shake_events: ResMut<Events<ShakeCamera>>,
+    player_query: Query<Without<RemotePlayer, &MMOPlayer>>,
+) {
+    for damage in damaged_reader.iter(&damaged_events) {
+        if player_query.get::<MMOPlayer>(damage.target).is_err() { continue; }
+        shake_events.send(ShakeCamera {
+            intensity: (damage.amount * HURT_SHAKE_PER_DAMAGE).min(HURT_SHAKE_MAX),
+            duration: HURT_SHAKE_DURATION,
+        });
+    }
+
+    for hit in hit_reader.iter(&hit_events) {
+        if player_query.get::<MMOPlayer>(hit.attacker).is_err() { continue; }
+        shake_events.send(ShakeCamera { intensity: HIT_SHAKE, duration: HIT_SHAKE_DURATION });
+    }
+}
+
+fn update_camera_shake(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    mut shake_reader: Local<EventReader<ShakeCamera>>,
+    shake_events: Res<Events<ShakeCamera>>,
+    mut shake: ResMut<CameraShake>,
+) {
+    for event in shake_reader.iter(&shake_events) {
+        shake.start(event.intensity, event.duration);
+    }
+
+    // a paused game holds the camera still, shake included
+    if *game_state != GameState::Playing { return; }
+    shake.tick(time.delta_seconds);
+}
@@ -0,0 +1,155 @@
+use bevy::{
+    prelude::*,
+    input::{keyboard::{ElementState, KeyboardInput}, mouse::MouseButtonInput},
+};
+use crate::game_state::GameState;
+use crate::network::RemotePlayer;
+use crate::player::{look_rotation, InputSource, KeyboardCaptured, MMOPlayer};
+
+#[derive(Clone, Copy)]
+pub struct CameraKeyframe {
+    // seconds from the start of the path
+    pub time: f32,
+    pub position: Vec3,
+    pub look_at: Vec3,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PathPlayback {
+    OneShot,
+    // jumps back to the first keyframe after the last, so a path that should
+    // loop seamlessly has to end where it starts
+    Loop,
+}
+
+// world space keyframes, kept in time order. while playing, the local player's camera follows
+// the path instead of the player until it ends or any key or mouse button is pressed
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+    pub playback: PathPlayback,
+    pub playing: bool,
+    pub elapsed: f32,
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        CameraPath {
+            keyframes: Vec::new(),
+            playback: PathPlayback::OneShot,
+            playing: false,
+            elapsed: 0.,
+        }
+    }
+}
+
+impl CameraPath {
+    pub fn add(&mut self, keyframe: CameraKeyframe) {
+        let index = self.keyframes.iter().position(|other| other.time > keyframe.time).unwrap_or(self.keyframes.len());
+        self.keyframes.insert(index, keyframe);
+    }
+
+    pub fn play(&mut self, playback: PathPlayback) {
+        self.playback = playback;
+        self.elapsed = 0.;
+        self.playing = self.keyframes.len() >= 2;
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0., |last| last.time)
+    }
+
+    // position and look at target at the given time, a catmull-rom spline through the
+    // keyframes so the camera doesn't turn corners at each one
+    pub fn sample(&self, time: f32) -> Option<(Vec3, Vec3)> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        let next = self.keyframes.iter().position(|keyframe| keyframe.time > time).unwrap_or(last).max(1);
+        let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let span = to.time - from.time;
+        let t = if span > 0. { ((time - from.time) / span).clamp(0., 1.) } else { 1. };
+
+        // the ends repeat their own keyframe in place of the missing neighbour
+        let before = &self.keyframes[next.saturating_sub(2)];
+        let after = &self.keyframes[(next + 1).min(last)];
+        Some((
+            catmull_rom(before.position, from.position, to.position, after.position, t),
+            catmull_rom(before.look_at, from.look_at, to.look_at, after.look_at, t),
+        ))
+    }
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.
+        + (p2 - p0) * t
+        + (p0 * 2. - p1 * 5. + p2 * 4. - p3) * t2
+        + (p1 * 3. - p0 - p2 * 3. + p3) * t3)
+        * 0.5
+}
+
+pub struct CinematicPlugin;
+
+impl Plugin for CinematicPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<CameraPath>()
+            .add_system(play_camera_path.system());
+    }
+}
+
+// the camera hangs off the player, so the path is brought into the player's frame.
+// update_player_camera leaves the camera alone while this is playing, and eases it
+// back to the player once it stops
+#[allow(clippy::too_many_arguments)]
+fn play_camera_path(
+    mut keyboard_reader: Local<EventReader<KeyboardInput>>,
+    keyboard_events: Res<Events<KeyboardInput>>,
+    mut mouse_button_reader: Local<EventReader<MouseButtonInput>>,
+    mouse_button_events: Res<Events<MouseButtonInput>>,
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    captured: Res<KeyboardCaptured>,
+    mut path: ResMut<CameraPath>,
+    mut player_query: Query<Without<RemotePlayer, (&MMOPlayer, &Translation, &Rotation)>>,
+    camera_query: Query<(&mut Translation, &mut Rotation)>,
+) {
+    // typing into the console doesn't count, or the command that started the path would stop it
+    let key_pressed = keyboard_reader.iter(&keyboard_events).any(|event| event.state == ElementState::Pressed) && !captured.0;
+    let button_pressed = mouse_button_reader.iter(&mouse_button_events).any(|event| event.state == ElementState::Pressed);
+    if !path.playing || *game_state != GameState::Playing { return; }
+    if key_pressed || button_pressed {
+        path.playing = false;
+        return;
+    }
+
+    path.elapsed += time.delta_seconds;
+    let duration = path.duration();
+    if path.elapsed > duration {
+        match path.playback {
+            PathPlayback::Loop if duration > 0. => path.elapsed %= duration,
+            _ => {
+                path.playing = false;
+                return;
+            }
+        }
+    }
+    let (position, look_at) = match path.sample(path.elapsed) {
+        Some(sample) => sample,
+        None => return,
+    };
+
+    for (player, translation, rotation) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let camera_entity = match player.camera_entity {
+            Some(camera_entity) => camera_entity,
+            None => continue,
+        };
+        let to_local = rotation.0.conjugate();
+        let (eye, target) = (to_local * (position - translation.0), to_local * (look_at - translation.0));
+        if let Ok(mut camera) = camera_query.entity(camera_entity) {
+            if let Some((mut camera_translation, mut camera_rotation)) = camera.get() {
+                camera_translation.0 = eye;
+                camera_rotation.0 = look_rotation(eye, target);
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use bevy::{
+    prelude::*,
+    render::camera::Camera,
+};
+use crate::cursor::{cursor_ray, CursorPosition};
+use crate::game_state::GameState;
+use crate::player::{InputSource, KeyBindings, MMOPlayer};
+use crate::selection::BoxSelect;
+use crate::terrain::TerrainHeight;
+
+pub struct ClickToMovePlugin;
+
+impl Plugin for ClickToMovePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(set_move_target.system());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn set_move_target(
+    mouse_button_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    game_state: Res<GameState>,
+    cursor: Res<CursorPosition>,
+    box_select: Res<BoxSelect>,
+    windows: Res<Windows>,
+    terrain: Res<TerrainHeight>,
+    mut player_query: Query<&mut MMOPlayer>,
+    camera_query: Query<(&Transform, &Camera)>,
+) {
+    // on release, so the end of a box selection drag doesn't also walk the player there
+    if *game_state != GameState::Playing || !mouse_button_input.just_released(MouseButton::Left) { return; }
+    if box_select.drag_rect(&cursor).is_some() { return; }
+    let queue = keyboard_input.pressed(bindings.queue_modifier);
+
+    for mut player in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let camera_entity = match player.camera_entity {
+            Some(camera_entity) => camera_entity,
+            None => continue,
+        };
+
+        let camera_transform = camera_query.get::<Transform>(camera_entity);
+        let camera = camera_query.get::<Camera>(camera_entity);
+        if let (Ok(camera_transform), Ok(camera)) = (camera_transform, camera) {
+            if let Some(ray) = cursor_ray(&cursor, &windows, &camera_transform, &camera) {
+                if let Some(target) = ray.intersect_terrain(&terrain) {
+                    // the queue modifier adds a leg on the end of the current path, a plain click starts a new one
+                    if queue && player.move_target.is_some() {
+                        player.waypoints.push_back(target);
+                    } else {
+                        player.waypoints.clear();
+                        player.move_target = Some(target);
+                    }
+                }
+            }
+        }
+    }
+}
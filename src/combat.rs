@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use crate::game_state::GameState;
+use crate::health::{Damage, Health, Invulnerable};
+use crate::knockback::Knockback;
+use crate::network::RemotePlayer;
+use crate::player::{CameraMode, InputSource, KeyBindings, KeyboardCaptured, MMOPlayer};
+use crate::targeting::Enemy;
+use crate::terrain::TerrainHeight;
+
+const MELEE_RANGE: f32 = 3.5;
+// half angle either side of the player's facing
+const MELEE_ARC: f32 = std::f32::consts::FRAC_PI_4;
+const MELEE_DAMAGE: f32 = 25.;
+// starting speed of the shove away from the attacker
+const MELEE_KNOCKBACK: f32 = 20.;
+const PROJECTILE_SPEED: f32 = 40.;
+const PROJECTILE_LIFETIME: f32 = 2.;
+const PROJECTILE_DAMAGE: f32 = 15.;
+const PROJECTILE_KNOCKBACK: f32 = 10.;
+const PROJECTILE_RADIUS: f32 = 0.3;
+// how close to an entity's origin counts as hitting it
+const PROJECTILE_HIT_RADIUS: f32 = 1.5;
+// fired from about chest height, a little in front of the player
+const PROJECTILE_LAUNCH_HEIGHT: f32 = 2.;
+const PROJECTILE_LAUNCH_FORWARD: f32 = 1.;
+
+// seconds between attacks, remaining counts down to the next one being allowed
+pub struct AttackCooldown {
+    pub duration: f32,
+    pub remaining: f32,
+}
+
+impl Default for AttackCooldown {
+    fn default() -> Self {
+        AttackCooldown {
+            duration: 0.6,
+            remaining: 0.,
+        }
+    }
+}
+
+impl AttackCooldown {
+    pub fn ready(&self) -> bool {
+        self.remaining <= 0.
+    }
+}
+
+#[allow(dead_code)]
+pub struct Hit {
+    pub attacker: Entity,
+    pub victim: Entity,
+    pub damage: f32,
+}
+
+pub struct Projectile {
+    pub owner: Entity,
+    pub velocity: Vec3,
+    // seconds left before it despawns without hitting anything
+    pub lifetime: f32,
+    pub damage: f32,
+}
+
+// for effects to react to, victim is none when it hit the ground
+#[allow(dead_code)]
+pub struct ProjectileImpact {
+    pub position: Vec3,
+    pub victim: Option<Entity>,
+}
+
+struct ProjectileAppearance {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<Hit>()
+            .add_event::<ProjectileImpact>()
+            .add_startup_system(load_projectile_appearance.system())
+            .add_system(tick_attack_cooldowns.system())
+            .add_system(melee_attack.system())
+            .add_system(fire_projectiles.system())
+            .add_system(move_projectiles.system())
+            .add_system(despawn_dead_enemies.system());
+    }
+}
+
+fn tick_attack_cooldowns(time: Res<Time>, game_state: Res<GameState>, mut query: Query<&mut AttackCooldown>) {
+    if *game_state != GameState::Playing { return; }
+
+    for mut cooldown in &mut query.iter() {
+        cooldown.remaining = (cooldown.remaining - time.delta_seconds).max(0.);
+    }
+}
+
+// hits every enemy within range and inside the arc in front of the player, left click is
+// already click to move so the attack is on its own key
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn melee_attack(
+    game_state: Res<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    captured: Res<KeyboardCaptured>,
+    mut damage_events: ResMut<Events<Damage>>,
+    mut hit_events: ResMut<Events<Hit>>,
+    mut knockback_events: ResMut<Events<Knockback>>,
+    mut player_query: Query<Without<RemotePlayer, (Entity, &MMOPlayer, &Transform, &mut AttackCooldown)>>,
+    mut enemy_query: Query<Without<Invulnerable, With<Enemy, (Entity, &Health, &Transform)>>>,
+) {
+    if *game_state != GameState::Playing || captured.0 { return; }
+    if !keyboard_input.just_pressed(bindings.attack) { return; }
+
+    for (player_entity, player, transform, mut cooldown) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse || !cooldown.ready() { continue; }
+        cooldown.remaining = cooldown.duration;
+
+        let position = Vec3::from(transform.value.w_axis().truncate());
+        let mut forward = Vec3::from(transform.value.z_axis().truncate());
+        *forward.y_mut() = 0.;
+        let forward = forward.normalize();
+
+        for (enemy_entity, health, enemy_transform) in &mut enemy_query.iter() {
+            if health.current <= 0. { continue; }
+
+            let mut offset = Vec3::from(enemy_transform.value.w_axis().truncate()) - position;
+            *offset.y_mut() = 0.;
+            let distance = offset.length();
+            if distance > MELEE_RANGE { continue; }
+            if distance > 0. && forward.dot(offset / distance).min(1.).acos() > MELEE_ARC { continue; }
+
+            damage_events.send(Damage { target: enemy_entity, amount: MELEE_DAMAGE });
+            hit_events.send(Hit { attacker: player_entity, victim: enemy_entity, damage: MELEE_DAMAGE });
+            // straight on when standing right on top of it
+            let direction = if distance > 0. { offset } else { forward };
+            knockback_events.send(Knockback { target: enemy_entity, direction, strength: MELEE_KNOCKBACK });
+        }
+    }
+}
+
+fn load_projectile_appearance(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(ProjectileAppearance {
+        mesh: meshes.add(Mesh::from(shape::Icosphere { radius: PROJECTILE_RADIUS, subdivisions: 2 })),
+        material: materials.add(Color::rgb(1., 0.6, 0.1).into()),
+    });
+}
+
+// fires along the player's facing, first person also aims up and down with the camera
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn fire_projectiles(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    captured: Res<KeyboardCaptured>,
+    appearance: Res<ProjectileAppearance>,
+    mut player_query: Query<Without<RemotePlayer, (Entity, &MMOPlayer, &Transform, &mut AttackCooldown)>>,
+) {
+    if *game_state != GameState::Playing || captured.0 { return; }
+    if !keyboard_input.just_pressed(bindings.ranged_attack) { return; }
+
+    for (player_entity, player, transform, mut cooldown) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse || !cooldown.ready() { continue; }
+        cooldown.remaining = cooldown.duration;
+
+        let rotation = Quat::from_rotation_y(-player.yaw);
+        let direction = match player.camera_mode {
+            // the same direction the first person camera looks in, see update_player_camera
+            CameraMode::FirstPerson => {
+                let look = Vec3::new(0., -player.camera_pitch.cos(), player.camera_pitch.sin());
+                rotation * look.normalize()
+            }
+            CameraMode::ThirdPerson | CameraMode::Strategy => rotation * Vec3::unit_z(),
+        };
+        let position = Vec3::from(transform.value.w_axis().truncate()) + rotation * Vec3::new(0., PROJECTILE_LAUNCH_HEIGHT, PROJECTILE_LAUNCH_FORWARD);
+
+        commands
+            .spawn(PbrComponents {
+                mesh: appearance.mesh,
+                material: appearance.material,
+                translation: Translation(position),
+                ..Default::default()
+            })
+            .with(Projectile {
+                owner: player_entity,
+                velocity: direction * PROJECTILE_SPEED,
+                lifetime: PROJECTILE_LIFETIME,
+                damage: PROJECTILE_DAMAGE,
+            });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn move_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    terrain: Res<TerrainHeight>,
+    mut damage_events: ResMut<Events<Damage>>,
+    mut hit_events: ResMut<Events<Hit>>,
+    mut impact_events: ResMut<Events<ProjectileImpact>>,
+    mut knockback_events: ResMut<Events<Knockback>>,
+    mut projectile_query: Query<(Entity, &mut Projectile, &mut Translation)>,
+    mut target_query: Query<Without<Invulnerable, (Entity, &Health, &Transform)>>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    for (entity, mut projectile, mut translation) in &mut projectile_query.iter() {
+        projectile.lifetime -= time.delta_seconds;
+        if projectile.lifetime <= 0. {
+            commands.despawn(entity);
+            continue;
+        }
+        translation.0 += projectile.velocity * time.delta_seconds;
+
+        // only checked where the projectile lands each frame, fine at this speed. it flies
+        // straight through anything invulnerable, like a dashing player
+        let mut victim = None;
+        for (target, health, target_transform) in &mut target_query.iter() {
+            if target == projectile.owner || health.current <= 0. { continue; }
+            let center = Vec3::from(target_transform.value.w_axis().truncate());
+            if (center - translation.0).length() <= PROJECTILE_HIT_RADIUS {
+                victim = Some(target);
+                break;
+            }
+        }
+
+        if let Some(victim) = victim {
+            damage_events.send(Damage { target: victim, amount: projectile.damage });
+            hit_events.send(Hit { attacker: projectile.owner, victim, damage: projectile.damage });
+            knockback_events.send(Knockback { target: victim, direction: projectile.velocity, strength: PROJECTILE_KNOCKBACK });
+        } else if translation.y() > terrain.height_at(translation.x(), translation.z()) {
+            continue;
+        }
+        impact_events.send(ProjectileImpact { position: translation.0, victim });
+        commands.despawn(entity);
+    }
+}
+
+fn despawn_dead_enemies(mut commands: Commands, mut query: Query<With<Enemy, (Entity, &Health)>>) {
+    for (entity, health) in &mut query.iter() {
+        if health.current <= 0. { commands.despawn(entity); }
+    }
+}
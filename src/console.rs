@@ -0,0 +1,354 @@
+use bevy::{
+    prelude::*,
+    input::keyboard::{ElementState, KeyboardInput},
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use crate::buffs::{BuffKind, Buffs};
+use crate::cinematic::{CameraKeyframe, CameraPath, PathPlayback};
+use crate::display::{self, WindowSettings, MIN_MAX_FPS};
+use crate::formation::{Formation, FormationKind};
+use crate::hud::HUD_FONT_PATH;
+use crate::player::{InputSource, KeyBindings, KeyboardCaptured, MMOPlayer, MovementState, Teleported};
+use crate::speed_modifiers::{SpeedModifierKind, SpeedModifiers};
+use crate::timestep::FixedPosition;
+
+const CONSOLE_FONT_SIZE: f32 = 18.;
+
+// handlers get the arguments after the command name and the whole world, and
+// return the line to print back
+pub type ConsoleHandler = Arc<dyn Fn(&[&str], &mut World, &mut Resources) -> Result<String, String> + Send + Sync>;
+
+struct ConsoleCommand {
+    usage: &'static str,
+    handler: ConsoleHandler,
+}
+
+#[derive(Default)]
+pub struct ConsoleCommands {
+    commands: BTreeMap<&'static str, ConsoleCommand>,
+}
+
+impl ConsoleCommands {
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        handler: impl Fn(&[&str], &mut World, &mut Resources) -> Result<String, String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.commands.insert(name, ConsoleCommand { usage, handler: Arc::new(handler) });
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    pub output: String,
+    submitted: Vec<String>,
+}
+
+struct ConsoleInputText;
+struct ConsoleOutputText;
+
+#[derive(Default)]
+struct ConsoleState {
+    keyboard_input_event_reader: EventReader<KeyboardInput>,
+}
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let mut commands = ConsoleCommands::default();
+        register_builtin_commands(&mut commands);
+
+        app.init_resource::<Console>()
+            .add_resource(commands)
+            .add_startup_system(spawn_console.system())
+            .add_system_to_stage(stage::PRE_UPDATE, read_console_input.system())
+            .add_system(run_console_commands.thread_local_system())
+            .add_system(update_console_text.system());
+    }
+}
+
+fn spawn_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = match asset_server.load(HUD_FONT_PATH) {
+        Ok(font) => font,
+        Err(err) => {
+            eprintln!("failed to load console font '{}': {}, console disabled", HUD_FONT_PATH, err);
+            return;
+        }
+    };
+
+    let line = |bottom: f32| TextComponents {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(5.0),
+                bottom: Val::Px(bottom),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text {
+            value: String::new(),
+            font,
+            style: TextStyle {
+                font_size: CONSOLE_FONT_SIZE,
+                color: Color::WHITE,
+            },
+        },
+        draw: Draw { is_visible: false, ..Default::default() },
+        ..Default::default()
+    };
+    commands
+        .spawn(line(5.0))
+        .with(ConsoleInputText)
+        .spawn(line(5.0 + CONSOLE_FONT_SIZE * 1.5))
+        .with(ConsoleOutputText);
+}
+
+// bevy 0.1 has no text input events, so keys are mapped to characters by hand
+fn key_to_char(key: KeyCode, shift: bool) -> Option<char> {
+    use KeyCode::*;
+    let letter = |lower: char| Some(if shift { lower.to_ascii_uppercase() } else { lower });
+    match key {
+        A => letter('a'), B => letter('b'), C => letter('c'), D => letter('d'), E => letter('e'),
+        F => letter('f'), G => letter('g'), H => letter('h'), I => letter('i'), J => letter('j'),
+        K => letter('k'), L => letter('l'), M => letter('m'), N => letter('n'), O => letter('o'),
+        P => letter('p'), Q => letter('q'), R => letter('r'), S => letter('s'), T => letter('t'),
+        U => letter('u'), V => letter('v'), W => letter('w'), X => letter('x'), Y => letter('y'),
+        Z => letter('z'),
+        Key0 | Numpad0 => Some('0'), Key1 | Numpad1 => Some('1'), Key2 | Numpad2 => Some('2'),
+        Key3 | Numpad3 => Some('3'), Key4 | Numpad4 => Some('4'), Key5 | Numpad5 => Some('5'),
+        Key6 | Numpad6 => Some('6'), Key7 | Numpad7 => Some('7'), Key8 | Numpad8 => Some('8'),
+        Key9 | Numpad9 => Some('9'),
+        Space => Some(' '),
+        Minus | Subtract => Some(if shift { '_' } else { '-' }),
+        Period | Decimal => Some('.'),
+        Comma => Some(','),
+        Slash => Some('/'),
+        _ => None,
+    }
+}
+
+fn read_console_input(
+    mut state: Local<ConsoleState>,
+    keyboard_events: Res<Events<KeyboardInput>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut console: ResMut<Console>,
+    mut captured: ResMut<KeyboardCaptured>,
+) {
+    let shift = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+    for event in state.keyboard_input_event_reader.iter(&keyboard_events) {
+        if event.state != ElementState::Pressed { continue; }
+        let key = match event.key_code {
+            Some(key) => key,
+            None => continue,
+        };
+
+        if key == bindings.toggle_console {
+            console.open = !console.open;
+            continue;
+        }
+        if !console.open { continue; }
+
+        match key {
+            KeyCode::Back => { console.input.pop(); }
+            KeyCode::Return | KeyCode::NumpadEnter => {
+                let line = std::mem::take(&mut console.input);
+                if !line.trim().is_empty() { console.submitted.push(line); }
+            }
+            key => if let Some(character) = key_to_char(key, shift) { console.input.push(character); },
+        }
+    }
+
+    // keep the typed keys from also driving the player
+    captured.0 = console.open;
+}
+
+fn run_console_commands(world: &mut World, resources: &mut Resources) {
+    let submitted = std::mem::take(&mut resources.get_mut::<Console>().unwrap().submitted);
+    for line in submitted {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => continue,
+        };
+
+        // the handler may want the registry itself, so it isn't held while running
+        let command = resources
+            .get::<ConsoleCommands>()
+            .unwrap()
+            .commands
+            .get(name)
+            .map(|command| (command.usage, command.handler.clone()));
+        let output = match command {
+            Some((usage, handler)) => match handler(args, world, resources) {
+                Ok(output) => output,
+                Err(err) => format!("{}, usage: {}", err, usage),
+            },
+            None => format!("unknown command '{}', try help", name),
+        };
+        resources.get_mut::<Console>().unwrap().output = output;
+    }
+}
+
+fn update_console_text(
+    console: Res<Console>,
+    mut input_query: Query<(&ConsoleInputText, &mut Text, &mut Draw)>,
+    mut output_query: Query<(&ConsoleOutputText, &mut Text, &mut Draw)>,
+) {
+    for (_marker, mut text, mut draw) in &mut input_query.iter() {
+        draw.is_visible = console.open;
+        let value = format!("> {}_", console.input);
+        if text.value != value { text.value = value; }
+    }
+    for (_marker, mut text, mut draw) in &mut output_query.iter() {
+        draw.is_visible = console.open && !console.output.is_empty();
+        if text.value != console.output { text.value = console.output.clone(); }
+    }
+}
+
+fn parse_floats<const N: usize>(args: &[&str]) -> Result<[f32; N], String> {
+    if args.len() != N { return Err(format!("expected {} numbers", N)); }
+    let mut values = [0.; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg.parse().map_err(|_| format!("'{}' isn't a number", arg))?;
+    }
+    Ok(values)
+}
+
+fn register_builtin_commands(commands: &mut ConsoleCommands) {
+    commands
+        .register("help", "help", |_args, _world, resources| {
+            let commands = resources.get::<ConsoleCommands>().unwrap();
+            let usages: Vec<&str> = commands.commands.values().map(|command| command.usage).collect();
+            Ok(usages.join(", "))
+        })
+        .register("pos", "pos", |_args, world, _resources| {
+            for (player, translation) in &mut world.query::<(&MMOPlayer, &Translation)>() {
+                if player.input_source != InputSource::KeyboardMouse { continue; }
+                return Ok(format!("{:.2} {:.2} {:.2}", translation.x(), translation.y(), translation.z()));
+            }
+            Err("no local player".to_string())
+        })
+        .register("tp", "tp <x> <y> <z>", |args, world, resources| {
+            let [x, y, z] = parse_floats::<3>(args)?;
+            let target = Vec3::new(x, y, z);
+            let query = &mut world.query::<(Entity, &mut MMOPlayer, &mut FixedPosition, &mut Translation)>();
+            for (entity, mut player, mut position, mut translation) in query {
+                if player.input_source != InputSource::KeyboardMouse { continue; }
+                // let gravity settle the player if they were put in the air
+                let state = MovementState::at_rest(target, false);
+                position.teleport(target);
+                translation.0 = target;
+                player.move_target = None;
+                player.waypoints.clear();
+                player.set_movement_state(&state);
+                resources.get_mut::<Events<Teleported>>().unwrap().send(Teleported { entity, state });
+                return Ok(format!("teleported to {:.2} {:.2} {:.2}", x, y, z));
+            }
+            Err("no local player".to_string())
+        })
+        .register("speed", "speed <n>", |args, world, _resources| {
+            let [speed] = parse_floats::<1>(args)?;
+            for mut player in &mut world.query::<&mut MMOPlayer>() {
+                if player.input_source != InputSource::KeyboardMouse { continue; }
+                player.move_speed = speed;
+                return Ok(format!("move speed set to {}", speed));
+            }
+            Err("no local player".to_string())
+        })
+        .register("slow", "slow <multiplier> <seconds>", |args, world, _resources| {
+            let [multiplier, seconds] = parse_floats::<2>(args)?;
+            for (player, mut modifiers) in &mut world.query::<(&MMOPlayer, &mut SpeedModifiers)>() {
+                if player.input_source != InputSource::KeyboardMouse { continue; }
+                modifiers.add_timed("console", SpeedModifierKind::Multiply(multiplier), seconds);
+                return Ok(format!("speed multiplied by {} for {}s", multiplier, seconds));
+            }
+            Err("no local player".to_string())
+        })
+        .register("buff", "buff <haste|slow> <seconds>", |args, world, _resources| {
+            let (kind, seconds) = match args {
+                [kind, seconds] => (*kind, seconds),
+                _ => return Err("expected a buff and a duration".to_string()),
+            };
+            let kind = BuffKind::from_name(kind).ok_or_else(|| format!("unknown buff '{}'", kind))?;
+            let [seconds] = parse_floats::<1>(&[seconds])?;
+            for (player, mut buffs) in &mut world.query::<(&MMOPlayer, &mut Buffs)>() {
+                if player.input_source != InputSource::KeyboardMouse { continue; }
+                buffs.apply(kind, seconds);
+                return Ok(format!("{:?} for {}s", kind, seconds));
+            }
+            Err("no local player".to_string())
+        })
+        .register("formation", "formation <line|grid> [spacing]", |args, _world, resources| {
+            let (kind, spacing) = match args {
+                [kind] => (*kind, None),
+                [kind, spacing] => (*kind, Some(spacing)),
+                _ => return Err("expected a formation and an optional spacing".to_string()),
+            };
+            let kind = FormationKind::from_name(kind).ok_or_else(|| format!("unknown formation '{}'", kind))?;
+            let mut formation = resources.get_mut::<Formation>().ok_or("no formation to change")?;
+            formation.kind = kind;
+            if let Some(spacing) = spacing {
+                let [spacing] = parse_floats::<1>(&[spacing])?;
+                formation.spacing = spacing;
+            }
+            Ok(format!("{:?} formation, {} apart", formation.kind, formation.spacing))
+        })
+        .register("campath", "campath <add [seconds]|play [loop]|clear>", |args, world, resources| {
+            let mut path = resources.get_mut::<CameraPath>().ok_or("no camera path")?;
+            match args {
+                ["add"] | ["add", _] => {
+                    let [gap] = if args.len() == 2 { parse_floats::<1>(&args[1..])? } else { [2.] };
+                    // the view the local camera has right now, looking at a point a little way ahead
+                    let mut view = None;
+                    for player in &mut world.query::<&MMOPlayer>() {
+                        if player.input_source != InputSource::KeyboardMouse { continue; }
+                        view = player.camera_entity.and_then(|camera| world.get::<Transform>(camera).ok().map(|transform| transform.value));
+                    }
+                    let view = view.ok_or("no local camera")?;
+                    let position = Vec3::from(view.w_axis().truncate());
+                    let forward = -Vec3::from(view.z_axis().truncate());
+                    let time = if path.keyframes.is_empty() { 0. } else { path.duration() + gap };
+                    path.add(CameraKeyframe { time, position, look_at: position + forward * 10. });
+                    Ok(format!("keyframe {} at {}s", path.keyframes.len(), time))
+                }
+                ["play"] | ["play", "loop"] => {
+                    let playback = if args.len() == 2 { PathPlayback::Loop } else { PathPlayback::OneShot };
+                    path.play(playback);
+                    if !path.playing { return Err("need at least two keyframes".to_string()); }
+                    // out of the way, and so the next key pressed stops the path rather than typing
+                    if let Some(mut console) = resources.get_mut::<Console>() { console.open = false; }
+                    Ok(format!("playing {:?}, press any key to stop", playback))
+                }
+                ["clear"] => {
+                    *path = CameraPath::default();
+                    Ok("camera path cleared".to_string())
+                }
+                _ => Err("expected add, play or clear".to_string()),
+            }
+        })
+        .register("vsync", "vsync <on|off>", |args, _world, resources| {
+            let vsync = match args {
+                ["on"] => true,
+                ["off"] => false,
+                _ => return Err("expected on or off".to_string()),
+            };
+            display::set_vsync(resources, vsync);
+            Ok(format!("vsync {}", if vsync { "on" } else { "off" }))
+        })
+        .register("maxfps", "maxfps <n|off>", |args, _world, resources| {
+            let max_fps = match args {
+                ["off"] => None,
+                _ => Some(parse_floats::<1>(args)?[0].max(MIN_MAX_FPS)),
+            };
+            resources.get_mut::<WindowSettings>().ok_or("no window settings")?.max_fps = max_fps;
+            Ok(max_fps.map_or("frame rate uncapped".to_string(), |max_fps| format!("frame rate capped at {}", max_fps)))
+        });
+}
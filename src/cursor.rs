@@ -0,0 +1,154 @@
+use bevy::{
+    prelude::*,
+    render::camera::Camera,
+};
+use crate::terrain::TerrainHeight;
+
+// how far a ray looks for the terrain before giving up
+const MAX_TERRAIN_DISTANCE: f32 = 1000.;
+
+#[derive(Default)]
+pub struct CursorPosition(pub Option<Vec2>);
+
+#[derive(Default)]
+struct CursorState {
+    cursor_moved_event_reader: EventReader<CursorMoved>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    // cursor is in window pixels with the origin at the bottom left, as bevy reports it
+    pub fn from_cursor(cursor: Vec2, window_size: Vec2, camera_transform: &Mat4, projection: &Mat4) -> Ray {
+        let ndc = cursor / window_size * 2. - Vec2::one();
+        let ndc_to_world = *camera_transform * projection.inverse();
+        let near = unproject(&ndc_to_world, ndc.extend(0.));
+        let far = unproject(&ndc_to_world, ndc.extend(1.));
+        Ray {
+            origin: near,
+            direction: (far - near).normalize(),
+        }
+    }
+
+    pub fn intersect_ground(&self, height: f32) -> Option<Vec3> {
+        if self.direction.y().abs() < f32::EPSILON { return None; }
+
+        let distance = (height - self.origin.y()) / self.direction.y();
+        if distance < 0. { return None; }
+        Some(self.origin + self.direction * distance)
+    }
+
+    // flat terrain is a plane, anything else is marched against
+    pub fn intersect_terrain(&self, terrain: &TerrainHeight) -> Option<Vec3> {
+        if terrain.is_flat() { return self.intersect_ground(terrain.height_at(0., 0.)); }
+        terrain
+            .ray_distance(self.origin, self.direction, MAX_TERRAIN_DISTANCE)
+            .map(|distance| self.origin + self.direction * distance)
+    }
+
+    // distance along the ray to the nearest point where it enters the sphere
+    pub fn intersect_sphere(&self, center: Vec3, radius: f32) -> Option<f32> {
+        let to_center = center - self.origin;
+        let along = to_center.dot(self.direction);
+        let miss_squared = to_center.length_squared() - along * along;
+        if miss_squared > radius * radius { return None; }
+
+        let half_chord = (radius * radius - miss_squared).sqrt();
+        let distance = if along - half_chord >= 0. { along - half_chord } else { along + half_chord };
+        if distance < 0. { None } else { Some(distance) }
+    }
+}
+
+fn unproject(ndc_to_world: &Mat4, ndc: Vec3) -> Vec3 {
+    let point = ndc_to_world.mul_vec4(ndc.extend(1.));
+    Vec3::from(point.truncate()) / point.w()
+}
+
+// casts from the given camera through the last known cursor position on the primary window
+pub fn cursor_ray(
+    cursor: &CursorPosition,
+    windows: &Windows,
+    camera_transform: &Transform,
+    camera: &Camera,
+) -> Option<Ray> {
+    let cursor = cursor.0?;
+    let window = windows.get_primary()?;
+    let window_size = Vec2::new(window.width as f32, window.height as f32);
+    Some(Ray::from_cursor(cursor, window_size, &camera_transform.value, &camera.projection_matrix))
+}
+
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<CursorPosition>()
+            .add_system_to_stage(stage::PRE_UPDATE, track_cursor.system());
+    }
+}
+
+fn track_cursor(
+    mut state: Local<CursorState>,
+    cursor_moved_events: Res<Events<CursorMoved>>,
+    mut cursor: ResMut<CursorPosition>,
+) {
+    if let Some(event) = state.cursor_moved_event_reader.latest(&cursor_moved_events) {
+        cursor.0 = Some(event.position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: Vec3, direction: Vec3) -> Ray {
+        Ray { origin, direction: direction.normalize() }
+    }
+
+    #[test]
+    fn ground_is_hit_where_the_ray_crosses_it() {
+        let hit = ray(Vec3::new(0., 10., 0.), Vec3::new(1., -1., 0.)).intersect_ground(0.).unwrap();
+        assert!((hit - Vec3::new(10., 0., 0.)).length() < 1e-4);
+
+        let hit = ray(Vec3::new(2., 5., -3.), -Vec3::unit_y()).intersect_ground(1.).unwrap();
+        assert!((hit - Vec3::new(2., 1., -3.)).length() < 1e-4);
+    }
+
+    #[test]
+    fn ground_is_missed_when_parallel_or_behind() {
+        assert!(ray(Vec3::new(0., 10., 0.), Vec3::unit_x()).intersect_ground(0.).is_none());
+        assert!(ray(Vec3::new(0., 10., 0.), Vec3::unit_y()).intersect_ground(0.).is_none());
+    }
+
+    #[test]
+    fn terrain_is_hit_on_the_slope_not_at_zero() {
+        use bevy::render::texture::TextureFormat;
+
+        // rises from 0 on the left edge to 10 on the right, across 20 units
+        let data = [0u8, 0, 0, 255, 255, 0, 0, 255].iter().cycle().take(16).cloned().collect();
+        let texture = Texture { data, size: Vec2::new(2., 2.), format: TextureFormat::Rgba8UnormSrgb };
+        let terrain = TerrainHeight::from_texture(&texture, 20., 10.).unwrap();
+
+        let hit = ray(Vec3::new(5., 30., 0.), -Vec3::unit_y()).intersect_terrain(&terrain).unwrap();
+        assert!((hit - Vec3::new(5., 7.5, 0.)).length() < 1e-3, "{:?}", hit);
+        let hit = ray(Vec3::new(-10., 20., 0.), Vec3::new(1., -1., 0.)).intersect_terrain(&terrain).unwrap();
+        assert!((hit.y() - terrain.height_at(hit.x(), hit.z())).abs() < 1e-3, "{:?}", hit);
+        assert!(ray(Vec3::new(0., 30., 0.), Vec3::unit_y()).intersect_terrain(&terrain).is_none());
+
+        let hit = ray(Vec3::new(3., 10., 0.), -Vec3::unit_y()).intersect_terrain(&TerrainHeight::flat()).unwrap();
+        assert!((hit - Vec3::new(3., 0., 0.)).length() < 1e-4);
+    }
+
+    #[test]
+    fn sphere_is_hit_at_the_near_side() {
+        let ray = ray(Vec3::zero(), Vec3::unit_z());
+        assert!((ray.intersect_sphere(Vec3::new(0., 0., 10.), 2.).unwrap() - 8.).abs() < 1e-4);
+        // from inside, the way out is the only hit in front
+        assert!((ray.intersect_sphere(Vec3::zero(), 3.).unwrap() - 3.).abs() < 1e-4);
+        assert!(ray.intersect_sphere(Vec3::new(0., 5., 10.), 2.).is_none());
+        assert!(ray.intersect_sphere(Vec3::new(0., 0., -10.), 2.).is_none());
+    }
+}
@@ -0,0 +1,131 @@
+use bevy::{
+    prelude::*,
+    render::camera::Camera,
+};
+use crate::health::Damaged;
+use crate::hud::HUD_FONT_PATH;
+use crate::nameplate::{local_camera_view, world_to_screen, GLYPH_ASPECT};
+use crate::player::MMOPlayer;
+
+const DAMAGE_NUMBER_HEIGHT: f32 = 4.;
+const DAMAGE_NUMBER_RISE_SPEED: f32 = 2.;
+const DAMAGE_NUMBER_LIFETIME: f32 = 1.;
+const DAMAGE_NUMBER_FONT_SIZE: f32 = 22.;
+// the oldest numbers are dropped early past this, so a burst of hits can't pile up entities
+const MAX_DAMAGE_NUMBERS: usize = 64;
+
+// drawn as ui text over a world position like nameplates, see nameplate.rs
+struct DamageNumber {
+    position: Vec3,
+    age: f32,
+}
+
+#[derive(Default)]
+struct DamageNumberState {
+    damaged_event_reader: EventReader<Damaged>,
+}
+
+pub struct DamageNumberPlugin;
+
+impl Plugin for DamageNumberPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(spawn_damage_numbers.system())
+            .add_system(update_damage_numbers.system());
+    }
+}
+
+fn spawn_damage_numbers(
+    mut commands: Commands,
+    mut state: Local<DamageNumberState>,
+    asset_server: Res<AssetServer>,
+    damaged_events: Res<Events<Damaged>>,
+    victim_query: Query<&Transform>,
+) {
+    for damage in state.damaged_event_reader.iter(&damaged_events) {
+        let victim_transform = match victim_query.get::<Transform>(damage.target) {
+            Ok(transform) => transform,
+            Err(_) => continue,
+        };
+        let font = match asset_server.load(HUD_FONT_PATH) {
+            Ok(font) => font,
+            Err(err) => {
+                eprintln!("failed to load damage number font '{}': {}", HUD_FONT_PATH, err);
+                return;
+            }
+        };
+
+        let position = Vec3::from(victim_transform.value.w_axis().truncate()) + Vec3::unit_y() * DAMAGE_NUMBER_HEIGHT;
+        commands
+            .spawn(TextComponents {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                text: Text {
+                    value: format!("{:.0}", damage.amount),
+                    font,
+                    style: TextStyle {
+                        font_size: DAMAGE_NUMBER_FONT_SIZE,
+                        color: Color::rgb(1., 0.85, 0.2),
+                    },
+                },
+                // hidden until it has been placed on screen
+                draw: Draw { is_visible: false, ..Default::default() },
+                ..Default::default()
+            })
+            .with(DamageNumber { position, age: 0. });
+    }
+}
+
+fn update_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    windows: Res<Windows>,
+    mut player_query: Query<&MMOPlayer>,
+    camera_query: Query<(&Transform, &Camera)>,
+    mut number_query: Query<(Entity, &mut DamageNumber, &mut Text, &mut Style, &mut Draw)>,
+) {
+    let window_size = windows
+        .get_primary()
+        .map(|window| Vec2::new(window.width as f32, window.height as f32));
+    let view = local_camera_view(&mut player_query, &camera_query);
+
+    let mut alive = Vec::new();
+    for (entity, mut number, mut text, mut style, mut draw) in &mut number_query.iter() {
+        number.age += time.delta_seconds;
+        if number.age >= DAMAGE_NUMBER_LIFETIME {
+            commands.despawn(entity);
+            continue;
+        }
+        alive.push((entity, number.age));
+
+        *number.position.y_mut() += DAMAGE_NUMBER_RISE_SPEED * time.delta_seconds;
+        text.style.color.a = 1. - number.age / DAMAGE_NUMBER_LIFETIME;
+
+        let screen = match (view, window_size) {
+            (Some((camera_transform, projection)), Some(window_size)) => {
+                world_to_screen(&camera_transform, &projection, window_size, number.position)
+            }
+            _ => None,
+        };
+        let screen = match screen {
+            Some(screen) => screen,
+            None => {
+                draw.is_visible = false;
+                continue;
+            }
+        };
+        draw.is_visible = true;
+
+        let half_width = text.value.chars().count() as f32 * DAMAGE_NUMBER_FONT_SIZE * GLYPH_ASPECT / 2.;
+        style.position.left = Val::Px(screen.x() - half_width);
+        style.position.top = Val::Px(screen.y() - DAMAGE_NUMBER_FONT_SIZE);
+    }
+
+    if alive.len() > MAX_DAMAGE_NUMBERS {
+        alive.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        for &(entity, _) in &alive[..alive.len() - MAX_DAMAGE_NUMBERS] {
+            commands.despawn(entity);
+        }
+    }
+}
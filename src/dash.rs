@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+use crate::health::Invulnerable;
+
+// a short burst of fixed speed in the movement direction, or straight ahead without input.
+// counted down in fixed steps by the movement system, which also starts it
+pub struct Dash {
+    pub cooldown: f32,
+    pub duration: f32,
+    pub speed: f32,
+    pub remaining: f32,
+    pub cooldown_remaining: f32,
+    // world space, a dash keeps going the same way even if the camera turns
+    pub direction: Vec3,
+}
+
+impl Default for Dash {
+    fn default() -> Self {
+        Dash {
+            cooldown: 1.,
+            duration: 0.2,
+            speed: 40.,
+            remaining: 0.,
+            cooldown_remaining: 0.,
+            direction: Vec3::zero(),
+        }
+    }
+}
+
+impl Dash {
+    pub fn dashing(&self) -> bool {
+        self.remaining > 0.
+    }
+
+    pub fn try_start(&mut self, direction: Vec3) -> bool {
+        if self.dashing() || self.cooldown_remaining > 0. { return false; }
+        self.direction = direction;
+        self.remaining = self.duration;
+        self.cooldown_remaining = self.cooldown;
+        true
+    }
+
+    pub fn tick(&mut self, dt: f32) {
+        self.remaining = (self.remaining - dt).max(0.);
+        self.cooldown_remaining = (self.cooldown_remaining - dt).max(0.);
+    }
+}
+
+pub struct DashPlugin;
+
+impl Plugin for DashPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(update_dash_invulnerability.system());
+    }
+}
+
+// players can't be hurt mid dash
+fn update_dash_invulnerability(mut commands: Commands, mut query: Query<(Entity, &Dash, Option<&Invulnerable>)>) {
+    for (entity, dash, invulnerable) in &mut query.iter() {
+        match (dash.dashing(), invulnerable.is_some()) {
+            (true, false) => { commands.insert_one(entity, Invulnerable); }
+            (false, true) => { commands.remove_one::<Invulnerable>(entity); }
+            _ => {}
+        }
+    }
+}
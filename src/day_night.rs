@@ -0,0 +1,122 @@
+use bevy::{prelude::*, render::pass::ClearColor};
+use std::f32::consts::PI;
+
+// far enough that the sun lights the whole ground from roughly the same direction
+const SUN_DISTANCE: f32 = 500.;
+const SKY_LIGHT_HEIGHT: f32 = 1000.;
+
+const NOON_COLOR: Color = Color::rgb(1.0, 0.96, 0.9);
+const SUNSET_COLOR: Color = Color::rgb(1.0, 0.5, 0.2);
+const DAY_AMBIENT: Color = Color::rgb(0.25, 0.28, 0.32);
+const NIGHT_AMBIENT: Color = Color::rgb(0.03, 0.03, 0.06);
+const DAY_SKY: Color = Color::rgb(0.5, 0.7, 0.9);
+const NIGHT_SKY: Color = Color::rgb(0.01, 0.01, 0.03);
+
+// phase runs from 0 to 1 over a day: 0 is midnight, 0.25 sunrise, 0.5 noon and 0.75 sunset.
+// set paused to freeze the cycle, e.g. with set_phase to hold a particular time for screenshots
+pub struct TimeOfDay {
+    pub phase: f32,
+    pub day_length: f32,
+    pub paused: bool,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        TimeOfDay {
+            phase: 0.35,
+            day_length: 600.,
+            paused: false,
+        }
+    }
+}
+
+impl TimeOfDay {
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase.rem_euclid(1.);
+    }
+
+    // height of the sun above the horizon, -1 at midnight to 1 at noon
+    fn sun_elevation(&self) -> f32 {
+        self.sun_angle().sin()
+    }
+
+    fn sun_angle(&self) -> f32 {
+        (self.phase - 0.25) * 2. * PI
+    }
+
+    // 0 for night and 1 for day, faded a little either side of the horizon so dusk
+    // isn't an instant cut
+    pub fn daylight(&self) -> f32 {
+        ((self.sun_elevation() + 0.2) / 0.4).clamp(0., 1.)
+    }
+}
+
+struct Sun;
+
+// the forward shader's ambient term is fixed, so a dim light straight overhead stands
+// in for sky light and is what the cycle brightens and darkens
+struct SkyLight;
+
+pub struct DayNightPlugin;
+
+impl Plugin for DayNightPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<TimeOfDay>()
+            .add_startup_system(spawn_lights.system())
+            .add_system(advance_time_of_day.system())
+            .add_system(update_lights.system());
+    }
+}
+
+fn spawn_lights(mut commands: Commands) {
+    commands
+        .spawn(LightComponents::default())
+        .with(Sun)
+        .spawn(LightComponents {
+            translation: Translation::new(0., SKY_LIGHT_HEIGHT, 0.),
+            ..Default::default()
+        })
+        .with(SkyLight);
+}
+
+fn advance_time_of_day(time: Res<Time>, mut time_of_day: ResMut<TimeOfDay>) {
+    if time_of_day.paused || time_of_day.day_length <= 0. { return; }
+
+    let phase = time_of_day.phase + time.delta_seconds / time_of_day.day_length;
+    time_of_day.set_phase(phase);
+}
+
+pub fn mix(from: Color, to: Color, t: f32) -> Color {
+    let t = t.clamp(0., 1.);
+    Color::rgb(
+        from.r + (to.r - from.r) * t,
+        from.g + (to.g - from.g) * t,
+        from.b + (to.b - from.b) * t,
+    )
+}
+
+fn update_lights(
+    time_of_day: Res<TimeOfDay>,
+    mut clear_color: ResMut<ClearColor>,
+    mut sun_query: Query<With<Sun, (&mut Light, &mut Translation)>>,
+    mut sky_query: Query<With<SkyLight, &mut Light>>,
+) {
+    let angle = time_of_day.sun_angle();
+    let elevation = time_of_day.sun_elevation();
+    let daylight = elevation.max(0.);
+
+    // the sun rises in the east (+x) and is tilted slightly south so noon isn't straight down
+    let sun_position = Vec3::new(angle.cos(), angle.sin(), 0.3).normalize() * SUN_DISTANCE;
+    let sun_color = mix(Color::BLACK, mix(SUNSET_COLOR, NOON_COLOR, daylight.sqrt()), daylight * 4.);
+
+    for (mut light, mut translation) in &mut sun_query.iter() {
+        translation.0 = sun_position;
+        light.color = sun_color;
+    }
+
+    let sky = time_of_day.daylight();
+    for mut light in &mut sky_query.iter() {
+        light.color = mix(NIGHT_AMBIENT, DAY_AMBIENT, sky);
+    }
+    clear_color.0 = mix(NIGHT_SKY, DAY_SKY, sky);
+}
@@ -0,0 +1,266 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use crate::network::PlayerSnapshot;
+
+// positions go over the wire in whole steps of this many world units
+const POSITION_STEP: f32 = 0.01;
+const YAW_STEPS: f32 = 65536.;
+// sent states kept around as possible baselines, and received ones to decode against.
+// an ack older than this is too late to be any use
+const MAX_BASELINES: usize = 32;
+
+const CHANGED_X: u8 = 1;
+const CHANGED_Y: u8 = 1 << 1;
+const CHANGED_Z: u8 = 1 << 2;
+const CHANGED_YAW: u8 = 1 << 3;
+
+// a snapshot as it is sent, so both ends diff exactly the same numbers
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuantizedSnapshot {
+    pub pos: [i32; 3],
+    pub yaw: u16,
+}
+
+impl QuantizedSnapshot {
+    pub fn quantize(snapshot: PlayerSnapshot) -> Self {
+        let step = |value: f32| (value / POSITION_STEP).round() as i32;
+        let yaw = (snapshot.yaw.rem_euclid(2. * PI) / (2. * PI) * YAW_STEPS).round() as u32 % YAW_STEPS as u32;
+        QuantizedSnapshot {
+            pos: [step(snapshot.pos.x()), step(snapshot.pos.y()), step(snapshot.pos.z())],
+            yaw: yaw as u16,
+        }
+    }
+
+    pub fn snapshot(&self) -> PlayerSnapshot {
+        let [x, y, z] = self.pos;
+        PlayerSnapshot {
+            pos: Vec3::new(x as f32, y as f32, z as f32) * POSITION_STEP,
+            yaw: self.yaw as f32 / YAW_STEPS * 2. * PI,
+        }
+    }
+}
+
+// the packet is the sequence, then which sent state it's relative to (0 for none, so
+// from zero, otherwise that state's sequence + 1), a byte of flags for the fields that
+// changed and then the change to each of them. numbers are zigzag varints, so a player
+// that barely moved costs a byte or two per axis and one that stood still costs nothing
+pub fn encode(sequence: u32, baseline: Option<(u32, QuantizedSnapshot)>, current: QuantizedSnapshot) -> Vec<u8> {
+    let (baseline_tag, from) = match baseline {
+        Some((baseline_sequence, from)) => (baseline_sequence as u64 + 1, from),
+        None => (0, QuantizedSnapshot::default()),
+    };
+    let mut bytes = Vec::new();
+    write_varint(&mut bytes, sequence as u64);
+    write_varint(&mut bytes, baseline_tag);
+
+    let changes = [
+        (CHANGED_X, current.pos[0] as i64 - from.pos[0] as i64),
+        (CHANGED_Y, current.pos[1] as i64 - from.pos[1] as i64),
+        (CHANGED_Z, current.pos[2] as i64 - from.pos[2] as i64),
+        // the short way round, a turn across north is a small change rather than a huge one
+        (CHANGED_YAW, current.yaw.wrapping_sub(from.yaw) as i16 as i64),
+    ];
+    let flags = changes.iter().filter(|&&(_, change)| change != 0).fold(0, |flags, &(flag, _)| flags | flag);
+    bytes.push(flags);
+    for &(_, change) in changes.iter().filter(|&&(_, change)| change != 0) {
+        write_varint(&mut bytes, zigzag(change));
+    }
+    bytes
+}
+
+// the sequence and the baseline tag, read ahead of the rest so the receiver can look
+// the baseline up before decoding against it
+pub fn read_header(bytes: &[u8]) -> Result<(u32, Option<u32>), String> {
+    let mut cursor = 0;
+    let sequence = read_varint(bytes, &mut cursor)? as u32;
+    let baseline = read_varint(bytes, &mut cursor)?;
+    Ok((sequence, baseline.checked_sub(1).map(|baseline| baseline as u32)))
+}
+
+pub fn decode(bytes: &[u8], baseline: Option<QuantizedSnapshot>) -> Result<QuantizedSnapshot, String> {
+    let mut cursor = 0;
+    read_varint(bytes, &mut cursor)?;
+    let wants_baseline = read_varint(bytes, &mut cursor)? != 0;
+    let mut state = match (wants_baseline, baseline) {
+        (true, Some(baseline)) => baseline,
+        (true, None) => return Err("delta against a baseline that isn't known here".to_string()),
+        (false, _) => QuantizedSnapshot::default(),
+    };
+    let flags = *bytes.get(cursor).ok_or("snapshot ends before its flags")?;
+    cursor += 1;
+
+    for (axis, &flag) in [CHANGED_X, CHANGED_Y, CHANGED_Z].iter().enumerate() {
+        if flags & flag == 0 { continue; }
+        let change = unzigzag(read_varint(bytes, &mut cursor)?);
+        state.pos[axis] = (state.pos[axis] as i64 + change) as i32;
+    }
+    if flags & CHANGED_YAW != 0 {
+        let change = unzigzag(read_varint(bytes, &mut cursor)?);
+        state.yaw = state.yaw.wrapping_add(change as u16);
+    }
+    if cursor != bytes.len() { return Err("trailing bytes after snapshot".to_string()); }
+    Ok(state)
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], cursor: &mut usize) -> Result<u64, String> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*cursor).ok_or("snapshot ends in the middle of a number")?;
+        *cursor += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 { return Ok(value); }
+    }
+    Err("number in snapshot is too long".to_string())
+}
+
+// the sending end. every snapshot is a delta against the newest one the other end has
+// acknowledged, or sent whole until something has been
+#[derive(Default)]
+pub struct SnapshotEncoder {
+    sequence: u32,
+    sent: VecDeque<(u32, QuantizedSnapshot)>,
+    acknowledged: Option<(u32, QuantizedSnapshot)>,
+}
+
+impl SnapshotEncoder {
+    pub fn encode(&mut self, snapshot: PlayerSnapshot) -> Vec<u8> {
+        let current = QuantizedSnapshot::quantize(snapshot);
+        // a baseline that has aged out here has aged out at the other end too
+        let baseline = self.acknowledged.filter(|(acknowledged, _)| self.sent.iter().any(|(sent, _)| sent == acknowledged));
+        let bytes = encode(self.sequence, baseline, current);
+        self.sent.push_back((self.sequence, current));
+        if self.sent.len() > MAX_BASELINES { self.sent.pop_front(); }
+        self.sequence = self.sequence.wrapping_add(1);
+        bytes
+    }
+
+    // acks can arrive out of order, an older one than the current baseline changes nothing
+    pub fn acknowledge(&mut self, sequence: u32) {
+        if self.acknowledged.is_some_and(|(acknowledged, _)| acknowledged.wrapping_sub(sequence) as i32 >= 0) { return; }
+        if let Some(&sent) = self.sent.iter().find(|(sent, _)| *sent == sequence) {
+            self.acknowledged = Some(sent);
+        }
+    }
+}
+
+// the receiving end, one per remote sender. decode hands back the sequence to acknowledge
+#[derive(Default)]
+pub struct SnapshotDecoder {
+    received: VecDeque<(u32, QuantizedSnapshot)>,
+}
+
+impl SnapshotDecoder {
+    pub fn decode(&mut self, bytes: &[u8]) -> Result<(u32, PlayerSnapshot), String> {
+        let (sequence, baseline) = read_header(bytes)?;
+        let baseline = match baseline {
+            Some(baseline) => match self.received.iter().find(|(received, _)| *received == baseline) {
+                Some(&(_, state)) => Some(state),
+                None => return Err(format!("baseline {} for snapshot {} has already been dropped", baseline, sequence)),
+            },
+            None => None,
+        };
+        let state = decode(bytes, baseline)?;
+        self.received.push_back((sequence, state));
+        if self.received.len() > MAX_BASELINES { self.received.pop_front(); }
+        Ok((sequence, state.snapshot()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(x: f32, y: f32, z: f32, yaw: f32) -> PlayerSnapshot {
+        PlayerSnapshot { pos: Vec3::new(x, y, z), yaw }
+    }
+
+    fn assert_close(decoded: PlayerSnapshot, sent: PlayerSnapshot) {
+        assert!((decoded.pos - sent.pos).abs().max_element() <= POSITION_STEP / 2. + 1e-3, "{:?} vs {:?}", decoded, sent);
+        let yaw = (decoded.yaw - sent.yaw.rem_euclid(2. * PI)).abs();
+        assert!(yaw.min(2. * PI - yaw) <= PI / YAW_STEPS + 1e-4, "{:?} vs {:?}", decoded, sent);
+    }
+
+    #[test]
+    fn packets_round_trip_against_their_baseline() {
+        let from = QuantizedSnapshot::quantize(snapshot(10., 2., -7.5, 1.));
+        let cases = [
+            ("no change", from),
+            ("small change", QuantizedSnapshot::quantize(snapshot(10.05, 2., -7.46, 1.02))),
+            ("teleport", QuantizedSnapshot::quantize(snapshot(-9000., 350., 12000.5, 5.9))),
+            ("across north", QuantizedSnapshot::quantize(snapshot(10., 2., -7.5, -0.01))),
+        ];
+        for &(name, current) in cases.iter() {
+            let bytes = encode(7, Some((3, from)), current);
+            assert_eq!(read_header(&bytes), Ok((7, Some(3))), "{}", name);
+            assert_eq!(decode(&bytes, Some(from)), Ok(current), "{}", name);
+
+            // and sent whole, as it is before anything has been acknowledged
+            let bytes = encode(7, None, current);
+            assert_eq!(read_header(&bytes), Ok((7, None)), "{}", name);
+            assert_eq!(decode(&bytes, None), Ok(current), "{}", name);
+        }
+
+        // standing still is just the header and an empty set of flags
+        assert_eq!(encode(7, Some((3, from)), from).len(), 3);
+        assert!(decode(&encode(7, Some((3, from)), from), None).is_err());
+    }
+
+    #[test]
+    fn encoder_and_decoder_stay_in_step() {
+        let mut encoder = SnapshotEncoder::default();
+        let mut decoder = SnapshotDecoder::default();
+        let path = [
+            snapshot(30., 1., -20., 0.5),
+            snapshot(30., 1., -20., 0.5),
+            snapshot(30.1, 1., -19.95, 0.6),
+            snapshot(30.2, 1.3, -19.9, 0.7),
+            snapshot(-5000., 20., 8000., 4.),
+            snapshot(-5000., 20., 8000., 4.),
+        ];
+        let mut sizes = Vec::new();
+        for &sent in path.iter() {
+            let bytes = encoder.encode(sent);
+            sizes.push(bytes.len());
+            let (sequence, decoded) = decoder.decode(&bytes).unwrap();
+            assert_close(decoded, sent);
+            encoder.acknowledge(sequence);
+        }
+
+        // once acked, no change costs less than the same state sent whole, and a small
+        // change less than a teleport
+        assert!(sizes[1] < sizes[0]);
+        assert!(sizes[2] < sizes[4]);
+        assert!(sizes[5] < sizes[4]);
+    }
+
+    #[test]
+    fn unacknowledged_snapshots_still_decode() {
+        let mut encoder = SnapshotEncoder::default();
+        let mut decoder = SnapshotDecoder::default();
+        let (sequence, _) = decoder.decode(&encoder.encode(snapshot(1., 0., 1., 0.))).unwrap();
+        encoder.acknowledge(sequence);
+        // none of these are acked, each is a delta against the first
+        for step in 1..=5 {
+            let sent = snapshot(1. + step as f32, 0., 1., 0.);
+            let (_, decoded) = decoder.decode(&encoder.encode(sent)).unwrap();
+            assert_close(decoded, sent);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::{
+    prelude::*,
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+};
+use crate::hud::HUD_FONT_PATH;
+use crate::player::KeyBindings;
+
+// a few times a second, every frame is too jittery to read
+const OVERLAY_REFRESH_SECONDS: f32 = 0.25;
+
+struct DiagnosticsText;
+
+pub struct DiagnosticsOverlayPlugin;
+
+impl Plugin for DiagnosticsOverlayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
+            .add_startup_system(spawn_diagnostics_overlay.system())
+            .add_system(update_diagnostics_overlay.system());
+    }
+}
+
+fn spawn_diagnostics_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = match asset_server.load(HUD_FONT_PATH) {
+        Ok(font) => font,
+        Err(err) => {
+            eprintln!("failed to load diagnostics font '{}': {}, overlay disabled", HUD_FONT_PATH, err);
+            return;
+        }
+    };
+
+    commands
+        .spawn(TextComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(5.0),
+                    top: Val::Px(5.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font,
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            },
+            // off until toggled on
+            draw: Draw { is_visible: false, ..Default::default() },
+            ..Default::default()
+        })
+        .with(DiagnosticsText);
+}
+
+fn update_diagnostics_overlay(
+    mut since_refresh: Local<f32>,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    diagnostics: Res<Diagnostics>,
+    mut text_query: Query<(&DiagnosticsText, &mut Text, &mut Draw)>,
+) {
+    let toggle = keyboard_input.just_pressed(bindings.toggle_diagnostics);
+    *since_refresh += time.delta_seconds;
+    // refresh straight away when shown, so it doesn't open on stale numbers
+    let refresh = *since_refresh >= OVERLAY_REFRESH_SECONDS || toggle;
+    if refresh { *since_refresh = 0.; }
+
+    for (_marker, mut text, mut draw) in &mut text_query.iter() {
+        if toggle { draw.is_visible = !draw.is_visible; }
+        if !draw.is_visible || !refresh { continue; }
+
+        let fps = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS).and_then(|fps| fps.average());
+        let frame_time = diagnostics.get(FrameTimeDiagnosticsPlugin::FRAME_TIME).and_then(|frame_time| frame_time.average());
+        text.value = match (fps, frame_time) {
+            (Some(fps), Some(frame_time)) => format!("fps: {:.0}  frame: {:.2}ms", fps, frame_time * 1000.),
+            _ => "fps: -  frame: -".to_string(),
+        };
+    }
+}
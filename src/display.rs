@@ -0,0 +1,131 @@
+use bevy::{
+    prelude::*,
+    window::{WindowId, WindowMode, WindowResized},
+    winit::WinitWindows,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use winit::{dpi::PhysicalSize, window::Fullscreen};
+use crate::player::KeyBindings;
+
+pub const WINDOW_TITLE: &str = "tersia";
+// movement runs at most 5 fixed steps a frame and drops the rest, so below 12fps the
+// simulation slows down. caps are kept above that, and anything at or above it leaves
+// simulation speed alone since the fixed steps bank real time, not frames
+pub const MIN_MAX_FPS: f32 = 15.;
+
+// width and height are the windowed size, fullscreen always covers the whole monitor
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    // none leaves the frame rate to vsync, or uncapped without it
+    pub max_fps: Option<f32>,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            width: 1280,
+            height: 720,
+            fullscreen: false,
+            vsync: true,
+            max_fps: None,
+        }
+    }
+}
+
+impl WindowSettings {
+    // has to be added before the default plugins, which create the window from it
+    pub fn descriptor(&self) -> WindowDescriptor {
+        WindowDescriptor {
+            title: WINDOW_TITLE.to_string(),
+            width: self.width.max(1),
+            height: self.height.max(1),
+            mode: self.mode(),
+            vsync: self.vsync,
+            ..Default::default()
+        }
+    }
+
+    fn mode(&self) -> WindowMode {
+        if self.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed }
+    }
+}
+
+pub struct DisplayPlugin;
+
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(toggle_fullscreen.system())
+            .add_system(track_window_size.system())
+            .add_system_to_stage(stage::LAST, limit_frame_rate.system());
+    }
+}
+
+// bevy picks the present mode when it builds the swap chain, which it rebuilds on resize,
+// so a resize to the same size is how the change gets applied
+pub fn set_vsync(resources: &mut Resources, vsync: bool) {
+    if let Some(mut settings) = resources.get_mut::<WindowSettings>() { settings.vsync = vsync; }
+    let size = resources.get_mut::<Windows>().and_then(|mut windows| {
+        let window = windows.get_mut(WindowId::primary())?;
+        window.vsync = vsync;
+        Some((window.width, window.height))
+    });
+    if let (Some((width, height)), Some(mut resized_events)) = (size, resources.get_mut::<Events<WindowResized>>()) {
+        resized_events.send(WindowResized { id: WindowId::primary(), width: width as usize, height: height as usize });
+    }
+}
+
+// bevy 0.1 only reads the window mode when creating the window, so the switch goes through winit.
+// winit reports the new size as a resize, which is what updates the camera projections
+fn toggle_fullscreen(
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    winit_windows: Res<WinitWindows>,
+    mut settings: ResMut<WindowSettings>,
+    mut windows: ResMut<Windows>,
+) {
+    if !keyboard_input.just_pressed(bindings.toggle_fullscreen) { return; }
+    let winit_window = match winit_windows.get_window(WindowId::primary()) {
+        Some(window) => window,
+        None => return,
+    };
+
+    settings.fullscreen = !settings.fullscreen;
+    if settings.fullscreen {
+        winit_window.set_fullscreen(Some(Fullscreen::Borderless(winit_window.current_monitor())));
+    } else {
+        winit_window.set_fullscreen(None);
+        // a window that started fullscreen has no windowed size of its own to go back to
+        winit_window.set_inner_size(PhysicalSize::new(settings.width.max(1), settings.height.max(1)));
+    }
+    if let Some(window) = windows.get_mut(WindowId::primary()) {
+        window.mode = settings.mode();
+    }
+}
+
+// remember the windowed size the player dragged the window to, so it is saved with the settings
+fn track_window_size(
+    mut resized_reader: Local<EventReader<WindowResized>>,
+    resized_events: Res<Events<WindowResized>>,
+    mut settings: ResMut<WindowSettings>,
+) {
+    for event in resized_reader.iter(&resized_events) {
+        if !event.id.is_primary() || settings.fullscreen { continue; }
+        settings.width = event.width as u32;
+        settings.height = event.height as u32;
+    }
+}
+
+// sleeps off whatever is left of the frame's share of a second at the end of the frame
+fn limit_frame_rate(mut frame_start: Local<Option<Instant>>, settings: Res<WindowSettings>) {
+    if let (Some(max_fps), Some(start)) = (settings.max_fps, *frame_start) {
+        let frame = Duration::from_secs_f32(1. / max_fps.max(MIN_MAX_FPS));
+        if let Some(remaining) = frame.checked_sub(start.elapsed()) { std::thread::sleep(remaining); }
+    }
+    *frame_start = Some(Instant::now());
+}
@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use crate::combat::Hit;
+use crate::health::Health;
+use crate::speed_modifiers::{SpeedModifierKind, SpeedModifiers};
+
+const LEVEL_SPEED_SOURCE: &str = "level";
+
+// xp counts up from zero again at each level
+pub struct Experience {
+    pub current: u32,
+    pub level: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Experience { current: 0, level: 1 }
+    }
+}
+
+// the curve is a function so it can be swapped out without touching the level up logic
+pub struct Leveling {
+    // xp needed to go from the given level to the next
+    pub xp_to_next: fn(u32) -> u32,
+    pub xp_per_damage: f32,
+    pub speed_per_level: f32,
+    pub health_per_level: f32,
+}
+
+impl Default for Leveling {
+    fn default() -> Self {
+        Leveling {
+            xp_to_next: |level| 100 * level * level,
+            xp_per_damage: 1.,
+            speed_per_level: 0.25,
+            health_per_level: 10.,
+        }
+    }
+}
+
+impl Leveling {
+    // a curve that gives zero for some level would level the player up forever
+    pub fn xp_to_next(&self, level: u32) -> u32 {
+        (self.xp_to_next)(level).max(1)
+    }
+}
+
+#[allow(dead_code)]
+pub struct LevelUp {
+    pub entity: Entity,
+    pub level: u32,
+}
+
+#[derive(Default)]
+struct ExperienceState {
+    hit_event_reader: EventReader<Hit>,
+}
+
+pub struct ExperiencePlugin;
+
+impl Plugin for ExperiencePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Leveling>()
+            .add_event::<LevelUp>()
+            .add_system(gain_experience.system());
+    }
+}
+
+// damage dealt is paid out as xp to whoever dealt it. each level up makes the player a
+// little faster and tougher for good
+fn gain_experience(
+    mut state: Local<ExperienceState>,
+    leveling: Res<Leveling>,
+    hit_events: Res<Events<Hit>>,
+    mut level_up_events: ResMut<Events<LevelUp>>,
+    query: Query<(&mut Experience, &mut SpeedModifiers, &mut Health)>,
+) {
+    for hit in state.hit_event_reader.iter(&hit_events) {
+        let mut experience = match query.get_mut::<Experience>(hit.attacker) {
+            Ok(experience) => experience,
+            Err(_) => continue,
+        };
+        experience.current += (hit.damage * leveling.xp_per_damage).round() as u32;
+
+        let mut levels_gained = 0;
+        while experience.current >= leveling.xp_to_next(experience.level) {
+            experience.current -= leveling.xp_to_next(experience.level);
+            experience.level += 1;
+            levels_gained += 1;
+            level_up_events.send(LevelUp { entity: hit.attacker, level: experience.level });
+        }
+        if levels_gained == 0 { continue; }
+
+        if let Ok(mut modifiers) = query.get_mut::<SpeedModifiers>(hit.attacker) {
+            apply_level_speed(&leveling, experience.level, &mut modifiers);
+        }
+        if let Ok(mut health) = query.get_mut::<Health>(hit.attacker) {
+            health.max += leveling.health_per_level * levels_gained as f32;
+            health.current += leveling.health_per_level * levels_gained as f32;
+        }
+    }
+}
+
+// replaces whatever bonus an earlier level gave
+pub fn apply_level_speed(leveling: &Leveling, level: u32, modifiers: &mut SpeedModifiers) {
+    modifiers.remove(LEVEL_SPEED_SOURCE);
+    let bonus = leveling.speed_per_level * level.saturating_sub(1) as f32;
+    modifiers.add(LEVEL_SPEED_SOURCE, SpeedModifierKind::Add(bonus));
+}
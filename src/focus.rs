@@ -0,0 +1,68 @@
+use bevy::{
+    prelude::*,
+    render::camera::Camera,
+};
+use crate::cursor::{cursor_ray, CursorPosition};
+use crate::game_state::GameState;
+use crate::interest::Distant;
+use crate::player::{InputSource, MMOPlayer};
+
+// entities that can be middle clicked to have the camera orbit them, picked by a bounding sphere
+pub struct Focusable {
+    pub radius: f32,
+}
+
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(set_camera_focus.system());
+    }
+}
+
+// middle click focuses the nearest focusable under the cursor, clicking empty space
+// or the player itself clears it back to following the player
+fn set_camera_focus(
+    mouse_button_input: Res<Input<MouseButton>>,
+    game_state: Res<GameState>,
+    cursor: Res<CursorPosition>,
+    windows: Res<Windows>,
+    mut player_query: Query<(Entity, &mut MMOPlayer)>,
+    camera_query: Query<(&Transform, &Camera)>,
+    mut focusable_query: Query<Without<Distant, (Entity, &Focusable, &Transform)>>,
+) {
+    if *game_state != GameState::Playing || !mouse_button_input.just_pressed(MouseButton::Middle) { return; }
+
+    for (player_entity, mut player) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let camera_entity = match player.camera_entity {
+            Some(camera_entity) => camera_entity,
+            None => continue,
+        };
+
+        let camera_transform = camera_query.get::<Transform>(camera_entity);
+        let camera = camera_query.get::<Camera>(camera_entity);
+        let ray = match (camera_transform, camera) {
+            (Ok(camera_transform), Ok(camera)) => cursor_ray(&cursor, &windows, &camera_transform, &camera),
+            _ => None,
+        };
+        let ray = match ray {
+            Some(ray) => ray,
+            None => continue,
+        };
+
+        let mut nearest: Option<(Entity, f32)> = None;
+        for (entity, focusable, transform) in &mut focusable_query.iter() {
+            let center = Vec3::from(transform.value.w_axis().truncate());
+            if let Some(distance) = ray.intersect_sphere(center, focusable.radius) {
+                if nearest.is_none_or(|(_, nearest)| distance < nearest) {
+                    nearest = Some((entity, distance));
+                }
+            }
+        }
+
+        player.camera_focus = nearest
+            .map(|(entity, _)| entity)
+            .filter(|&entity| entity != player_entity);
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::{prelude::*, asset::LoadState};
+use std::collections::HashMap;
+use crate::game_state::GameState;
+use crate::network::RemotePlayer;
+use crate::player::{MMOPlayer, Velocity};
+
+pub const FOOTSTEP_SOUND_PATH: &str = "res/footstep.mp3";
+// ground covered per step, so the interval is this over the speed and sprinting steps faster
+const STRIDE_LENGTH: f32 = 2.2;
+const MIN_STEP_INTERVAL: f32 = 0.18;
+// slower than this counts as standing still
+const MIN_STEP_SPEED: f32 = 0.5;
+
+pub struct FootstepSound(pub Handle<AudioSource>);
+
+pub struct FootstepPlugin;
+
+impl Plugin for FootstepPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(play_footsteps.system())
+            .add_system(report_missing_footstep_sound.system());
+    }
+}
+
+// the repo doesn't ship a clip, so say once why the footsteps are silent
+fn report_missing_footstep_sound(mut reported: Local<bool>, asset_server: Res<AssetServer>, sound: Res<FootstepSound>) {
+    if *reported { return; }
+    match asset_server.get_load_state(sound.0) {
+        Some(LoadState::Loading(_)) => return,
+        Some(LoadState::Loaded(_)) => {}
+        _ => eprintln!("failed to load the footstep sound '{}', footsteps will be silent", FOOTSTEP_SOUND_PATH),
+    }
+    *reported = true;
+}
+
+fn play_footsteps(
+    // seconds until each local player's next step
+    mut until_next_step: Local<HashMap<Entity, f32>>,
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    sound: Res<FootstepSound>,
+    audio_sources: Res<Assets<AudioSource>>,
+    audio_output: Res<AudioOutput>,
+    mut player_query: Query<Without<RemotePlayer, (Entity, &MMOPlayer, &Velocity)>>,
+) {
+    if *game_state != GameState::Playing { return; }
+    // the output queues anything not loaded yet and retries every frame forever, so a missing
+    // file would pile up steps that never play
+    let loaded = audio_sources.get(&sound.0).is_some();
+
+    for (entity, player, velocity) in &mut player_query.iter() {
+        let speed = Vec2::new(velocity.0.x(), velocity.0.z()).length();
+        if !player.grounded || player.swimming || speed < MIN_STEP_SPEED {
+            // the first step lands straight away when setting off again
+            until_next_step.remove(&entity);
+            continue;
+        }
+
+        let interval = (STRIDE_LENGTH / speed).max(MIN_STEP_INTERVAL);
+        let until_next = until_next_step.entry(entity).or_insert(0.);
+        *until_next -= time.delta_seconds;
+        if *until_next > 0. { continue; }
+        // carry over the overshoot so steps stay evenly spaced at low frame rates
+        *until_next = (*until_next + interval).max(0.);
+        if loaded { audio_output.play(sound.0); }
+    }
+}
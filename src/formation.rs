@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FormationKind {
+    // one rank side by side, across the direction of travel
+    Line,
+    // as close to square as the group allows, filled rank by rank from the front
+    Grid,
+}
+
+impl FormationKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "line" => Some(FormationKind::Line),
+            "grid" => Some(FormationKind::Grid),
+            _ => None,
+        }
+    }
+}
+
+// how group move orders lay units out around the clicked point
+pub struct Formation {
+    pub kind: FormationKind,
+    // distance between neighbouring units
+    pub spacing: f32,
+}
+
+impl Default for Formation {
+    fn default() -> Self {
+        Formation {
+            kind: FormationKind::Grid,
+            spacing: 2.5,
+        }
+    }
+}
+
+impl Formation {
+    // one slot per unit, in no particular order, centred on the target and facing along
+    // `forward`. a single unit goes exactly to the target
+    pub fn slots(&self, target: Vec3, forward: Vec3, count: usize) -> Vec<Vec3> {
+        let forward = Vec3::new(forward.x(), 0., forward.z());
+        let forward = if forward.length_squared() > f32::EPSILON { forward.normalize() } else { Vec3::unit_z() };
+        let right = Vec3::unit_y().cross(forward);
+
+        let columns = match self.kind {
+            FormationKind::Line => count,
+            FormationKind::Grid => (count as f32).sqrt().ceil() as usize,
+        }.max(1);
+        let rows = count.div_ceil(columns);
+
+        (0..count)
+            .map(|index| {
+                let (row, column) = (index / columns, index % columns);
+                // the last rank can be short, centre it rather than leaving it hanging off one side
+                let in_row = if row == rows - 1 { count - row * columns } else { columns };
+                let across = (column as f32 - (in_row - 1) as f32 / 2.) * self.spacing;
+                let back = (row as f32 - (rows - 1) as f32 / 2.) * self.spacing;
+                target + right * across - forward * back
+            })
+            .collect()
+    }
+}
+
+// pairs each unit with a slot, nearest first, so units don't cross over each other to
+// reach the far side of the formation. returns the slot for each unit, in the same order
+pub fn assign_slots(units: &[Vec3], mut slots: Vec<Vec3>) -> Vec<Vec3> {
+    let mut assigned = vec![Vec3::zero(); units.len()];
+    let mut unassigned: Vec<usize> = (0..units.len()).collect();
+    while let Some(slot) = slots.pop() {
+        let nearest = unassigned
+            .iter()
+            .enumerate()
+            .min_by(|(_, &a), (_, &b)| {
+                (units[a] - slot).length_squared().partial_cmp(&(units[b] - slot).length_squared()).unwrap()
+            })
+            .map(|(position, _)| position);
+        match nearest {
+            Some(position) => assigned[unassigned.swap_remove(position)] = slot,
+            None => break,
+        }
+    }
+    assigned
+}
@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+use crate::player::{KeyBindings, MMOPlayer};
+
+// bevy 0.1 has no state machine, so systems that should stop while paused check this
+// resource themselves and return early
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum GameState {
+    MainMenu,
+    Loading,
+    #[default]
+    Playing,
+    Paused,
+    // rebinding keys, opened from the main menu or the pause menu
+    Controls,
+}
+
+// sent once when loading finishes and play starts, the players and everything else
+// in the world are spawned off it
+pub struct GameStarted;
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GameState>()
+            .add_event::<GameStarted>()
+            .add_system_to_stage(stage::PRE_UPDATE, toggle_pause.system());
+    }
+}
+
+fn toggle_pause(
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut state: ResMut<GameState>,
+    mut player_query: Query<&MMOPlayer>,
+) {
+    if !keyboard_input.just_pressed(bindings.pause) { return; }
+
+    // while locked on, the first press only lets go of the target when both share a key
+    if *state == GameState::Playing && bindings.clear_lock == bindings.pause
+        && player_query.iter().iter().any(|player| player.lock_target.is_some())
+    {
+        return;
+    }
+
+    *state = match *state {
+        GameState::Playing => GameState::Paused,
+        GameState::Paused => GameState::Playing,
+        // the menus have no pause
+        state => state,
+    };
+}
@@ -0,0 +1,157 @@
+use bevy::{
+    prelude::*,
+    render::mesh::VertexAttribute,
+    render::pipeline::PrimitiveTopology,
+};
+use crate::player::{InputSource, KeyBindings, MMOPlayer};
+
+const AXIS_LENGTH: f32 = 5.;
+const TARGET_MARKER_SIZE: f32 = 0.5;
+// lifts the axes off the ground so they aren't hidden inside it
+const AXIS_HEIGHT: f32 = 1.;
+const PATH_HEIGHT: f32 = 0.1;
+const WAYPOINT_MARKER_SIZE: f32 = 0.4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum GizmoKind {
+    Forward,
+    Right,
+    Camera,
+    // the local player's click to move target and queued waypoints, shown even with the
+    // debug gizmos off since it's feedback for the clicks
+    Path,
+}
+
+// one line mesh per colour, rebuilt in world space every frame they are shown
+struct Gizmo {
+    kind: GizmoKind,
+    mesh: Handle<Mesh>,
+}
+
+#[derive(Default)]
+pub struct GizmoSettings {
+    pub visible: bool,
+}
+
+pub struct GizmoPlugin;
+
+impl Plugin for GizmoPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GizmoSettings>()
+            .add_startup_system(spawn_gizmos.system())
+            .add_system(toggle_gizmos.system())
+            .add_system(update_gizmos.system());
+    }
+}
+
+pub fn line_mesh(points: &[Vec3]) -> Mesh {
+    let positions: Vec<[f32; 3]> = points.iter().map(|&point| point.into()).collect();
+    Mesh {
+        primitive_topology: PrimitiveTopology::LineList,
+        attributes: vec![
+            VertexAttribute::normal(vec![[0., 1., 0.]; points.len()]),
+            VertexAttribute::uv(vec![[0., 0.]; points.len()]),
+            VertexAttribute::position(positions),
+        ],
+        indices: Some((0..points.len() as u32).collect()),
+    }
+}
+
+fn spawn_gizmos(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let gizmos = [
+        (GizmoKind::Forward, Color::rgb(0.2, 0.4, 1.0)),
+        (GizmoKind::Right, Color::rgb(1.0, 0.2, 0.2)),
+        (GizmoKind::Camera, Color::rgb(1.0, 0.9, 0.2)),
+        (GizmoKind::Path, Color::rgb(0.9, 0.9, 0.9)),
+    ];
+    for &(kind, color) in gizmos.iter() {
+        // a degenerate line rather than an empty mesh, which would be an empty vertex buffer
+        let mesh = meshes.add(line_mesh(&[Vec3::zero(); 2]));
+        let mut components = PbrComponents {
+            mesh,
+            material: materials.add(StandardMaterial {
+                albedo: color,
+                shaded: false,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        components.draw.is_visible = false;
+        commands.spawn(components).with(Gizmo { kind, mesh });
+    }
+}
+
+fn toggle_gizmos(
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut settings: ResMut<GizmoSettings>,
+) {
+    if keyboard_input.just_pressed(bindings.toggle_gizmos) { settings.visible = !settings.visible; }
+}
+
+fn update_gizmos(
+    settings: Res<GizmoSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut player_query: Query<(&MMOPlayer, &Transform)>,
+    camera_query: Query<&Transform>,
+    mut gizmo_query: Query<(&Gizmo, &mut Draw)>,
+) {
+    let mut forward = Vec::new();
+    let mut right = Vec::new();
+    let mut camera = Vec::new();
+    let mut path = Vec::new();
+    for (player, transform) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let lift = Vec3::unit_y() * PATH_HEIGHT;
+        let mut from = Vec3::from(transform.value.w_axis().truncate()) + lift;
+        for &waypoint in player.move_target.iter().chain(player.waypoints.iter()) {
+            let waypoint = waypoint + lift;
+            path.extend_from_slice(&[from, waypoint]);
+            for &axis in [Vec3::unit_x(), Vec3::unit_z()].iter() {
+                path.extend_from_slice(&[waypoint - axis * WAYPOINT_MARKER_SIZE, waypoint + axis * WAYPOINT_MARKER_SIZE]);
+            }
+            from = waypoint;
+        }
+    }
+    if settings.visible {
+        for (player, transform) in &mut player_query.iter() {
+            let origin = Vec3::from(transform.value.w_axis().truncate()) + Vec3::unit_y() * AXIS_HEIGHT;
+            let forward_axis = Vec3::from(transform.value.z_axis().truncate());
+            let right_axis = -Vec3::from(transform.value.x_axis().truncate());
+            forward.extend_from_slice(&[origin, origin + forward_axis * AXIS_LENGTH]);
+            right.extend_from_slice(&[origin, origin + right_axis * AXIS_LENGTH]);
+
+            let camera_transform = match player.camera_entity.map(|entity| camera_query.get::<Transform>(entity)) {
+                Some(Ok(camera_transform)) => camera_transform,
+                _ => continue,
+            };
+            // cameras look down their -z axis, the target is drawn at the orbit distance along it
+            let eye = Vec3::from(camera_transform.value.w_axis().truncate());
+            let look = -Vec3::from(camera_transform.value.z_axis().truncate());
+            let target = eye + look * player.camera_distance;
+            camera.extend_from_slice(&[eye, target]);
+            for &axis in [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()].iter() {
+                camera.extend_from_slice(&[target - axis * TARGET_MARKER_SIZE, target + axis * TARGET_MARKER_SIZE]);
+            }
+        }
+    }
+
+    for (gizmo, mut draw) in &mut gizmo_query.iter() {
+        let points = match gizmo.kind {
+            GizmoKind::Forward => &forward,
+            GizmoKind::Right => &right,
+            GizmoKind::Camera => &camera,
+            GizmoKind::Path => &path,
+        };
+        // hidden gizmos leave their last mesh alone rather than uploading an empty one
+        draw.is_visible = !points.is_empty();
+        if !draw.is_visible { continue; }
+        if let Some(mesh) = meshes.get_mut(&gizmo.mesh) {
+            *mesh = line_mesh(points);
+        }
+    }
+}
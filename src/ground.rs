@@ -0,0 +1,112 @@
+use bevy::{
+    prelude::*,
+    render::mesh::VertexAttribute,
+    render::pipeline::PrimitiveTopology,
+};
+
+pub const GRID_TEXTURE_PATH: &str = "res/grid.png";
+// bevy 0.1 uploads mesh indices as u16, and each tile is 4 vertices
+const MAX_TILES_PER_SIDE: u32 = 128;
+
+// set texture to None for a plain coloured ground. the colour tints the texture when there is one
+pub struct GroundSettings {
+    pub size: f32,
+    pub tile_size: f32,
+    pub color: Color,
+    pub texture: Option<String>,
+}
+
+impl Default for GroundSettings {
+    fn default() -> Self {
+        GroundSettings {
+            size: 1000.,
+            tile_size: 10.,
+            color: Color::rgb(0.87, 0.92, 0.94),
+            texture: Some(GRID_TEXTURE_PATH.to_string()),
+        }
+    }
+}
+
+pub struct GroundPlugin;
+
+impl Plugin for GroundPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GroundSettings>()
+            .add_startup_system(spawn_ground.system());
+    }
+}
+
+fn spawn_ground(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<GroundSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let texture = settings.texture.as_ref().and_then(|path| match asset_server.load(path) {
+        Ok(texture) => Some(texture),
+        Err(err) => {
+            eprintln!("failed to load ground texture '{}': {}, using a solid colour", path, err);
+            None
+        }
+    });
+
+    // a single quad is enough when there is nothing to tile
+    let mesh = match texture {
+        Some(_) => tiled_plane(settings.size, settings.tile_size),
+        None => Mesh::from(shape::Plane { size: settings.size }),
+    };
+
+    commands.spawn(PbrComponents {
+        mesh: meshes.add(mesh),
+        material: materials.add(StandardMaterial {
+            albedo: settings.color,
+            albedo_texture: texture,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+}
+
+// bevy 0.1 always samples textures clamped to the edge, so uvs past 1 can't repeat it.
+// instead every tile is its own quad with the full 0 to 1 range
+fn tiled_plane(size: f32, tile_size: f32) -> Mesh {
+    let tiles = ((size / tile_size.max(f32::EPSILON)).ceil().max(1.) as u32).min(MAX_TILES_PER_SIDE);
+    let tile_size = size / tiles as f32;
+    let half = size / 2.;
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    for row in 0..tiles {
+        for column in 0..tiles {
+            let x = column as f32 * tile_size - half;
+            let z = row as f32 * tile_size - half;
+            let corner = positions.len() as u32;
+
+            positions.extend_from_slice(&[
+                [x, 0., z],
+                [x + tile_size, 0., z],
+                [x, 0., z + tile_size],
+                [x + tile_size, 0., z + tile_size],
+            ]);
+            normals.extend_from_slice(&[[0., 1., 0.]; 4]);
+            uvs.extend_from_slice(&[[0., 0.], [1., 0.], [0., 1.], [1., 1.]]);
+            indices.extend_from_slice(&[
+                corner, corner + 2, corner + 1,
+                corner + 1, corner + 2, corner + 3,
+            ]);
+        }
+    }
+
+    Mesh {
+        primitive_topology: PrimitiveTopology::TriangleList,
+        attributes: vec![
+            VertexAttribute::position(positions),
+            VertexAttribute::normal(normals),
+            VertexAttribute::uv(uvs),
+        ],
+        indices: Some(indices),
+    }
+}
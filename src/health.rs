@@ -0,0 +1,194 @@
+use bevy::prelude::*;
+use crate::network::RemotePlayer;
+use crate::player::{MMOPlayer, MovementState, Teleported};
+use crate::timestep::FixedPosition;
+
+// where players start and where they come back to after dying, and how they're facing.
+// insert one before the game starts to start somewhere else
+pub struct SpawnConfig {
+    pub position: Vec3,
+    pub yaw: f32,
+    // none starts at the zoom settings' initial distance
+    pub camera_distance: Option<f32>,
+    // radians down from straight overhead, like MMOPlayer's
+    pub camera_pitch: f32,
+}
+
+impl Default for SpawnConfig {
+    fn default() -> Self {
+        SpawnConfig {
+            position: Vec3::zero(),
+            yaw: 0.,
+            camera_distance: None,
+            camera_pitch: 30f32.to_radians(),
+        }
+    }
+}
+
+// heals regen_per_second once regen_delay seconds have passed without taking damage
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    pub regen_per_second: f32,
+    pub regen_delay: f32,
+    pub since_damage: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health {
+            current: 100.,
+            max: 100.,
+            regen_per_second: 5.,
+            regen_delay: 5.,
+            since_damage: 0.,
+        }
+    }
+}
+
+// landing faster than safe_speed hurts, by damage_per_speed for every unit over it.
+// the default is well clear of a jump on flat ground
+pub struct FallDamage {
+    pub safe_speed: f32,
+    pub damage_per_speed: f32,
+}
+
+impl Default for FallDamage {
+    fn default() -> Self {
+        FallDamage {
+            safe_speed: 20.,
+            damage_per_speed: 5.,
+        }
+    }
+}
+
+// damage against these is ignored, and attacks pass them by without a hit
+pub struct Invulnerable;
+
+// sent by anything that hurts, the health system applies it
+pub struct Damage {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+// sent by the health system for damage that went through, amount is what was taken off.
+// anything showing damage reads this rather than the requests, which can be ignored
+pub struct Damaged {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+pub struct Died {
+    pub entity: Entity,
+}
+
+#[allow(dead_code)]
+pub struct Respawned {
+    pub entity: Entity,
+}
+
+#[derive(Default)]
+struct HealthState {
+    damage_event_reader: EventReader<Damage>,
+}
+
+#[derive(Default)]
+struct RespawnState {
+    died_event_reader: EventReader<Died>,
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SpawnConfig>()
+            .init_resource::<FallDamage>()
+            .add_event::<Damage>()
+            .add_event::<Damaged>()
+            .add_event::<Died>()
+            .add_event::<Respawned>()
+            .add_system(apply_fall_damage.system())
+            .add_system(apply_damage.system())
+            .add_system(regenerate_health.system())
+            .add_system(respawn_dead_players.system());
+    }
+}
+
+fn apply_damage(
+    mut state: Local<HealthState>,
+    damage_events: Res<Events<Damage>>,
+    mut damaged_events: ResMut<Events<Damaged>>,
+    mut died_events: ResMut<Events<Died>>,
+    health_query: Query<(&mut Health, Option<&Invulnerable>)>,
+) {
+    for damage in state.damage_event_reader.iter(&damage_events) {
+        if health_query.get::<Invulnerable>(damage.target).is_ok() { continue; }
+        let mut health = match health_query.get_mut::<Health>(damage.target) {
+            Ok(health) => health,
+            Err(_) => continue,
+        };
+        // already dead this frame, don't report it twice
+        if health.current <= 0. { continue; }
+
+        let before = health.current;
+        health.current = (health.current - damage.amount).max(0.);
+        health.since_damage = 0.;
+        damaged_events.send(Damaged { target: damage.target, amount: before - health.current });
+        if health.current <= 0. { died_events.send(Died { entity: damage.target }); }
+    }
+}
+
+fn apply_fall_damage(
+    fall_damage: Res<FallDamage>,
+    mut damage_events: ResMut<Events<Damage>>,
+    mut query: Query<(Entity, &mut MMOPlayer)>,
+) {
+    for (entity, mut player) in &mut query.iter() {
+        let speed = match player.landing_speed.take() {
+            Some(speed) => speed,
+            None => continue,
+        };
+        if speed <= fall_damage.safe_speed { continue; }
+        damage_events.send(Damage { target: entity, amount: (speed - fall_damage.safe_speed) * fall_damage.damage_per_speed });
+    }
+}
+
+fn regenerate_health(time: Res<Time>, mut query: Query<&mut Health>) {
+    for mut health in &mut query.iter() {
+        health.since_damage += time.delta_seconds;
+        if health.current <= 0. || health.since_damage < health.regen_delay { continue; }
+        health.current = (health.current + health.regen_per_second * time.delta_seconds).min(health.max);
+    }
+}
+
+// respawning is a teleport, the interpolated position is reset on both ends so
+// the player doesn't smear across the map for a frame. the player is turned to the
+// spawn's facing with the camera behind them, at the zoom and pitch they died with
+#[allow(clippy::type_complexity)]
+fn respawn_dead_players(
+    mut state: Local<RespawnState>,
+    spawn: Res<SpawnConfig>,
+    died_events: Res<Events<Died>>,
+    mut respawned_events: ResMut<Events<Respawned>>,
+    mut teleported_events: ResMut<Events<Teleported>>,
+    mut query: Query<Without<RemotePlayer, (Entity, &mut MMOPlayer, &mut Health, &mut FixedPosition, &mut Translation)>>,
+) {
+    let died: Vec<Entity> = state.died_event_reader.iter(&died_events).map(|died| died.entity).collect();
+    if died.is_empty() { return; }
+
+    for (entity, mut player, mut health, mut position, mut translation) in &mut query.iter() {
+        if !died.contains(&entity) { continue; }
+
+        let state = MovementState::at_rest(spawn.position, true);
+        position.teleport(spawn.position);
+        translation.0 = spawn.position;
+        player.set_movement_state(&state);
+        teleported_events.send(Teleported { entity, state });
+        player.move_target = None;
+        player.yaw = spawn.yaw;
+        player.camera_yaw = spawn.yaw;
+        health.current = health.max;
+        health.since_damage = 0.;
+        respawned_events.send(Respawned { entity });
+    }
+}
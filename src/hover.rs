@@ -0,0 +1,123 @@
+use bevy::{
+    prelude::*,
+    render::camera::Camera,
+};
+use std::collections::HashMap;
+use crate::cursor::{cursor_ray, CursorPosition};
+use crate::focus::Focusable;
+use crate::game_state::GameState;
+use crate::interest::Distant;
+use crate::player::{InputSource, MMOPlayer};
+
+// how much lighter the hovered entity's material gets
+const HIGHLIGHT_SCALE: f32 = 1.4;
+const HIGHLIGHT_LIFT: f32 = 0.2;
+
+// on the focusable under the cursor. its material is swapped for a lighter copy while hovered
+pub struct Hovered {
+    original: Handle<StandardMaterial>,
+}
+
+// the lighter copy of each material that has been hovered, made once and reused
+#[derive(Default)]
+struct HighlightMaterials(HashMap<Handle<StandardMaterial>, Handle<StandardMaterial>>);
+
+pub struct HoverPlugin;
+
+impl Plugin for HoverPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<HighlightMaterials>()
+            .add_system(update_hover.system());
+    }
+}
+
+fn highlighted(material: &StandardMaterial) -> StandardMaterial {
+    let lift = |channel: f32| (channel * HIGHLIGHT_SCALE + HIGHLIGHT_LIFT).min(1.);
+    let albedo = material.albedo;
+    StandardMaterial {
+        albedo: Color::rgba(lift(albedo.r), lift(albedo.g), lift(albedo.b), albedo.a),
+        albedo_texture: material.albedo_texture,
+        shaded: material.shaded,
+        ..Default::default()
+    }
+}
+
+// same pick as the middle click focus, the nearest focusable the cursor ray passes through.
+// the local player never highlights itself
+#[allow(clippy::too_many_arguments)]
+fn update_hover(
+    mut commands: Commands,
+    mut hovered: Local<Option<Entity>>,
+    game_state: Res<GameState>,
+    cursor: Res<CursorPosition>,
+    windows: Res<Windows>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut highlights: ResMut<HighlightMaterials>,
+    mut player_query: Query<(Entity, &MMOPlayer)>,
+    camera_query: Query<(&Transform, &Camera)>,
+    mut focusable_query: Query<Without<Distant, (Entity, &Focusable, &Transform)>>,
+    material_query: Query<(&mut Handle<StandardMaterial>, Option<&Hovered>)>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    let mut ray = None;
+    let mut local_player = None;
+    for (entity, player) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        local_player = Some(entity);
+        if let Some(camera_entity) = player.camera_entity {
+            let camera_transform = camera_query.get::<Transform>(camera_entity);
+            let camera = camera_query.get::<Camera>(camera_entity);
+            if let (Ok(camera_transform), Ok(camera)) = (camera_transform, camera) {
+                ray = cursor_ray(&cursor, &windows, &camera_transform, &camera);
+            }
+        }
+    }
+
+    let mut nearest: Option<(Entity, f32)> = None;
+    if let Some(ray) = ray {
+        for (entity, focusable, transform) in &mut focusable_query.iter() {
+            if Some(entity) == local_player { continue; }
+            let center = Vec3::from(transform.value.w_axis().truncate());
+            if let Some(distance) = ray.intersect_sphere(center, focusable.radius) {
+                if nearest.is_none_or(|(_, nearest)| distance < nearest) {
+                    nearest = Some((entity, distance));
+                }
+            }
+        }
+    }
+    let target = nearest.map(|(entity, _)| entity);
+    if target == *hovered { return; }
+
+    // a previous target that has been despawned since has nothing to put back
+    if let Some(previous) = hovered.take() {
+        if let Ok(original) = material_query.get::<Hovered>(previous).map(|hover| hover.original) {
+            if let Ok(mut material) = material_query.get_mut::<Handle<StandardMaterial>>(previous) {
+                *material = original;
+            }
+            commands.remove_one::<Hovered>(previous);
+        }
+    }
+
+    let target = match target {
+        Some(target) => target,
+        None => return,
+    };
+    if let Ok(mut material) = material_query.get_mut::<Handle<StandardMaterial>>(target) {
+        let original = *material;
+        let highlight = match highlights.0.get(&original) {
+            Some(&highlight) => highlight,
+            None => {
+                let highlight = match materials.get(&original).map(highlighted) {
+                    Some(highlight) => materials.add(highlight),
+                    None => return,
+                };
+                highlights.0.insert(original, highlight);
+                highlight
+            }
+        };
+        *material = highlight;
+        commands.insert_one(target, Hovered { original });
+        *hovered = Some(target);
+    }
+}
@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use std::fmt::Write;
+use crate::experience::{Experience, Leveling};
+use crate::health::Health;
+use crate::inventory::Inventory;
+use crate::nameplate::PlayerName;
+use crate::network::RemotePlayer;
+use crate::npc::Npc;
+use crate::stamina::Stamina;
+use crate::player::{InputSource, KeyBindings, MMOPlayer, Velocity};
+
+pub const HUD_FONT_PATH: &str = "res/fonts/DejaVuSansMono.ttf";
+const TARGET_BAR_WIDTH: f32 = 300.;
+const TARGET_BAR_HEIGHT: f32 = 14.;
+const TARGET_LABEL_SIZE: f32 = 18.;
+const TARGET_FRAME_TOP: f32 = 30.;
+
+struct CoordinatesText;
+
+// every node of the locked target's frame, shown and hidden together since
+// bevy 0.1 doesn't hide children along with their parent
+struct TargetFramePart;
+
+struct TargetHealthFill;
+
+struct TargetLabel;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(spawn_hud.system())
+            .add_system(update_coordinates_text.system())
+            .add_system(update_target_frame.system());
+    }
+}
+
+fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>, mut materials: ResMut<Assets<ColorMaterial>>) {
+    let font = match asset_server.load(HUD_FONT_PATH) {
+        Ok(font) => font,
+        Err(err) => {
+            eprintln!("failed to load hud font '{}': {}, hud disabled", HUD_FONT_PATH, err);
+            return;
+        }
+    };
+
+    commands
+        .spawn(TextComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(5.0),
+                    top: Val::Px(5.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font,
+                style: TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            },
+            ..Default::default()
+        })
+        .with(CoordinatesText);
+
+    // the name over the bar, centred along the top of the screen
+    let hidden = || Draw { is_visible: false, ..Default::default() };
+    commands
+        .spawn(NodeComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.),
+                    top: Val::Px(TARGET_FRAME_TOP),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Px(TARGET_LABEL_SIZE + TARGET_BAR_HEIGHT)),
+                // ui y points up in bevy 0.1, so reversing the column lays it out top to bottom
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0., 0., 0., 0.).into()),
+            draw: hidden(),
+            ..Default::default()
+        })
+        .with(TargetFramePart)
+        .with_children(|parent| {
+            parent
+                .spawn(TextComponents {
+                    text: Text {
+                        value: String::new(),
+                        font,
+                        style: TextStyle {
+                            font_size: TARGET_LABEL_SIZE,
+                            color: Color::WHITE,
+                        },
+                    },
+                    draw: hidden(),
+                    ..Default::default()
+                })
+                .with(TargetFramePart)
+                .with(TargetLabel)
+                .spawn(NodeComponents {
+                    style: Style {
+                        size: Size::new(Val::Px(TARGET_BAR_WIDTH), Val::Px(TARGET_BAR_HEIGHT)),
+                        ..Default::default()
+                    },
+                    material: materials.add(Color::rgba(0.05, 0.05, 0.08, 0.8).into()),
+                    draw: hidden(),
+                    ..Default::default()
+                })
+                .with(TargetFramePart)
+                .with_children(|parent| {
+                    parent
+                        .spawn(NodeComponents {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                ..Default::default()
+                            },
+                            material: materials.add(Color::rgb(0.8, 0.15, 0.15).into()),
+                            draw: hidden(),
+                            ..Default::default()
+                        })
+                        .with(TargetFramePart)
+                        .with(TargetHealthFill);
+                });
+        });
+}
+
+fn update_coordinates_text(
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    leveling: Res<Leveling>,
+    mut player_query: Query<(&MMOPlayer, &Translation, &Velocity, &Inventory, &Health, &Stamina, &Experience)>,
+    mut text_query: Query<(&CoordinatesText, &mut Text, &mut Draw)>,
+) {
+    let toggle = keyboard_input.just_pressed(bindings.toggle_hud);
+
+    for (_marker, mut text, mut draw) in &mut text_query.iter() {
+        if toggle { draw.is_visible = !draw.is_visible; }
+        if !draw.is_visible { continue; }
+
+        // reuse the existing buffer rather than formatting a fresh string every frame
+        text.value.clear();
+        for (player, translation, velocity, inventory, health, stamina, experience) in &mut player_query.iter() {
+            if player.input_source != InputSource::KeyboardMouse { continue; }
+            let _ = write!(text.value, "x: {:.2} y: {:.2} z: {:.2}", translation.x(), translation.y(), translation.z());
+            let _ = write!(text.value, "  speed: {:.2}", velocity.0.length());
+            let items: u32 = inventory.stacks.iter().map(|stack| stack.count).sum();
+            let _ = write!(text.value, "  items: {}", items);
+            let _ = write!(text.value, "  hp: {:.0}/{:.0}", health.current, health.max);
+            let _ = write!(text.value, "  stamina: {:.0}/{:.0}", stamina.current, stamina.max);
+            let _ = write!(text.value, "  level: {} ({}/{} xp)", experience.level, experience.current, leveling.xp_to_next(experience.level));
+        }
+    }
+}
+
+// players have their own name, npcs and training dummies only ever say what they are
+#[allow(clippy::type_complexity)]
+fn update_target_frame(
+    mut player_query: Query<Without<RemotePlayer, &MMOPlayer>>,
+    target_query: Query<(&Health, Option<&PlayerName>, Option<&Npc>)>,
+    mut part_query: Query<With<TargetFramePart, &mut Draw>>,
+    mut fill_query: Query<With<TargetHealthFill, &mut Style>>,
+    mut label_query: Query<With<TargetLabel, &mut Text>>,
+) {
+    let target = player_query
+        .iter()
+        .iter()
+        .find(|player| player.input_source == InputSource::KeyboardMouse)
+        .and_then(|player| player.lock_target);
+    // a despawned target has no health left to look up, and hides the frame the same
+    // as having no lock at all
+    let health = target.and_then(|target| target_query.get::<Health>(target).ok().map(|health| (target, health)));
+
+    for mut draw in &mut part_query.iter() {
+        draw.is_visible = health.is_some();
+    }
+    let (target, health) = match health {
+        Some(health) => health,
+        None => return,
+    };
+
+    let fraction = if health.max > 0. { (health.current / health.max).clamp(0., 1.) } else { 0. };
+    for mut style in &mut fill_query.iter() {
+        style.size.width = Val::Percent(fraction * 100.);
+    }
+    for mut text in &mut label_query.iter() {
+        text.value.clear();
+        match (target_query.get::<PlayerName>(target), target_query.get::<Npc>(target)) {
+            (Ok(name), _) => text.value.push_str(&name.0),
+            (Err(_), Ok(_)) => text.value.push_str("Enemy"),
+            (Err(_), Err(_)) => text.value.push_str("Training dummy"),
+        }
+        let _ = write!(text.value, "  {:.0}/{:.0}", health.current, health.max);
+    }
+}
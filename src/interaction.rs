@@ -0,0 +1,211 @@
+use bevy::prelude::*;
+use crate::game_state::GameState;
+use crate::hud::HUD_FONT_PATH;
+use crate::network::RemotePlayer;
+use crate::player::{InputSource, KeyBindings, KeyboardCaptured, MMOPlayer};
+
+const INTERACT_RANGE: f32 = 3.;
+// half angle either side of the player's facing
+const INTERACT_ARC: f32 = std::f32::consts::FRAC_PI_3;
+// seconds a line stays up in place of the prompt
+const SPEECH_DURATION: f32 = 4.;
+
+// anything the player can walk up to and use, label says what using it does
+pub struct Interactable {
+    pub label: String,
+}
+
+// what the interact key would use right now, if anything
+#[derive(Default)]
+pub struct InteractionTarget(pub Option<Entity>);
+
+pub struct Interacted {
+    // nothing reads who interacted yet, there's only ever the keyboard player
+    #[allow(dead_code)]
+    pub player: Entity,
+    pub target: Entity,
+}
+
+// an interactable that answers with its lines, one per interaction, around and around
+pub struct Speaker {
+    pub lines: Vec<String>,
+    next: usize,
+}
+
+impl Speaker {
+    pub fn new(lines: Vec<String>) -> Self {
+        Speaker { lines, next: 0 }
+    }
+}
+
+// the line the prompt shows instead of the label, and the seconds it has left
+#[derive(Default)]
+struct Speech {
+    line: Option<String>,
+    remaining: f32,
+}
+
+#[derive(Default)]
+struct SpeechState {
+    interacted_event_reader: EventReader<Interacted>,
+}
+
+struct InteractionPrompt;
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<InteractionTarget>()
+            .init_resource::<Speech>()
+            .add_event::<Interacted>()
+            .add_startup_system(spawn_interaction_prompt.system())
+            .add_system(find_interaction_target.system())
+            .add_system(interact.system())
+            .add_system(speak.system())
+            .add_system(update_interaction_prompt.system());
+    }
+}
+
+fn spawn_interaction_prompt(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = match asset_server.load(HUD_FONT_PATH) {
+        Ok(font) => font,
+        Err(err) => {
+            eprintln!("failed to load interaction font '{}': {}, no interaction prompt", HUD_FONT_PATH, err);
+            return;
+        }
+    };
+
+    commands
+        .spawn(NodeComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.),
+                    bottom: Val::Px(120.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Percent(100.), Val::Px(24.)),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material: Handle::default(),
+            draw: Draw { is_visible: false, ..Default::default() },
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(TextComponents {
+                    text: Text {
+                        value: String::new(),
+                        font,
+                        style: TextStyle {
+                            font_size: 20.,
+                            color: Color::WHITE,
+                        },
+                    },
+                    draw: Draw { is_visible: false, ..Default::default() },
+                    ..Default::default()
+                })
+                .with(InteractionPrompt);
+        });
+}
+
+// the closest interactable within range and inside the arc in front of the player
+fn find_interaction_target(
+    game_state: Res<GameState>,
+    mut target: ResMut<InteractionTarget>,
+    mut player_query: Query<Without<RemotePlayer, (&MMOPlayer, &Transform)>>,
+    mut interactable_query: Query<With<Interactable, (Entity, &Transform)>>,
+) {
+    target.0 = None;
+    if *game_state != GameState::Playing { return; }
+
+    for (player, transform) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let position = Vec3::from(transform.value.w_axis().truncate());
+        let mut forward = Vec3::from(transform.value.z_axis().truncate());
+        *forward.y_mut() = 0.;
+        let forward = forward.normalize();
+
+        let mut closest = None;
+        for (entity, interactable_transform) in &mut interactable_query.iter() {
+            let mut offset = Vec3::from(interactable_transform.value.w_axis().truncate()) - position;
+            *offset.y_mut() = 0.;
+            let distance = offset.length();
+            if distance > INTERACT_RANGE { continue; }
+            if distance > 0. && forward.dot(offset / distance).min(1.).acos() > INTERACT_ARC { continue; }
+            if closest.is_none_or(|(_, closest)| distance < closest) { closest = Some((entity, distance)); }
+        }
+        target.0 = closest.map(|(entity, _)| entity);
+        break;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn interact(
+    game_state: Res<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    captured: Res<KeyboardCaptured>,
+    target: Res<InteractionTarget>,
+    mut interacted_events: ResMut<Events<Interacted>>,
+    mut player_query: Query<Without<RemotePlayer, (Entity, &MMOPlayer)>>,
+) {
+    if *game_state != GameState::Playing || captured.0 || !keyboard_input.just_pressed(bindings.interact) { return; }
+    let target = match target.0 {
+        Some(target) => target,
+        None => return,
+    };
+
+    for (entity, player) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        interacted_events.send(Interacted { player: entity, target });
+        break;
+    }
+}
+
+fn speak(
+    mut state: Local<SpeechState>,
+    time: Res<Time>,
+    interacted_events: Res<Events<Interacted>>,
+    mut speech: ResMut<Speech>,
+    speaker_query: Query<&mut Speaker>,
+) {
+    speech.remaining -= time.delta_seconds;
+    if speech.remaining <= 0. { speech.line = None; }
+
+    for interacted in state.interacted_event_reader.iter(&interacted_events) {
+        let mut speaker = match speaker_query.get_mut::<Speaker>(interacted.target) {
+            Ok(speaker) => speaker,
+            Err(_) => continue,
+        };
+        if speaker.lines.is_empty() { continue; }
+        let index = speaker.next % speaker.lines.len();
+        speaker.next = index + 1;
+        speech.line = Some(speaker.lines[index].clone());
+        speech.remaining = SPEECH_DURATION;
+    }
+}
+
+// the parent node is only there to centre the text, so only the text is shown
+fn update_interaction_prompt(
+    bindings: Res<KeyBindings>,
+    target: Res<InteractionTarget>,
+    speech: Res<Speech>,
+    interactable_query: Query<&Interactable>,
+    mut prompt_query: Query<With<InteractionPrompt, (&mut Text, &mut Draw)>>,
+) {
+    let label = target.0.and_then(|target| interactable_query.get::<Interactable>(target).ok());
+    let prompt = match (&speech.line, &label) {
+        (Some(line), _) => Some(line.clone()),
+        (None, Some(label)) => Some(format!("[{:?}] {}", bindings.interact, label.label)),
+        (None, None) => None,
+    };
+    for (mut text, mut draw) in &mut prompt_query.iter() {
+        draw.is_visible = prompt.is_some();
+        if let Some(prompt) = &prompt {
+            if &text.value != prompt { text.value = prompt.clone(); }
+        }
+    }
+}
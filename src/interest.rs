@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+use crate::network::{RemotePlayer, SnapshotBuffer};
+use crate::player::{InputSource, MMOPlayer};
+
+// remote players further than radius from the keyboard player are hidden and skipped
+// by per-frame work. they only come back inside radius and only leave past
+// radius + hysteresis, so anyone hovering on the edge doesn't flicker
+pub struct InterestSettings {
+    pub radius: f32,
+    pub hysteresis: f32,
+}
+
+impl Default for InterestSettings {
+    fn default() -> Self {
+        InterestSettings {
+            radius: 150.,
+            hysteresis: 10.,
+        }
+    }
+}
+
+// marks entities outside the interest radius
+pub struct Distant;
+
+pub struct InterestPlugin;
+
+impl Plugin for InterestPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<InterestSettings>()
+            .add_system(update_interest.system());
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_interest(
+    mut commands: Commands,
+    settings: Res<InterestSettings>,
+    mut player_query: Query<Without<RemotePlayer, (&MMOPlayer, &Translation)>>,
+    mut remote_query: Query<With<RemotePlayer, (Entity, &Translation, Option<&SnapshotBuffer>, &mut Draw, Option<&Distant>)>>,
+) {
+    let mut centre = None;
+    for (player, translation) in &mut player_query.iter() {
+        if player.input_source == InputSource::KeyboardMouse { centre = Some(translation.0); }
+    }
+    let centre = match centre {
+        Some(centre) => centre,
+        None => return,
+    };
+
+    for (entity, translation, buffer, mut draw, distant) in &mut remote_query.iter() {
+        // distant players aren't interpolated, so their translation is wherever they were
+        // when they went out of range, the newest snapshot says where they are now
+        let position = buffer.and_then(SnapshotBuffer::newest).map_or(translation.0, |snapshot| snapshot.pos);
+        let distance = (position - centre).length();
+        match distant {
+            Some(_) if distance < settings.radius => {
+                commands.remove_one::<Distant>(entity);
+                draw.is_visible = true;
+            }
+            None if distance > settings.radius + settings.hysteresis => {
+                commands.insert_one(entity, Distant);
+                draw.is_visible = false;
+            }
+            _ => {}
+        }
+    }
+}
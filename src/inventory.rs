@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use crate::game_state::GameStarted;
+use crate::network::RemotePlayer;
+use crate::player::MMOPlayer;
+use crate::spatial::SpatialGrid;
+
+pub type ItemId = u32;
+
+const PICKUP_RADIUS: f32 = 2.0;
+const ITEM_SIZE: f32 = 0.75;
+
+// an item lying in the world, walked over to be picked up
+pub struct Item {
+    pub id: ItemId,
+    pub count: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub id: ItemId,
+    pub count: u32,
+}
+
+// one stack per item id, in the order they were first picked up
+#[derive(Default)]
+pub struct Inventory {
+    pub stacks: Vec<ItemStack>,
+}
+
+impl Inventory {
+    pub fn add(&mut self, id: ItemId, count: u32) {
+        match self.stacks.iter_mut().find(|stack| stack.id == id) {
+            Some(stack) => stack.count += count,
+            None => self.stacks.push(ItemStack { id, count }),
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub struct ItemPickedUp {
+    pub player: Entity,
+    pub id: ItemId,
+    pub count: u32,
+}
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<ItemPickedUp>()
+            .add_system(spawn_items.system())
+            .add_system(pick_up_items.system());
+    }
+}
+
+pub fn spawn_item(
+    commands: &mut Commands,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    position: Vec3,
+    item: Item,
+) -> Entity {
+    commands
+        .spawn(PbrComponents {
+            mesh,
+            material,
+            translation: Translation(position),
+            ..Default::default()
+        })
+        .with(item)
+        .current_entity()
+        .unwrap()
+}
+
+// a handful of items scattered around the spawn to walk over
+fn spawn_items(
+    mut commands: Commands,
+    mut game_started_reader: Local<EventReader<GameStarted>>,
+    game_started_events: Res<Events<GameStarted>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if game_started_reader.iter(&game_started_events).last().is_none() { return; }
+
+    let mesh = meshes.add(Mesh::from(shape::Cube { size: ITEM_SIZE / 2. }));
+    let material = materials.add(Color::rgb(0.9, 0.75, 0.2).into());
+    for (index, &(x, z)) in [(10., 10.), (-12., 6.), (4., -15.), (-8., -10.), (18., -4.)].iter().enumerate() {
+        let item = Item { id: index as ItemId % 2, count: 1 };
+        spawn_item(&mut commands, mesh, material, Vec3::new(x, ITEM_SIZE / 2., z), item);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn pick_up_items(
+    mut commands: Commands,
+    grid: Res<SpatialGrid>,
+    mut picked_up_events: ResMut<Events<ItemPickedUp>>,
+    mut player_query: Query<Without<RemotePlayer, (Entity, &MMOPlayer, &Translation, &mut Inventory)>>,
+    item_query: Query<&Item>,
+) {
+    // the despawns don't happen until the end of the frame, so without this two players
+    // standing by the same item would both get it
+    let mut claimed = HashSet::new();
+    for (player_entity, _player, player_translation, mut inventory) in &mut player_query.iter() {
+        for item_entity in grid.query_radius(player_translation.0, PICKUP_RADIUS) {
+            let item = match item_query.get::<Item>(item_entity) {
+                Ok(item) => item,
+                Err(_) => continue,
+            };
+            if !claimed.insert(item_entity) { continue; }
+
+            inventory.add(item.id, item.count);
+            picked_up_events.send(ItemPickedUp {
+                player: player_entity,
+                id: item.id,
+                count: item.count,
+            });
+            commands.despawn(item_entity);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use crate::game_state::GameState;
+use crate::npc::Npc;
+use crate::terrain::TerrainHeight;
+
+// sent by anything that shoves, direction points away from whatever did it
+pub struct Knockback {
+    pub target: Entity,
+    pub direction: Vec3,
+    pub strength: f32,
+}
+
+pub struct KnockbackSettings {
+    // per second, the push falls off exponentially at this rate
+    pub decay: f32,
+    // the furthest one push, or a pile of them, can move anything
+    pub max_distance: f32,
+}
+
+impl Default for KnockbackSettings {
+    fn default() -> Self {
+        KnockbackSettings {
+            decay: 8.,
+            max_distance: 4.,
+        }
+    }
+}
+
+// world space velocity on top of whatever the entity is doing itself. only ever
+// horizontal, so a hit from above can't drive anything into the ground
+#[derive(Default)]
+pub struct KnockbackImpulse {
+    pub velocity: Vec3,
+}
+
+impl KnockbackImpulse {
+    pub fn tick(&mut self, settings: &KnockbackSettings, dt: f32) {
+        self.velocity *= (-settings.decay * dt).exp();
+        if self.velocity.length_squared() < 0.0001 { self.velocity = Vec3::zero(); }
+    }
+}
+
+pub struct KnockbackPlugin;
+
+impl Plugin for KnockbackPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<KnockbackSettings>()
+            .add_event::<Knockback>()
+            .add_system(receive_knockback.system())
+            .add_system(move_knocked_back_npcs.system());
+    }
+}
+
+fn receive_knockback(
+    mut reader: Local<EventReader<Knockback>>,
+    settings: Res<KnockbackSettings>,
+    knockback_events: Res<Events<Knockback>>,
+    query: Query<&mut KnockbackImpulse>,
+) {
+    for knockback in reader.iter(&knockback_events) {
+        let mut impulse = match query.get_mut::<KnockbackImpulse>(knockback.target) {
+            Ok(impulse) => impulse,
+            Err(_) => continue,
+        };
+        let mut direction = knockback.direction;
+        *direction.y_mut() = 0.;
+        if direction == Vec3::zero() { continue; }
+        impulse.velocity += direction.normalize() * knockback.strength;
+
+        // an impulse decaying at decay per second covers speed / decay before it stops
+        let max_speed = settings.max_distance * settings.decay;
+        let speed = impulse.velocity.length();
+        if speed > max_speed { impulse.velocity *= max_speed / speed; }
+    }
+}
+
+// players are pushed inside their movement step, this is everything else
+fn move_knocked_back_npcs(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    settings: Res<KnockbackSettings>,
+    terrain: Res<TerrainHeight>,
+    mut query: Query<(&Npc, &mut KnockbackImpulse, &mut Translation)>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    for (npc, mut impulse, mut translation) in &mut query.iter() {
+        if impulse.velocity == Vec3::zero() { continue; }
+        translation.0 += impulse.velocity * time.delta_seconds;
+        let ground = terrain.height_at(translation.x(), translation.z()) + npc.height;
+        *translation.y_mut() = translation.y().max(ground);
+        impulse.tick(&settings, time.delta_seconds);
+    }
+}
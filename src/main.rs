@@ -1,158 +1,278 @@
-use bevy:: {
+use bevy::{
     prelude::*,
-    input::mouse::{
-        MouseMotion,
-        MouseWheel,
-    },
+    app::AppExit,
+    core::CorePlugin,
+    input::InputPlugin,
+    transform::TransformPlugin,
+    type_registry::TypeRegistryPlugin,
 };
-use bevy_obj::ObjPlugin;
+use animation::AnimationPlugin;
+use bench::BenchPlugin;
+use buffs::BuffPlugin;
+use camera_shake::CameraShakePlugin;
+use cinematic::CinematicPlugin;
+use click_to_move::ClickToMovePlugin;
+use combat::CombatPlugin;
+use console::ConsolePlugin;
+use cursor::CursorPlugin;
+use damage_numbers::DamageNumberPlugin;
+use dash::DashPlugin;
+use diagnostics_overlay::DiagnosticsOverlayPlugin;
+use display::DisplayPlugin;
+use day_night::DayNightPlugin;
+use experience::ExperiencePlugin;
+use focus::FocusPlugin;
+use footsteps::{FootstepPlugin, FootstepSound, FOOTSTEP_SOUND_PATH};
+use game_state::{GameStarted, GameStatePlugin};
+use gizmos::GizmoPlugin;
+use ground::GroundPlugin;
+use health::HealthPlugin;
+use hover::HoverPlugin;
+use hud::HudPlugin;
+use interaction::InteractionPlugin;
+use interest::InterestPlugin;
+use inventory::InventoryPlugin;
+use knockback::KnockbackPlugin;
+use menu::MenuPlugin;
+use minimap::MinimapPlugin;
+use nameplate::NameplatePlugin;
+use network::NetworkPlugin;
+use npc::NpcPlugin;
+use player::{PlayerPlugin, PlayerSimulationPlugin};
+use prediction::PredictionPlugin;
+use save::SavePlugin;
+use selection::SelectionPlugin;
+#[cfg(not(target_arch = "wasm32"))]
+use server::ServerPlugin;
+use settings::{Settings, SETTINGS_PATH};
+use shadows::ShadowPlugin;
+use sky::SkyPlugin;
+use spatial::SpatialPlugin;
+use stamina::StaminaPlugin;
+use strategy_camera::StrategyCameraPlugin;
+use std::time::{Duration, Instant};
+use targeting::TargetingPlugin;
+use terrain::TerrainPlugin;
+use timestep::FixedTimestepPlugin;
+use water::WaterPlugin;
+use wireframe::WireframePlugin;
+use zones::ZonePlugin;
 
-struct MMOPlayer {
-    yaw: f32,
+mod animation;
+mod bench;
+mod buffs;
+mod camera_shake;
+mod cinematic;
+mod click_to_move;
+mod combat;
+mod console;
+mod cursor;
+mod damage_numbers;
+mod dash;
+mod diagnostics_overlay;
+mod display;
+mod day_night;
+mod delta;
+mod experience;
+mod focus;
+mod footsteps;
+mod formation;
+mod game_state;
+mod gizmos;
+mod ground;
+mod health;
+mod hover;
+mod hud;
+mod interaction;
+mod interest;
+mod inventory;
+mod knockback;
+mod menu;
+mod minimap;
+mod nameplate;
+mod network;
+mod npc;
+mod player;
+mod prediction;
+mod save;
+mod selection;
+#[cfg(not(target_arch = "wasm32"))]
+mod server;
+mod settings;
+mod shadows;
+mod sky;
+mod spatial;
+mod speed_modifiers;
+mod stamina;
+mod strategy_camera;
+mod targeting;
+mod terrain;
+mod timestep;
+mod transport;
+mod water;
+#[cfg(feature = "websocket")]
+mod websocket;
+#[cfg(not(target_arch = "wasm32"))]
+mod websocket_server;
+mod wireframe;
+mod zones;
 
-    camera_distance: f32,
-    camera_pitch: f32,
-    camera_entity: Option<Entity>,
-}
-
-impl Default for MMOPlayer {
-    fn default() -> Self {
-        MMOPlayer {
-            yaw: 0.,
+// runs the simulation without a window or renderer, to host an authoritative server
+const SERVER_FLAG: &str = "--server";
+const SERVER_TICK_RATE: f64 = 60.;
 
-            camera_distance: 20.,
-            camera_pitch: 30.0f32.to_radians(),
-            camera_entity: None,
+fn main() {
+    let mut app = App::build();
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if std::env::args().any(|arg| arg == SERVER_FLAG) {
+            add_server_plugins(&mut app);
+            return app.run();
         }
     }
+    add_client_plugins(&mut app, bench::parse_bench_flag());
+    app.run();
 }
 
-#[derive(Default)]
-struct State {
-    mouse_motion_event_reader: EventReader<MouseMotion>,
-    mouse_wheel_event_reader: EventReader<MouseWheel>,
+// bevy 0.1 has no MinimalPlugins, so this is the non-rendering part of add_default_plugins,
+// with a fixed rate loop standing in for the window's event loop. browsers can't listen
+// for connections, so only native builds can host
+#[cfg(not(target_arch = "wasm32"))]
+fn add_server_plugins(app: &mut AppBuilder) {
+    app.add_plugin(TypeRegistryPlugin)
+        .add_plugin(CorePlugin)
+        .add_plugin(TransformPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(GameStatePlugin)
+        .add_plugin(FixedTimestepPlugin)
+        .add_plugin(PlayerSimulationPlugin)
+        .add_plugin(HealthPlugin)
+        .add_plugin(StaminaPlugin)
+        .add_plugin(BuffPlugin)
+        .add_plugin(DashPlugin)
+        .add_plugin(ServerPlugin)
+        .set_runner(run_server_loop);
 }
 
-fn main() {
-    App::build()
-        .add_resource(Msaa { samples: 4 })
-        .init_resource::<State>()
+// ScheduleRunnerPlugin's loop in bevy 0.1 runs the schedule without initializing it, so the
+// first system with a Local panics. this does both, on this thread like the plugin, since
+// app.update's parallel executor never finishes the first frame of the headless app
+#[cfg(not(target_arch = "wasm32"))]
+fn run_server_loop(mut app: App) {
+    let tick = Duration::from_secs_f64(1. / SERVER_TICK_RATE);
+    let mut app_exit_event_reader = EventReader::<AppExit>::default();
+    loop {
+        let start = Instant::now();
+        app.schedule.initialize(&mut app.resources);
+        app.schedule.run(&mut app.world, &mut app.resources);
+        if let Some(app_exit_events) = app.resources.get::<Events<AppExit>>() {
+            if app_exit_event_reader.latest(&app_exit_events).is_some() { break; }
+        }
+        if let Some(left) = tick.checked_sub(start.elapsed()) { std::thread::sleep(left); }
+    }
+}
+
+fn add_client_plugins(app: &mut AppBuilder, bench_bots: Option<usize>) {
+    let settings = Settings::load(SETTINGS_PATH);
+    app.add_resource(Msaa { samples: 4 })
+        .add_resource(settings.window.descriptor())
+        .add_resource(settings.window.clone())
+        .add_resource(settings.minimap.clone())
         .add_default_plugins()
-        .add_plugin(ObjPlugin)
+        .add_plugin(DisplayPlugin)
+        .add_plugin(GameStatePlugin)
+        .add_plugin(MenuPlugin);
+    if let Some(bots) = bench_bots {
+        app.add_plugin(BenchPlugin { bots });
+    }
+    app.add_plugin(FixedTimestepPlugin)
+        .add_plugin(PlayerPlugin { settings })
+        .add_plugin(AnimationPlugin)
+        .add_plugin(HealthPlugin)
+        .add_plugin(StaminaPlugin)
+        .add_plugin(BuffPlugin)
+        .add_plugin(DashPlugin)
+        .add_plugin(CursorPlugin)
+        .add_plugin(StrategyCameraPlugin)
+        .add_plugin(NetworkPlugin)
+        .add_plugin(PredictionPlugin)
+        .add_plugin(InterestPlugin)
+        .add_plugin(SpatialPlugin)
+        .add_plugin(ClickToMovePlugin)
+        .add_plugin(SelectionPlugin)
+        .add_plugin(FocusPlugin)
+        .add_plugin(HoverPlugin)
+        .add_plugin(TargetingPlugin)
+        .add_plugin(InteractionPlugin)
+        .add_plugin(NpcPlugin)
+        .add_plugin(CombatPlugin)
+        .add_plugin(KnockbackPlugin)
+        .add_plugin(ExperiencePlugin)
+        .add_plugin(InventoryPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(HudPlugin)
+        .add_plugin(DiagnosticsOverlayPlugin)
+        .add_plugin(MinimapPlugin)
+        .add_plugin(ConsolePlugin)
+        .add_plugin(NameplatePlugin)
+        .add_plugin(DamageNumberPlugin)
+        .add_plugin(CameraShakePlugin)
+        .add_plugin(CinematicPlugin)
+        .add_plugin(FootstepPlugin)
+        .add_plugin(GizmoPlugin)
+        .add_plugin(WireframePlugin)
+        .add_plugin(GroundPlugin)
+        .add_plugin(DayNightPlugin)
+        .add_plugin(ShadowPlugin)
+        .add_plugin(SkyPlugin)
+        .add_plugin(TerrainPlugin)
+        .add_plugin(ZonePlugin)
+        .add_plugin(WaterPlugin)
         .add_startup_system(setup.system())
-        .add_system(process_mouse_events.system())
-        .add_system(update_player.system())
-        .run();
+        .add_system(spawn_npcs.system());
 }
 
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // a missing clip only means silent footsteps
+    let footstep = asset_server.load(FOOTSTEP_SOUND_PATH).unwrap_or_default();
+    commands.insert_resource(FootstepSound(footstep));
 
-    let camera_entity = commands
-        .spawn(Camera3dComponents::default())
-        .current_entity();
-    
-    let player_entity = commands
-        .spawn(PbrComponents {
-            // load a mesh from binary glTF
-            mesh: asset_server
-                .load("res/person.obj")
-                .unwrap(),
-            // create a material for the mesh
-            material: materials.add(Color::rgb(0.5, 0.4, 0.3).into()),
-            translation: Translation::new(0.0, 0.0, 0.0),
-            ..Default::default()
-        }).with(MMOPlayer {
-            camera_entity,
-            camera_distance: 20.,
-            ..Default::default()
-        }).current_entity();
-
-    commands
-        .push_children(player_entity.unwrap(), &[camera_entity.unwrap()]);
     // add entities to the world
-    commands
-        .spawn(PbrComponents {
-            mesh: meshes.add(Mesh::from(shape::Plane { size: 1000.0 })),
-            material: materials.add(Color::rgb(0.87, 0.92, 0.94).into()),
-            translation: Translation::new(0.0, 0.0, 0.0),
-            ..Default::default()
-        })
-        // light
-        .spawn(LightComponents {
-            translation: Translation::new(4.0, 5.0, 4.0),
-            ..Default::default()
-        });
+    commands.spawn(UiCameraComponents::default());
 }
 
-fn process_mouse_events(
-    time: Res<Time>,
-    mut state: ResMut<State>, 
-    mouse_motion_events: Res<Events<MouseMotion>>,
-    mouse_wheel_events: Res<Events<MouseWheel>>,
-    mut query: Query<&mut MMOPlayer>,
-) {
-    let mut look = Vec2::zero();
-    for event in state.mouse_motion_event_reader.iter(&mouse_motion_events) {
-        look = event.delta;
-    }
-
-    let mut zoom_delta = 0.;
-    for event in state.mouse_wheel_event_reader.iter(&mouse_wheel_events) {
-        zoom_delta = event.y;
-    }
-
-    let zoom_sense = 10.0;
-    let look_sense = 1.0;
-
-    for mut player in &mut query.iter() {
-        player.yaw += look.x() * time.delta_seconds;
-        player.camera_pitch -= look.y() * time.delta_seconds * look_sense;
-        player.camera_distance -= zoom_delta * time.delta_seconds * zoom_sense;
-    }
-}
-
-fn update_player(
-    time: Res<Time>,
-    keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<(&mut MMOPlayer, &mut Translation, &Transform, &mut Rotation)>,
-    camera_query: Query<(&mut Translation, &mut Rotation)>,
+fn spawn_npcs(
+    mut commands: Commands,
+    mut game_started_reader: Local<EventReader<GameStarted>>,
+    game_started_events: Res<Events<GameStarted>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    let mut movement = Vec2::zero();
-    if keyboard_input.pressed(KeyCode::W) { *movement.y_mut() += 1.; }
-    if keyboard_input.pressed(KeyCode::S) { *movement.y_mut() -= 1.; }
-    if keyboard_input.pressed(KeyCode::D) { *movement.x_mut() += 1.; }
-    if keyboard_input.pressed(KeyCode::A) { *movement.x_mut() -= 1.; }
-
-    if movement != Vec2::zero() { movement.normalize(); }
-
-    let move_speed = 10.0;
-    movement *= time.delta_seconds * move_speed;
-
-    for (mut player, mut translation, transform, mut rotation) in &mut player_query.iter() {
-        player.camera_pitch = player.camera_pitch.max(1f32.to_radians()).min(179f32.to_radians());
-        player.camera_distance = player.camera_distance.max(5.).min(30.);
+    if game_started_reader.iter(&game_started_events).last().is_none() { return; }
 
-        let fwd = transform.value.z_axis().truncate() * movement.y();
-        let right = -transform.value.x_axis().truncate() * movement.x();
+    let npc_mesh = meshes.add(Mesh::from(shape::Cube { size: 1. }));
+    let npc_material = materials.add(Color::rgb(0.3, 0.5, 0.8).into());
+    let villager = npc::spawn_npc(&mut commands, npc_mesh, npc_material, 1., vec![
+        Vec3::new(-15., 0., -20.),
+        Vec3::new(15., 0., -20.),
+        Vec3::new(15., 0., -45.),
+        Vec3::new(-15., 0., -45.),
+    ]);
+    commands.insert(villager, (
+        interaction::Interactable { label: "Talk".to_string() },
+        interaction::Speaker::new(vec![
+            "Mind the red one out east, it doesn't like visitors.".to_string(),
+            "I walk this square all day. Someone has to.".to_string(),
+        ]),
+    ));
 
-        translation.0 += Vec3::from(fwd + right);
-        rotation.0 = Quat::from_rotation_y(-player.yaw);
-
-        if let Some(camera_entity) = player.camera_entity {
-            let cam_pos = Vec3::new(0., player.camera_pitch.cos(), -player.camera_pitch.sin()).normalize() * player.camera_distance;
-            if let Ok(mut cam_trans) = camera_query.get_mut::<Translation>(camera_entity) {
-                cam_trans.0 = cam_pos;
-            }
-
-            if let Ok(mut camera_rotation) = camera_query.get_mut::<Rotation>(camera_entity) {
-                let look = Mat4::face_toward(cam_pos, Vec3::zero(), Vec3::new(0.0, 1.0, 0.0));
-                camera_rotation.0 = look.to_scale_rotation_translation().1;
-            }
-        }
-    }
+    let enemy_material = materials.add(Color::rgb(0.7, 0.1, 0.1).into());
+    let enemy_home = Vec3::new(30., 0., 0.);
+    let enemy = npc::spawn_npc(&mut commands, npc_mesh, enemy_material, 1., vec![enemy_home]);
+    commands.insert(enemy, (
+        npc::Chaser::new(enemy_home),
+        targeting::Enemy,
+        health::Health::default(),
+        knockback::KnockbackImpulse::default(),
+    ));
 }
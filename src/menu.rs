@@ -0,0 +1,519 @@
+use bevy::{
+    prelude::*,
+    app::AppExit,
+    asset::LoadState,
+    input::keyboard::{ElementState, KeyboardInput},
+};
+use crate::game_state::{GameStarted, GameState};
+use crate::hud::HUD_FONT_PATH;
+use crate::player::{KeyBindings, PlayerAppearance};
+use crate::settings::SaveSettings;
+
+const MENU_FONT_SIZE: f32 = 32.;
+const CONTROLS_FONT_SIZE: f32 = 18.;
+// the controls are split over this many columns so they fit on a small window
+const CONTROLS_COLUMNS: usize = 2;
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+const SPINNER_FRAMES_PER_SECOND: f64 = 8.;
+
+#[derive(Clone, Copy)]
+enum MenuButton {
+    Play,
+    Resume,
+    Controls,
+    Quit,
+    // the action at this index in KeyBindings::actions_mut
+    Rebind(usize),
+    ResetBindings,
+    Back,
+}
+
+// marks the root of the main menu or loading screen, despawned with everything under it
+struct MenuRoot;
+struct LoadingText;
+struct PauseMenuRoot;
+struct ControlsRoot;
+
+// the text on a rebind button, kept up to date with whatever the action is bound to
+struct BindingLabel {
+    index: usize,
+}
+
+// what the controls menu is waiting for, or what went wrong with the last rebind
+struct ControlsPrompt;
+
+struct MenuMaterials {
+    background: Handle<ColorMaterial>,
+    // lets the paused game show through
+    overlay: Handle<ColorMaterial>,
+    clear: Handle<ColorMaterial>,
+    button: Handle<ColorMaterial>,
+    hovered: Handle<ColorMaterial>,
+}
+
+// where the controls menu goes back to, and the action waiting on a key press
+struct ControlsMenu {
+    return_to: GameState,
+    listening: Option<usize>,
+    prompt: String,
+}
+
+impl Default for ControlsMenu {
+    fn default() -> Self {
+        ControlsMenu {
+            return_to: GameState::MainMenu,
+            listening: None,
+            prompt: String::new(),
+        }
+    }
+}
+
+// the client opens on the main menu, play moves to loading until the player mesh is
+// in, and then into the game. a headless server has no menu and starts out playing
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(GameState::MainMenu)
+            .init_resource::<ControlsMenu>()
+            .add_startup_system(spawn_main_menu.system())
+            .add_system(handle_menu_buttons.system())
+            .add_system(update_loading_screen.system())
+            .add_system(update_pause_menu.system())
+            .add_system(capture_rebinding.system())
+            .add_system(update_controls_labels.system());
+    }
+}
+
+fn load_font(asset_server: &AssetServer) -> Option<Handle<Font>> {
+    match asset_server.load(HUD_FONT_PATH) {
+        Ok(font) => Some(font),
+        Err(err) => {
+            eprintln!("failed to load menu font '{}': {}", HUD_FONT_PATH, err);
+            None
+        }
+    }
+}
+
+fn sized_text(value: &str, font: Handle<Font>, font_size: f32) -> TextComponents {
+    TextComponents {
+        text: Text {
+            value: value.to_string(),
+            font,
+            style: TextStyle {
+                font_size,
+                color: Color::WHITE,
+            },
+        },
+        ..Default::default()
+    }
+}
+
+fn text(value: &str, font: Handle<Font>) -> TextComponents {
+    sized_text(value, font, MENU_FONT_SIZE)
+}
+
+fn full_screen_column(material: Handle<ColorMaterial>) -> NodeComponents {
+    NodeComponents {
+        style: Style {
+            size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+            // ui y points up in bevy 0.1, so reversing the column lays it out top to bottom
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material,
+        ..Default::default()
+    }
+}
+
+fn button(size: Size<Val>, margin: f32, material: Handle<ColorMaterial>) -> ButtonComponents {
+    ButtonComponents {
+        style: Style {
+            size,
+            margin: Rect::all(Val::Px(margin)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material,
+        ..Default::default()
+    }
+}
+
+// a column of the big buttons the main and pause menus are made of
+fn spawn_menu_buttons(parent: &mut ChildBuilder, buttons: &[(&str, MenuButton)], font: Handle<Font>, material: Handle<ColorMaterial>) {
+    for &(label, menu_button) in buttons.iter() {
+        parent
+            .spawn(button(Size::new(Val::Px(200.), Val::Px(60.)), 10., material))
+            .with(menu_button)
+            .with_children(|parent| {
+                parent.spawn(text(label, font));
+            });
+    }
+}
+
+fn spawn_main_menu_screen(commands: &mut Commands, font: Handle<Font>, menu_materials: &MenuMaterials) {
+    commands
+        .spawn(full_screen_column(menu_materials.background))
+        .with(MenuRoot)
+        .with_children(|parent| {
+            parent.spawn(text("tersia", font));
+            spawn_menu_buttons(
+                parent,
+                &[("Play", MenuButton::Play), ("Controls", MenuButton::Controls), ("Quit", MenuButton::Quit)],
+                font,
+                menu_materials.button,
+            );
+        });
+}
+
+fn spawn_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut state: ResMut<GameState>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let menu_materials = MenuMaterials {
+        background: materials.add(Color::rgb(0.08, 0.08, 0.1).into()),
+        overlay: materials.add(Color::rgba(0.08, 0.08, 0.1, 0.7).into()),
+        clear: materials.add(Color::rgba(0., 0., 0., 0.).into()),
+        button: materials.add(Color::rgb(0.2, 0.2, 0.25).into()),
+        hovered: materials.add(Color::rgb(0.3, 0.3, 0.38).into()),
+    };
+
+    // bench mode already skipped the menu
+    if *state != GameState::MainMenu {
+        commands.insert_resource(menu_materials);
+        return;
+    }
+
+    // without a font the menu would be blank buttons, so go straight to loading
+    let font = match load_font(&asset_server) {
+        Some(font) => font,
+        None => {
+            *state = GameState::Loading;
+            commands.insert_resource(menu_materials);
+            return;
+        }
+    };
+
+    spawn_main_menu_screen(&mut commands, font, &menu_materials);
+    commands.insert_resource(menu_materials);
+}
+
+// a list of every action and what it's bound to, each one a button to rebind it
+fn spawn_controls_screen(commands: &mut Commands, font: Handle<Font>, menu_materials: &MenuMaterials, bindings: &KeyBindings) {
+    let actions = bindings.actions();
+    let per_column = actions.len().div_ceil(CONTROLS_COLUMNS);
+    commands
+        .spawn(full_screen_column(menu_materials.background))
+        .with(ControlsRoot)
+        .with_children(|parent| {
+            parent.spawn(text("Controls", font));
+            parent
+                .spawn(NodeComponents {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..Default::default()
+                    },
+                    material: menu_materials.clear,
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for (column, actions) in actions.chunks(per_column).enumerate() {
+                        parent
+                            .spawn(NodeComponents {
+                                style: Style {
+                                    flex_direction: FlexDirection::ColumnReverse,
+                                    ..Default::default()
+                                },
+                                material: menu_materials.clear,
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                for offset in 0..actions.len() {
+                                    let index = column * per_column + offset;
+                                    parent
+                                        .spawn(button(Size::new(Val::Px(320.), Val::Px(26.)), 3., menu_materials.button))
+                                        .with(MenuButton::Rebind(index))
+                                        .with_children(|parent| {
+                                            parent.spawn(sized_text("", font, CONTROLS_FONT_SIZE)).with(BindingLabel { index });
+                                        });
+                                }
+                            });
+                    }
+                });
+            parent.spawn(sized_text("", font, CONTROLS_FONT_SIZE)).with(ControlsPrompt);
+            parent
+                .spawn(NodeComponents {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..Default::default()
+                    },
+                    material: menu_materials.clear,
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for &(label, menu_button) in [("Reset", MenuButton::ResetBindings), ("Back", MenuButton::Back)].iter() {
+                        parent
+                            .spawn(button(Size::new(Val::Px(160.), Val::Px(50.)), 10., menu_materials.button))
+                            .with(menu_button)
+                            .with_children(|parent| {
+                                parent.spawn(text(label, font));
+                            });
+                    }
+                });
+        });
+}
+
+fn close_controls_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    menu_materials: &MenuMaterials,
+    controls: &mut ControlsMenu,
+    state: &mut GameState,
+    save_settings_events: &mut Events<SaveSettings>,
+    root_query: &mut Query<With<ControlsRoot, Entity>>,
+) {
+    for entity in &mut root_query.iter() {
+        commands.despawn_recursive(entity);
+    }
+    // the pause menu puts itself back up
+    if controls.return_to == GameState::MainMenu {
+        if let Some(font) = load_font(asset_server) { spawn_main_menu_screen(commands, font, menu_materials); }
+    }
+    *state = controls.return_to;
+    controls.listening = None;
+    controls.prompt.clear();
+    save_settings_events.send(SaveSettings);
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn handle_menu_buttons(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    menu_materials: Res<MenuMaterials>,
+    mut state: ResMut<GameState>,
+    mut controls: ResMut<ControlsMenu>,
+    mut bindings: ResMut<KeyBindings>,
+    mut exit_events: ResMut<Events<AppExit>>,
+    mut save_settings_events: ResMut<Events<SaveSettings>>,
+    mut button_query: Query<(&MenuButton, &Interaction, &mut Handle<ColorMaterial>)>,
+    mut root_query: Query<With<MenuRoot, Entity>>,
+    mut pause_query: Query<With<PauseMenuRoot, Entity>>,
+    mut controls_query: Query<With<ControlsRoot, Entity>>,
+) {
+    if !matches!(*state, GameState::MainMenu | GameState::Paused | GameState::Controls) { return; }
+
+    let mut pressed = None;
+    for (button, interaction, mut material) in &mut button_query.iter() {
+        *material = match (interaction, button) {
+            (Interaction::Hovered, _) | (Interaction::Clicked, _) => menu_materials.hovered,
+            // the action waiting on a key stays lit up
+            (Interaction::None, MenuButton::Rebind(index)) if controls.listening == Some(*index) => menu_materials.hovered,
+            (Interaction::None, _) => menu_materials.button,
+        };
+        if *interaction == Interaction::Clicked { pressed = Some(*button); }
+    }
+
+    match pressed {
+        Some(MenuButton::Play) => {
+            for entity in &mut root_query.iter() {
+                commands.despawn_recursive(entity);
+            }
+            *state = GameState::Loading;
+            if let Some(font) = load_font(&asset_server) {
+                commands
+                    .spawn(full_screen_column(menu_materials.background))
+                    .with(MenuRoot)
+                    .with_children(|parent| {
+                        parent.spawn(text("", font)).with(LoadingText);
+                    });
+            }
+        }
+        Some(MenuButton::Resume) => *state = GameState::Playing,
+        Some(MenuButton::Controls) => {
+            for entity in root_query.iter().iter().chain(pause_query.iter().iter()) {
+                commands.despawn_recursive(entity);
+            }
+            if let Some(font) = load_font(&asset_server) {
+                spawn_controls_screen(&mut commands, font, &menu_materials, &bindings);
+            }
+            controls.return_to = *state;
+            controls.listening = None;
+            controls.prompt.clear();
+            *state = GameState::Controls;
+        }
+        Some(MenuButton::Quit) => exit_events.send(AppExit),
+        Some(MenuButton::Rebind(index)) => {
+            controls.listening = Some(index);
+            let name = bindings.actions()[index].0;
+            controls.prompt = format!("press a key for {}, escape to cancel", name);
+        }
+        Some(MenuButton::ResetBindings) => {
+            *bindings = KeyBindings::default();
+            controls.listening = None;
+            controls.prompt = "every key is back to its default".to_string();
+        }
+        Some(MenuButton::Back) => close_controls_menu(
+            &mut commands,
+            &asset_server,
+            &menu_materials,
+            &mut controls,
+            &mut state,
+            &mut save_settings_events,
+            &mut controls_query,
+        ),
+        None => {}
+    }
+}
+
+// escape can't be bound from here since it cancels, reset puts it back on pause
+#[allow(clippy::too_many_arguments)]
+fn capture_rebinding(
+    mut commands: Commands,
+    mut keyboard_input_reader: Local<EventReader<KeyboardInput>>,
+    keyboard_input_events: Res<Events<KeyboardInput>>,
+    asset_server: Res<AssetServer>,
+    menu_materials: Res<MenuMaterials>,
+    mut state: ResMut<GameState>,
+    mut controls: ResMut<ControlsMenu>,
+    mut bindings: ResMut<KeyBindings>,
+    mut save_settings_events: ResMut<Events<SaveSettings>>,
+    mut root_query: Query<With<ControlsRoot, Entity>>,
+) {
+    // read every frame all the same, so presses from before the menu opened never land
+    let pressed: Vec<KeyCode> = keyboard_input_reader
+        .iter(&keyboard_input_events)
+        .filter(|event| event.state == ElementState::Pressed)
+        .filter_map(|event| event.key_code)
+        .collect();
+    if *state != GameState::Controls { return; }
+
+    for key in pressed {
+        let index = match controls.listening {
+            Some(index) => index,
+            None if key == KeyCode::Escape => {
+                return close_controls_menu(
+                    &mut commands,
+                    &asset_server,
+                    &menu_materials,
+                    &mut controls,
+                    &mut state,
+                    &mut save_settings_events,
+                    &mut root_query,
+                );
+            }
+            None => continue,
+        };
+        controls.listening = None;
+        if key == KeyCode::Escape {
+            controls.prompt.clear();
+            continue;
+        }
+
+        let mut actions = bindings.actions_mut();
+        let name = actions[index].0;
+        *actions[index].1 = key;
+        // still bound, just flagged, some actions are meant to share a key
+        let conflicts: Vec<&str> = actions
+            .iter()
+            .enumerate()
+            .filter(|&(other, (_, bound))| other != index && **bound == key)
+            .map(|(_, (other_name, _))| *other_name)
+            .collect();
+        controls.prompt = if conflicts.is_empty() {
+            format!("{} is now {:?}", name, key)
+        } else {
+            format!("{} is now {:?}, which is also bound to {}", name, key, conflicts.join(", "))
+        };
+    }
+}
+
+fn update_controls_labels(
+    state: Res<GameState>,
+    controls: Res<ControlsMenu>,
+    bindings: Res<KeyBindings>,
+    mut label_query: Query<(&BindingLabel, &mut Text)>,
+    mut prompt_query: Query<With<ControlsPrompt, &mut Text>>,
+) {
+    if *state != GameState::Controls { return; }
+
+    let actions = bindings.actions();
+    for (label, mut text) in &mut label_query.iter() {
+        let (name, key) = actions[label.index];
+        text.value = if controls.listening == Some(label.index) { format!("{}: ...", name) } else { format!("{}: {:?}", name, key) };
+    }
+    for mut text in &mut prompt_query.iter() {
+        if text.value != controls.prompt { text.value = controls.prompt.clone(); }
+    }
+}
+
+// put up over the game whenever it's paused, and taken down as soon as it isn't
+fn update_pause_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    menu_materials: Res<MenuMaterials>,
+    state: Res<GameState>,
+    mut root_query: Query<With<PauseMenuRoot, Entity>>,
+) {
+    let shown = root_query.iter().iter().next().is_some();
+    match (*state == GameState::Paused, shown) {
+        (true, false) => {
+            let font = match load_font(&asset_server) {
+                Some(font) => font,
+                None => return,
+            };
+            commands
+                .spawn(full_screen_column(menu_materials.overlay))
+                .with(PauseMenuRoot)
+                .with_children(|parent| {
+                    parent.spawn(text("paused", font));
+                    spawn_menu_buttons(
+                        parent,
+                        &[("Resume", MenuButton::Resume), ("Controls", MenuButton::Controls), ("Quit", MenuButton::Quit)],
+                        font,
+                        menu_materials.button,
+                    );
+                });
+        }
+        (false, true) => {
+            for entity in &mut root_query.iter() {
+                commands.despawn_recursive(entity);
+            }
+        }
+        _ => {}
+    }
+}
+
+// meshes made in code rather than loaded have no load state, and one that failed to
+// load is swapped for the fallback once the players are spawned, so both count as done
+#[allow(clippy::too_many_arguments)]
+fn update_loading_screen(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    appearance: Res<PlayerAppearance>,
+    mut state: ResMut<GameState>,
+    mut game_started_events: ResMut<Events<GameStarted>>,
+    mut text_query: Query<With<LoadingText, &mut Text>>,
+    mut root_query: Query<With<MenuRoot, Entity>>,
+) {
+    if *state != GameState::Loading { return; }
+
+    if !matches!(asset_server.get_load_state(appearance.mesh), Some(LoadState::Loading(_))) {
+        for entity in &mut root_query.iter() {
+            commands.despawn_recursive(entity);
+        }
+        *state = GameState::Playing;
+        game_started_events.send(GameStarted);
+        return;
+    }
+
+    let frame = (time.seconds_since_startup * SPINNER_FRAMES_PER_SECOND) as usize % SPINNER.len();
+    for mut text in &mut text_query.iter() {
+        text.value = format!("loading {}", SPINNER[frame]);
+    }
+}
@@ -0,0 +1,198 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::network::RemotePlayer;
+use crate::npc::Npc;
+use crate::player::{InputSource, MMOPlayer};
+use crate::targeting::Enemy;
+
+const MINIMAP_MARGIN: f32 = 10.;
+const DOT_SIZE: f32 = 6.;
+const PLAYER_DOT_SIZE: f32 = 8.;
+// dots are pooled, anything past this many isn't plotted
+const MAX_DOTS: usize = 64;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MinimapSettings {
+    // side length of the square map, in pixels
+    pub size: f32,
+    // pixels per world unit
+    pub scale: f32,
+    // turn with the camera so straight ahead is always up, instead of staying north up
+    pub rotate: bool,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        MinimapSettings {
+            size: 160.,
+            scale: 1.5,
+            rotate: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum DotKind {
+    Player,
+    Ally,
+    Remote,
+    Npc,
+    Enemy,
+}
+
+struct MinimapRoot;
+
+struct MinimapDot {
+    index: usize,
+}
+
+struct MinimapMaterials {
+    player: Handle<ColorMaterial>,
+    ally: Handle<ColorMaterial>,
+    remote: Handle<ColorMaterial>,
+    npc: Handle<ColorMaterial>,
+    enemy: Handle<ColorMaterial>,
+}
+
+impl MinimapMaterials {
+    fn get(&self, kind: DotKind) -> Handle<ColorMaterial> {
+        match kind {
+            DotKind::Player => self.player,
+            DotKind::Ally => self.ally,
+            DotKind::Remote => self.remote,
+            DotKind::Npc => self.npc,
+            DotKind::Enemy => self.enemy,
+        }
+    }
+}
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(spawn_minimap.system())
+            .add_system(update_minimap.system());
+    }
+}
+
+fn spawn_minimap(
+    mut commands: Commands,
+    settings: Res<MinimapSettings>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let minimap_materials = MinimapMaterials {
+        player: materials.add(Color::WHITE.into()),
+        ally: materials.add(Color::rgb(0.3, 1.0, 0.4).into()),
+        remote: materials.add(Color::rgb(0.3, 0.6, 1.0).into()),
+        npc: materials.add(Color::rgb(1.0, 0.7, 0.2).into()),
+        enemy: materials.add(Color::rgb(1.0, 0.2, 0.2).into()),
+    };
+
+    commands
+        .spawn(NodeComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(MINIMAP_MARGIN),
+                    bottom: Val::Px(MINIMAP_MARGIN),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(settings.size), Val::Px(settings.size)),
+                ..Default::default()
+            },
+            material: materials.add(Color::rgba(0.05, 0.05, 0.08, 0.6).into()),
+            ..Default::default()
+        })
+        .with(MinimapRoot)
+        .with_children(|parent| {
+            for index in 0..MAX_DOTS {
+                parent
+                    .spawn(NodeComponents {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            ..Default::default()
+                        },
+                        material: minimap_materials.npc,
+                        draw: Draw { is_visible: false, ..Default::default() },
+                        ..Default::default()
+                    })
+                    .with(MinimapDot { index });
+            }
+        });
+    commands.insert_resource(minimap_materials);
+}
+
+// offset is in minimap pixels from the centre, y up. anything off the map sits on its edge
+fn clamp_to_map(offset: Vec2, half_size: f32) -> Vec2 {
+    let furthest = offset.x().abs().max(offset.y().abs());
+    if furthest > half_size { offset * (half_size / furthest) } else { offset }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_minimap(
+    settings: Res<MinimapSettings>,
+    materials: Res<MinimapMaterials>,
+    mut player_query: Query<(Entity, &MMOPlayer, &Translation, Option<&RemotePlayer>)>,
+    mut npc_query: Query<With<Npc, (&Translation, Option<&Enemy>)>>,
+    mut dummy_query: Query<Without<Npc, With<Enemy, &Translation>>>,
+    mut root_query: Query<With<MinimapRoot, &mut Draw>>,
+    mut dot_query: Query<(&MinimapDot, &mut Style, &mut Handle<ColorMaterial>, &mut Draw)>,
+) {
+    let mut centre = None;
+    for (entity, player, translation, remote) in &mut player_query.iter() {
+        if player.input_source == InputSource::KeyboardMouse && remote.is_none() {
+            centre = Some((entity, translation.0, player.camera_yaw));
+        }
+    }
+
+    let mut dots = Vec::new();
+    if let Some((local, centre, yaw)) = centre {
+        // the same axes movement uses, so up on the map is where walking forward goes
+        let facing = Quat::from_rotation_y(if settings.rotate { -yaw } else { 0. });
+        let up = facing * Vec3::unit_z();
+        let right = facing * -Vec3::unit_x();
+        let half_size = settings.size / 2.;
+        let mut plot = |position: Vec3, kind: DotKind| {
+            let offset = (position - centre) * settings.scale;
+            dots.push((clamp_to_map(Vec2::new(offset.dot(right), offset.dot(up)), half_size), kind));
+        };
+
+        for (entity, _, translation, remote) in &mut player_query.iter() {
+            if entity == local { continue; }
+            plot(translation.0, if remote.is_some() { DotKind::Remote } else { DotKind::Ally });
+        }
+        for (translation, enemy) in &mut npc_query.iter() {
+            plot(translation.0, if enemy.is_some() { DotKind::Enemy } else { DotKind::Npc });
+        }
+        for translation in &mut dummy_query.iter() {
+            plot(translation.0, DotKind::Enemy);
+        }
+        // last so it's drawn on top of anything right next to the player, and never dropped
+        dots.truncate(MAX_DOTS - 1);
+        dots.push((Vec2::zero(), DotKind::Player));
+    }
+
+    // nothing to centre on in the menus
+    for mut draw in &mut root_query.iter() {
+        draw.is_visible = centre.is_some();
+    }
+
+    let half_size = settings.size / 2.;
+    for (dot, mut style, mut material, mut draw) in &mut dot_query.iter() {
+        let (offset, kind) = match dots.get(dot.index) {
+            Some(&entry) => entry,
+            None => {
+                draw.is_visible = false;
+                continue;
+            }
+        };
+        draw.is_visible = true;
+        let size = if let DotKind::Player = kind { PLAYER_DOT_SIZE } else { DOT_SIZE };
+        // ui positions count from the top left, the offset has y up
+        style.position.left = Val::Px(half_size + offset.x() - size / 2.);
+        style.position.top = Val::Px(half_size - offset.y() - size / 2.);
+        style.size = Size::new(Val::Px(size), Val::Px(size));
+        *material = materials.get(kind);
+    }
+}
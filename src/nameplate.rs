@@ -0,0 +1,160 @@
+use bevy::{
+    prelude::*,
+    render::camera::Camera,
+};
+use crate::hud::HUD_FONT_PATH;
+use crate::interest::Distant;
+use crate::player::{InputSource, MMOPlayer};
+
+const NAMEPLATE_HEIGHT: f32 = 8.;
+const NAMEPLATE_FONT_SIZE: f32 = 20.;
+// distance at which the label is drawn at NAMEPLATE_FONT_SIZE, it shrinks further away
+const NAMEPLATE_REFERENCE_DISTANCE: f32 = 20.;
+const NAMEPLATE_MIN_FONT_SIZE: f32 = 12.;
+const NAMEPLATE_MAX_FONT_SIZE: f32 = 28.;
+// roughly how wide a glyph of the hud's monospace font is for its size, used to centre labels
+pub const GLYPH_ASPECT: f32 = 0.6;
+
+pub struct PlayerName(pub String);
+
+// bevy 0.1 can't draw text in the 3d pass, so nameplates are ui text following the
+// owner's projected position. that keeps them facing the camera and crisp at
+// any zoom, and only the size is scaled with distance
+struct Nameplate {
+    owner: Entity,
+}
+
+// marks a named entity that already has its nameplate
+struct HasNameplate;
+
+pub struct NameplatePlugin;
+
+impl Plugin for NameplatePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(spawn_nameplates.system())
+            .add_system(update_nameplates.system());
+    }
+}
+
+fn spawn_nameplates(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut query: Query<Without<HasNameplate, (Entity, &PlayerName)>>,
+) {
+    for (owner, name) in &mut query.iter() {
+        let font = match asset_server.load(HUD_FONT_PATH) {
+            Ok(font) => font,
+            Err(err) => {
+                eprintln!("failed to load nameplate font '{}': {}", HUD_FONT_PATH, err);
+                return;
+            }
+        };
+
+        commands
+            .spawn(TextComponents {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                text: Text {
+                    value: name.0.clone(),
+                    font,
+                    style: TextStyle {
+                        font_size: NAMEPLATE_FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                },
+                ..Default::default()
+            })
+            .with(Nameplate { owner })
+            .insert_one(owner, HasNameplate);
+    }
+}
+
+fn update_nameplates(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    mut player_query: Query<&MMOPlayer>,
+    camera_query: Query<(&Transform, &Camera)>,
+    name_query: Query<(&PlayerName, &Transform, Option<&Distant>)>,
+    mut nameplate_query: Query<(Entity, &Nameplate, &mut Text, &mut Style, &mut Draw)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let window_size = Vec2::new(window.width as f32, window.height as f32);
+
+    let view = local_camera_view(&mut player_query, &camera_query);
+
+    for (entity, nameplate, mut text, mut style, mut draw) in &mut nameplate_query.iter() {
+        let owner_transform = match name_query.get::<Transform>(nameplate.owner) {
+            Ok(transform) => transform,
+            Err(_) => {
+                commands.despawn(entity);
+                continue;
+            }
+        };
+        if let Ok(name) = name_query.get::<PlayerName>(nameplate.owner) {
+            if text.value != name.0 { text.value = name.0.clone(); }
+        }
+
+        let distant = name_query.get::<Distant>(nameplate.owner).is_ok();
+        let (camera_transform, projection) = match view {
+            Some(view) if !distant => view,
+            _ => {
+                draw.is_visible = false;
+                continue;
+            }
+        };
+
+        let anchor = Vec3::from(owner_transform.value.w_axis().truncate()) + Vec3::unit_y() * NAMEPLATE_HEIGHT;
+        let screen = match world_to_screen(&camera_transform, &projection, window_size, anchor) {
+            Some(screen) => screen,
+            None => {
+                draw.is_visible = false;
+                continue;
+            }
+        };
+        draw.is_visible = true;
+
+        let distance = (anchor - Vec3::from(camera_transform.w_axis().truncate())).length();
+        let font_size = (NAMEPLATE_FONT_SIZE * NAMEPLATE_REFERENCE_DISTANCE / distance.max(f32::EPSILON))
+            .clamp(NAMEPLATE_MIN_FONT_SIZE, NAMEPLATE_MAX_FONT_SIZE);
+        text.style.font_size = font_size;
+
+        let half_width = text.value.chars().count() as f32 * font_size * GLYPH_ASPECT / 2.;
+        style.position.left = Val::Px(screen.x() - half_width);
+        style.position.top = Val::Px(screen.y() - font_size);
+    }
+}
+
+// the camera transform and projection of the keyboard player's view, the one that's on screen
+pub fn local_camera_view(
+    player_query: &mut Query<&MMOPlayer>,
+    camera_query: &Query<(&Transform, &Camera)>,
+) -> Option<(Mat4, Mat4)> {
+    let mut view = None;
+    for player in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        if let Some(camera_entity) = player.camera_entity {
+            let camera_transform = camera_query.get::<Transform>(camera_entity);
+            let camera = camera_query.get::<Camera>(camera_entity);
+            if let (Ok(camera_transform), Ok(camera)) = (camera_transform, camera) {
+                view = Some((camera_transform.value, camera.projection_matrix));
+            }
+        }
+    }
+    view
+}
+
+// ui pixel position of a world point, from the top left like ui positions are.
+// none when it's behind the camera
+pub fn world_to_screen(camera_transform: &Mat4, projection: &Mat4, window_size: Vec2, position: Vec3) -> Option<Vec2> {
+    let clip = (*projection * camera_transform.inverse()).mul_vec4(position.extend(1.));
+    if clip.w() <= 0. { return None; }
+
+    // ndc y points up
+    let ndc = Vec2::new(clip.x(), clip.y()) / clip.w();
+    Some(Vec2::new((ndc.x() + 1.) / 2. * window_size.x(), (1. - ndc.y()) / 2. * window_size.y()))
+}
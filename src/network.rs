@@ -0,0 +1,474 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::PI;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Mutex;
+use crate::focus::Focusable;
+use crate::interest::Distant;
+use crate::nameplate::PlayerName;
+use crate::player::{InputSource, MMOPlayer, MovementState, PlayerAppearance, StepInput};
+use crate::prediction::{AuthoritativeState, InputSequence};
+use crate::transport;
+
+pub type NetworkId = u32;
+
+// followed by the server address, see transport::connect
+const CONNECT_FLAG: &str = "--connect";
+// followed by the name to join as, the server makes one up without it
+const NAME_FLAG: &str = "--name";
+const REMOTE_FOCUS_RADIUS: f32 = 3.0;
+// more than enough to cover the interpolation delay at any sensible send rate
+const MAX_BUFFERED_SNAPSHOTS: usize = 16;
+
+// a player driven by snapshots from the network. the local movement and camera
+// systems skip these, so they can share the world with local players
+pub struct RemotePlayer {
+    pub id: NetworkId,
+}
+
+// the id a player goes by on the network. on the keyboard player once the server has
+// handed it out, on every remote player, and on every connected player on the server
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayerId(pub NetworkId);
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    pub pos: Vec3,
+    pub yaw: f32,
+}
+
+// whatever carries snapshots between peers. send is this client's own player each
+// frame, receive is everything that has arrived from the others since the last call.
+// transports to an authoritative server also carry inputs up and corrections back
+pub trait Transport: Send + Sync {
+    fn send(&mut self, snapshot: PlayerSnapshot);
+    fn receive(&mut self) -> Vec<(NetworkId, PlayerSnapshot)>;
+
+    fn send_input(&mut self, _sequence: InputSequence, _input: StepInput) {}
+
+    fn send_teleport(&mut self, _sequence: InputSequence, _state: MovementState) {}
+
+    fn receive_authoritative(&mut self) -> Option<AuthoritativeState> {
+        None
+    }
+
+    // the ids of players that have disconnected since the last call
+    fn receive_departures(&mut self) -> Vec<NetworkId> {
+        Vec::new()
+    }
+
+    // the id the server gave this client, none until the handshake is done or when offline
+    fn local_id(&self) -> Option<NetworkId> {
+        None
+    }
+
+    fn send_heartbeat(&mut self) {}
+
+    // whether anything at all has arrived since the last call
+    fn take_heard(&mut self) -> bool {
+        false
+    }
+
+    // false once the connection has dropped for good
+    fn connected(&self) -> bool {
+        false
+    }
+}
+
+// stands in until a real socket is plugged in, nobody hears it and it hears nobody
+pub struct OfflineTransport;
+
+impl Transport for OfflineTransport {
+    fn send(&mut self, _snapshot: PlayerSnapshot) {}
+
+    fn receive(&mut self) -> Vec<(NetworkId, PlayerSnapshot)> {
+        Vec::new()
+    }
+}
+
+// snapshots are stamped with when they arrived here, there is no shared clock yet
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<(f64, PlayerSnapshot)>,
+}
+
+impl SnapshotBuffer {
+    fn new(received: f64, snapshot: PlayerSnapshot) -> Self {
+        let mut snapshots = VecDeque::new();
+        snapshots.push_back((received, snapshot));
+        SnapshotBuffer { snapshots }
+    }
+
+    fn push(&mut self, received: f64, snapshot: PlayerSnapshot) {
+        self.snapshots.push_back((received, snapshot));
+        if self.snapshots.len() > MAX_BUFFERED_SNAPSHOTS { self.snapshots.pop_front(); }
+    }
+
+    pub fn newest(&self) -> Option<PlayerSnapshot> {
+        self.snapshots.back().map(|&(_, snapshot)| snapshot)
+    }
+
+    // the state at render_time, between the two snapshots either side of it. past the
+    // newest one the last movement carries on for up to max_extrapolation, then holds
+    fn sample(&mut self, render_time: f64, max_extrapolation: f64) -> Option<PlayerSnapshot> {
+        // drop snapshots that are entirely behind the one before render_time
+        while self.snapshots.len() > 2 && self.snapshots[1].0 <= render_time {
+            self.snapshots.pop_front();
+        }
+
+        let &(newest_time, newest) = self.snapshots.back()?;
+        let &(oldest_time, oldest) = self.snapshots.front()?;
+        if self.snapshots.len() < 2 { return Some(newest); }
+        if render_time <= oldest_time { return Some(oldest); }
+
+        let (from_time, from, to_time, to) = if render_time <= newest_time {
+            let (to_time, to) = self.snapshots[1];
+            (oldest_time, oldest, to_time, to)
+        } else {
+            let (from_time, from) = self.snapshots[self.snapshots.len() - 2];
+            (from_time, from, newest_time, newest)
+        };
+
+        let render_time = render_time.min(newest_time + max_extrapolation);
+        let span = (to_time - from_time).max(f64::EPSILON);
+        let t = ((render_time - from_time) / span) as f32;
+        Some(PlayerSnapshot {
+            pos: from.pos + (to.pos - from.pos) * t,
+            yaw: from.yaw + shortest_angle(from.yaw, to.yaw) * t,
+        })
+    }
+}
+
+fn shortest_angle(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(2. * PI) - PI
+}
+
+// remote players are drawn delay seconds in the past, so there is almost always a newer
+// snapshot to interpolate towards. a late one is bridged by extrapolating, but only briefly
+pub struct InterpolationSettings {
+    pub delay: f32,
+    pub max_extrapolation: f32,
+}
+
+impl Default for InterpolationSettings {
+    fn default() -> Self {
+        InterpolationSettings {
+            delay: 0.1,
+            max_extrapolation: 0.25,
+        }
+    }
+}
+
+// how long either end puts up with silence before giving the other up, and how often
+// each makes sure it isn't silent itself. the server uses the same settings
+pub struct ConnectionSettings {
+    pub heartbeat_interval: f32,
+    pub timeout: f32,
+    // seconds between attempts to get back to a server that was lost
+    pub reconnect_interval: f32,
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        ConnectionSettings {
+            heartbeat_interval: 1.,
+            timeout: 10.,
+            reconnect_interval: 5.,
+        }
+    }
+}
+
+// sent when a player's connection goes, on the server and the clients still connected to
+// it. a client that loses the server sends it for every remote player it was showing.
+// the player's entity is already despawned by then, nothing else listens yet
+#[allow(dead_code)]
+pub struct PlayerDisconnected {
+    pub id: NetworkId,
+}
+
+#[derive(Clone)]
+pub struct ServerAddress {
+    pub address: String,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConnectionStatus {
+    Offline,
+    Connected,
+    // the server was lost, and the transport is offline until an attempt gets back to it
+    Reconnecting,
+}
+
+pub struct Network {
+    pub transport: Box<dyn Transport>,
+    // where the transport connected to, none when playing offline
+    pub server: Option<ServerAddress>,
+    pub status: ConnectionStatus,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network {
+            transport: Box::new(OfflineTransport),
+            server: None,
+            status: ConnectionStatus::Offline,
+        }
+    }
+}
+
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(connect_from_args())
+            .init_resource::<InterpolationSettings>()
+            .init_resource::<ConnectionSettings>()
+            .add_event::<PlayerDisconnected>()
+            .add_system(watch_connection.system())
+            .add_system(assign_local_player_id.system())
+            .add_system(send_local_snapshot.system())
+            .add_system(buffer_remote_snapshots.system())
+            .add_system(despawn_departed_players.system())
+            .add_system(interpolate_remote_players.system());
+    }
+}
+
+// offline unless started with --connect, and offline again if that fails
+fn connect_from_args() -> Network {
+    let args: Vec<String> = std::env::args().collect();
+    let index = match args.iter().position(|arg| arg == CONNECT_FLAG) {
+        Some(index) => index,
+        None => return Network::default(),
+    };
+    let address = match args.get(index + 1) {
+        Some(address) => address,
+        None => {
+            eprintln!("{} needs a server address, e.g. {} 127.0.0.1:7777", CONNECT_FLAG, CONNECT_FLAG);
+            return Network::default();
+        }
+    };
+    let name = args.iter().position(|arg| arg == NAME_FLAG).and_then(|index| args.get(index + 1));
+    let server = ServerAddress { address: address.clone(), name: name.cloned().unwrap_or_default() };
+    match transport::connect(&server.address, &server.name) {
+        Ok(transport) => Network { transport, server: Some(server), status: ConnectionStatus::Connected },
+        Err(err) => {
+            eprintln!("couldn't connect to {}, playing offline: {}", address, err);
+            Network::default()
+        }
+    }
+}
+
+type ConnectAttempt = Receiver<Result<Box<dyn Transport>, String>>;
+
+// a tcp connect blocks, so reconnects go on their own thread. the browser's websocket
+// doesn't, and there are no threads there anyway
+fn connect_in_background(server: ServerAddress) -> ConnectAttempt {
+    let (sender, receiver) = channel();
+    #[cfg(not(target_arch = "wasm32"))]
+    std::thread::spawn(move || {
+        let _ = sender.send(transport::connect(&server.address, &server.name));
+    });
+    #[cfg(target_arch = "wasm32")]
+    let _ = sender.send(transport::connect(&server.address, &server.name));
+    receiver
+}
+
+#[derive(Default)]
+struct ConnectionWatch {
+    last_heard: f64,
+    last_heartbeat: f64,
+    next_attempt: f64,
+    attempt: Option<Mutex<ConnectAttempt>>,
+}
+
+// a server that closes the connection or goes quiet for longer than the timeout is given
+// up on. its players are taken out of the world and reconnects are tried until one works,
+// the game carries on offline in the meantime
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn watch_connection(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<ConnectionSettings>,
+    mut network: ResMut<Network>,
+    mut watch: Local<ConnectionWatch>,
+    mut disconnected_events: ResMut<Events<PlayerDisconnected>>,
+    mut remote_query: Query<(Entity, &RemotePlayer)>,
+    mut local_query: Query<Without<RemotePlayer, With<PlayerId, Entity>>>,
+) {
+    let server = match network.server.clone() {
+        Some(server) => server,
+        None => return,
+    };
+    let now = time.seconds_since_startup;
+
+    match network.status {
+        ConnectionStatus::Connected => {
+            if network.transport.take_heard() { watch.last_heard = now; }
+            if now - watch.last_heartbeat >= settings.heartbeat_interval as f64 {
+                network.transport.send_heartbeat();
+                watch.last_heartbeat = now;
+            }
+            let silent = now - watch.last_heard > settings.timeout as f64;
+            if !silent && network.transport.connected() { return; }
+
+            if silent { eprintln!("nothing from {} for {} seconds, giving up on it", server.address, settings.timeout); }
+            eprintln!("lost the server, reconnecting to {}", server.address);
+            network.transport = Box::new(OfflineTransport);
+            network.status = ConnectionStatus::Reconnecting;
+            watch.next_attempt = now;
+            for (entity, remote) in &mut remote_query.iter() {
+                commands.despawn(entity);
+                disconnected_events.send(PlayerDisconnected { id: remote.id });
+            }
+            // the server may well hand out a different id next time
+            for entity in &mut local_query.iter() {
+                commands.remove_one::<PlayerId>(entity);
+            }
+        }
+        ConnectionStatus::Reconnecting => {
+            let result = match watch.attempt.as_ref().map(|attempt| attempt.lock().unwrap().try_recv()) {
+                Some(Ok(result)) => result,
+                Some(Err(TryRecvError::Empty)) => return,
+                // the attempt's thread died without an answer
+                Some(Err(TryRecvError::Disconnected)) => Err("the connection attempt gave up".to_string()),
+                None => {
+                    if now >= watch.next_attempt {
+                        watch.attempt = Some(Mutex::new(connect_in_background(server)));
+                        watch.next_attempt = now + settings.reconnect_interval as f64;
+                    }
+                    return;
+                }
+            };
+            watch.attempt = None;
+            match result {
+                Ok(transport) => {
+                    println!("reconnected to {}", server.address);
+                    network.transport = transport;
+                    network.status = ConnectionStatus::Connected;
+                    watch.last_heard = now;
+                }
+                Err(err) => eprintln!("couldn't reconnect to {}: {}", server.address, err),
+            }
+        }
+        ConnectionStatus::Offline => {}
+    }
+}
+
+pub fn spawn_remote_player(
+    commands: &mut Commands,
+    appearance: &PlayerAppearance,
+    id: NetworkId,
+    received: f64,
+    snapshot: PlayerSnapshot,
+) -> Entity {
+    commands
+        .spawn(PbrComponents {
+            mesh: appearance.mesh,
+            material: appearance.material,
+            translation: Translation(snapshot.pos),
+            rotation: Rotation(Quat::from_rotation_y(-snapshot.yaw)),
+            ..Default::default()
+        })
+        .with(MMOPlayer {
+            input_source: InputSource::None,
+            yaw: snapshot.yaw,
+            ..Default::default()
+        })
+        .with(RemotePlayer { id })
+        .with(PlayerId(id))
+        .with(SnapshotBuffer::new(received, snapshot))
+        .with(Focusable { radius: REMOTE_FOCUS_RADIUS })
+        .with(PlayerName(format!("Player #{}", id)))
+        .current_entity()
+        .unwrap()
+}
+
+// the keyboard player is ours whatever id the server gives it, the id only tells us
+// which of the server's players we are
+#[allow(clippy::type_complexity)]
+fn assign_local_player_id(
+    mut commands: Commands,
+    network: Res<Network>,
+    mut query: Query<Without<RemotePlayer, Without<PlayerId, (Entity, &MMOPlayer)>>>,
+) {
+    let id = match network.transport.local_id() {
+        Some(id) => id,
+        None => return,
+    };
+    for (entity, player) in &mut query.iter() {
+        if player.input_source == InputSource::KeyboardMouse { commands.insert_one(entity, PlayerId(id)); }
+    }
+}
+
+fn send_local_snapshot(
+    mut network: ResMut<Network>,
+    mut query: Query<Without<RemotePlayer, (&MMOPlayer, &Translation)>>,
+) {
+    for (player, translation) in &mut query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        network.transport.send(PlayerSnapshot { pos: translation.0, yaw: player.yaw });
+    }
+}
+
+fn buffer_remote_snapshots(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut network: ResMut<Network>,
+    appearance: Res<PlayerAppearance>,
+    mut query: Query<(&RemotePlayer, &mut SnapshotBuffer)>,
+) {
+    let mut received: HashMap<NetworkId, Vec<PlayerSnapshot>> = HashMap::new();
+    for (id, snapshot) in network.transport.receive() {
+        received.entry(id).or_default().push(snapshot);
+    }
+    if received.is_empty() { return; }
+
+    let now = time.seconds_since_startup;
+    for (remote, mut buffer) in &mut query.iter() {
+        for snapshot in received.remove(&remote.id).unwrap_or_default() {
+            buffer.push(now, snapshot);
+        }
+    }
+
+    // anyone left hasn't been seen before
+    for (id, snapshots) in received {
+        if let Some(&snapshot) = snapshots.last() {
+            spawn_remote_player(&mut commands, &appearance, id, now, snapshot);
+        }
+    }
+}
+
+fn despawn_departed_players(
+    mut commands: Commands,
+    mut network: ResMut<Network>,
+    mut disconnected_events: ResMut<Events<PlayerDisconnected>>,
+    mut query: Query<(Entity, &RemotePlayer)>,
+) {
+    let departed = network.transport.receive_departures();
+    if departed.is_empty() { return; }
+    for (entity, remote) in &mut query.iter() {
+        if departed.contains(&remote.id) { commands.despawn(entity); }
+    }
+    for id in departed {
+        disconnected_events.send(PlayerDisconnected { id });
+    }
+}
+
+// distant players aren't drawn, so they stay wherever they were until they come back
+// into range. their snapshots keep buffering, so they pick up at the right place
+#[allow(clippy::type_complexity)]
+fn interpolate_remote_players(
+    time: Res<Time>,
+    settings: Res<InterpolationSettings>,
+    mut query: Query<Without<Distant, (&mut SnapshotBuffer, &mut MMOPlayer, &mut Translation, &mut Rotation)>>,
+) {
+    let render_time = time.seconds_since_startup - settings.delay as f64;
+    for (mut buffer, mut player, mut translation, mut rotation) in &mut query.iter() {
+        if let Some(snapshot) = buffer.sample(render_time, settings.max_extrapolation as f64) {
+            translation.0 = snapshot.pos;
+            player.yaw = snapshot.yaw;
+            rotation.0 = Quat::from_rotation_y(-snapshot.yaw);
+        }
+    }
+}
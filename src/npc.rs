@@ -0,0 +1,214 @@
+use bevy::prelude::*;
+use crate::focus::Focusable;
+use crate::game_state::GameState;
+use crate::player::{direction_towards, yaw_towards, MMOPlayer};
+use crate::spatial::SpatialGrid;
+use crate::terrain::TerrainHeight;
+
+const NPC_RADIUS: f32 = 1.5;
+
+// walks the waypoints in order and loops back to the first after the last
+pub struct Patrol {
+    pub waypoints: Vec<Vec3>,
+    pub next: usize,
+    pub speed: f32,
+    pub arrival_radius: f32,
+}
+
+impl Patrol {
+    pub fn new(waypoints: Vec<Vec3>) -> Self {
+        Patrol {
+            waypoints,
+            next: 0,
+            speed: 4.,
+            arrival_radius: 0.5,
+        }
+    }
+}
+
+// how far the npc's origin sits above the ground it's standing on
+pub struct Npc {
+    pub height: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChaseState {
+    Idle,
+    Chasing(Entity),
+    Returning,
+}
+
+// goes after any player that comes within aggro_radius, and gives up and walks back
+// home once they get further than leash_radius from it
+pub struct Chaser {
+    pub aggro_radius: f32,
+    pub leash_radius: f32,
+    // stops this far from the player instead of walking into them
+    pub stop_distance: f32,
+    pub speed: f32,
+    pub home: Vec3,
+    pub state: ChaseState,
+}
+
+impl Chaser {
+    pub fn new(home: Vec3) -> Self {
+        Chaser {
+            aggro_radius: 15.,
+            leash_radius: 35.,
+            stop_distance: 2.5,
+            speed: 7.,
+            home,
+            state: ChaseState::Idle,
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub struct AggroStarted {
+    pub npc: Entity,
+    pub player: Entity,
+}
+
+#[allow(dead_code)]
+pub struct AggroEnded {
+    pub npc: Entity,
+}
+
+pub struct NpcPlugin;
+
+impl Plugin for NpcPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<AggroStarted>()
+            .add_event::<AggroEnded>()
+            .add_system(update_chasers.system())
+            .add_system(patrol_npcs.system());
+    }
+}
+
+pub fn spawn_npc(
+    commands: &mut Commands,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    height: f32,
+    waypoints: Vec<Vec3>,
+) -> Entity {
+    let start = waypoints.first().copied().unwrap_or_default();
+    commands
+        .spawn(PbrComponents {
+            mesh,
+            material,
+            translation: Translation(start + Vec3::new(0., height, 0.)),
+            ..Default::default()
+        })
+        .with(Npc { height })
+        .with(Patrol::new(waypoints))
+        .with(Focusable { radius: NPC_RADIUS })
+        .current_entity()
+        .unwrap()
+}
+
+// moves horizontally towards target without overshooting, and reports whether it's already there
+fn walk_towards(translation: &mut Translation, rotation: &mut Rotation, target: Vec3, distance: f32, arrival_radius: f32) -> bool {
+    let direction = match direction_towards(translation.0, target, arrival_radius) {
+        Some(direction) => direction,
+        None => return true,
+    };
+    let mut to_target = target - translation.0;
+    *to_target.y_mut() = 0.;
+    translation.0 += direction * distance.min(to_target.length() - arrival_radius);
+    rotation.0 = Quat::from_rotation_y(-yaw_towards(direction));
+    false
+}
+
+fn snap_to_ground(translation: &mut Translation, terrain: &TerrainHeight, height: f32) {
+    let ground = terrain.height_at(translation.x(), translation.z());
+    *translation.y_mut() = ground + height;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_chasers(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    terrain: Res<TerrainHeight>,
+    grid: Res<SpatialGrid>,
+    mut aggro_started_events: ResMut<Events<AggroStarted>>,
+    mut aggro_ended_events: ResMut<Events<AggroEnded>>,
+    player_query: Query<With<MMOPlayer, &Translation>>,
+    mut chaser_query: Query<(Entity, &Npc, &mut Chaser, &mut Translation, &mut Rotation)>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    for (npc_entity, npc, mut chaser, mut translation, mut rotation) in &mut chaser_query.iter() {
+        // a chase ends when the player gets too far from home or is despawned
+        if let ChaseState::Chasing(player) = chaser.state {
+            let leashed = match player_query.get::<Translation>(player) {
+                Ok(player_translation) => (player_translation.0 - chaser.home).length() > chaser.leash_radius,
+                Err(_) => true,
+            };
+            if leashed {
+                chaser.state = ChaseState::Returning;
+                aggro_ended_events.send(AggroEnded { npc: npc_entity });
+            }
+        }
+
+        // nothing is picked up on the way home, so it can't be kited back and forth at the leash
+        if chaser.state == ChaseState::Idle {
+            let mut nearest: Option<(Entity, f32)> = None;
+            for player_entity in grid.query_radius(translation.0, chaser.aggro_radius) {
+                let player_translation = match player_query.get::<Translation>(player_entity) {
+                    Ok(player_translation) => player_translation,
+                    Err(_) => continue,
+                };
+                let distance = (player_translation.0 - translation.0).length();
+                if distance > chaser.aggro_radius { continue; }
+                if (player_translation.0 - chaser.home).length() > chaser.leash_radius { continue; }
+                if nearest.is_none_or(|(_, nearest)| distance < nearest) {
+                    nearest = Some((player_entity, distance));
+                }
+            }
+            if let Some((player, _)) = nearest {
+                chaser.state = ChaseState::Chasing(player);
+                aggro_started_events.send(AggroStarted { npc: npc_entity, player });
+            }
+        }
+
+        let distance = chaser.speed * time.delta_seconds;
+        match chaser.state {
+            ChaseState::Idle => continue,
+            ChaseState::Chasing(player) => {
+                if let Ok(player_translation) = player_query.get::<Translation>(player) {
+                    walk_towards(&mut translation, &mut rotation, player_translation.0, distance, chaser.stop_distance);
+                }
+            }
+            ChaseState::Returning => {
+                let home = chaser.home;
+                if walk_towards(&mut translation, &mut rotation, home, distance, 0.1) {
+                    chaser.state = ChaseState::Idle;
+                }
+            }
+        }
+        snap_to_ground(&mut translation, &terrain, npc.height);
+    }
+}
+
+fn patrol_npcs(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    terrain: Res<TerrainHeight>,
+    mut npc_query: Query<(&Npc, &mut Patrol, Option<&Chaser>, &mut Translation, &mut Rotation)>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    for (npc, mut patrol, chaser, mut translation, mut rotation) in &mut npc_query.iter() {
+        if patrol.waypoints.is_empty() { continue; }
+        // chasing takes over from the patrol until the chaser is back home
+        if chaser.is_some_and(|chaser| chaser.state != ChaseState::Idle) { continue; }
+
+        let waypoint = patrol.waypoints[patrol.next];
+        let distance = patrol.speed * time.delta_seconds;
+        if walk_towards(&mut translation, &mut rotation, waypoint, distance, patrol.arrival_radius) {
+            patrol.next = (patrol.next + 1) % patrol.waypoints.len();
+        }
+        snap_to_ground(&mut translation, &terrain, npc.height);
+    }
+}
@@ -0,0 +1,1915 @@
+use bevy:: {
+    prelude::*,
+    asset::LoadState,
+    render::{
+        camera::{ActiveCameras, Camera, CameraProjection, PerspectiveProjection},
+        pass::{
+            ClearColor, LoadOp, Operations, PassDescriptor, RenderPassDepthStencilAttachmentDescriptor, TextureAttachment,
+        },
+        render_graph::{base::MainPass, CameraNode, PassNode, RenderGraph, WindowSwapChainNode, WindowTextureNode},
+        texture::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsage},
+    },
+    window::{CreateWindow, WindowDescriptor, WindowId},
+    winit::WinitWindows,
+    input::mouse::{
+        MouseMotion,
+        MouseWheel,
+    },
+};
+use bevy_obj::ObjPlugin;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use winit::dpi::PhysicalPosition;
+use crate::buffs::Buffs;
+use crate::camera_shake::CameraShake;
+use crate::cinematic::CameraPath;
+use crate::combat::AttackCooldown;
+use crate::cursor::CursorPosition;
+use crate::dash::Dash;
+use crate::display::{WindowSettings, WINDOW_TITLE};
+use crate::experience::Experience;
+use crate::focus::Focusable;
+use crate::game_state::{GameStarted, GameState};
+use crate::health::{Health, SpawnConfig};
+use crate::inventory::Inventory;
+use crate::knockback::{KnockbackImpulse, KnockbackSettings};
+use crate::nameplate::PlayerName;
+use crate::network::RemotePlayer;
+use crate::prediction::{PredictionHistory, CORRECTION_SMOOTHING};
+use crate::settings::{self, Settings};
+use crate::speed_modifiers::{self, SpeedModifiers};
+use crate::stamina::Stamina;
+use crate::strategy_camera::strategy_camera_offset;
+use crate::terrain::TerrainHeight;
+use crate::timestep::{FixedPosition, FixedTimestep};
+use crate::water::{self, EnteredWater, LeftWater, WaterLevel};
+
+// the first of these that exists is used, so dropping a person.gltf into res/ replaces the obj
+const PLAYER_MESH_PATHS: [&str; 3] = ["res/person.gltf", "res/person.glb", "res/person.obj"];
+const PLAYER_TEXTURE_PATH: &str = "res/person.png";
+const PLAYER_COLOR: Color = Color::rgb(0.5, 0.4, 0.3);
+const CAMERA_GROUND_CLEARANCE: f32 = 0.5;
+const PLAYER_FOCUS_RADIUS: f32 = 3.0;
+// how far below the feet the ground can drop in one frame and still be walked down, rather than fallen off
+const GROUND_SNAP_DISTANCE: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Property, Serialize, Deserialize)]
+pub enum CameraMode {
+    ThirdPerson,
+    FirstPerson,
+    // detached overhead view that pans around on its own, see strategy_camera
+    Strategy,
+}
+
+#[derive(Clone, Copy, PartialEq, Property, Serialize, Deserialize)]
+pub enum InputSource {
+    KeyboardMouse,
+    // the second local player, on its own keys of the same keyboard
+    SecondKeyboard,
+    None,
+}
+
+// camera_entity and camera_focus are skipped when reflecting, since a raw entity id means
+// nothing once a scene is reloaded. players without a camera get a fresh one attached
+#[derive(Properties)]
+pub struct MMOPlayer {
+    pub input_source: InputSource,
+    pub yaw: f32,
+    pub move_speed: f32,
+    pub sprint_multiplier: f32,
+    pub sprinting: bool,
+    pub crouch_speed_multiplier: f32,
+    // how far the camera and what it looks at drop while crouched
+    pub crouch_height_offset: f32,
+    pub crouching: bool,
+    pub velocity: Vec2,
+    pub acceleration: f32,
+    pub friction: f32,
+    pub move_target: Option<Vec3>,
+    // shift clicked points to walk to in turn once move_target is reached
+    #[property(ignore)]
+    pub waypoints: VecDeque<Vec3>,
+    pub arrival_radius: f32,
+    pub vertical_velocity: f32,
+    pub grounded: bool,
+    pub time_since_grounded: f32,
+    pub jump_buffer_timer: f32,
+    pub jump_speed: f32,
+    pub gravity: f32,
+    // seconds after walking off an edge that a jump still counts
+    pub coyote_time: f32,
+    // seconds a jump pressed in the air is held for, to go off on landing
+    pub jump_buffer: f32,
+    // radians from flat, anything steeper can't be walked up and slides the player down
+    pub max_walkable_slope: f32,
+    pub slide_acceleration: f32,
+    pub swimming: bool,
+    // how deep the player's feet have to be to start swimming
+    pub swim_depth: f32,
+    pub swim_speed_multiplier: f32,
+    pub swim_gravity: f32,
+    // upward acceleration once swim_depth under, floats the player a little below it
+    pub buoyancy: f32,
+    pub water_drag: f32,
+    // downward speed at the last landing, until fall damage has looked at it
+    #[property(ignore)]
+    pub landing_speed: Option<f32>,
+
+    pub camera_yaw: f32,
+    // radians per second the turn keys swing the camera, for players without a mouse
+    pub turn_speed: f32,
+    pub camera_distance: f32,
+    pub target_camera_distance: f32,
+    pub camera_pitch: f32,
+    pub camera_smoothing: f32,
+    pub base_fov: f32,
+    pub sprint_fov: f32,
+    // aiming down sights pulls the third person camera in to ads_distance, or leaves it
+    // where it is when the wheel already has it closer, narrows the fov to ads_fov and
+    // moves the shoulder offset in towards the line projectiles are fired along
+    pub ads: bool,
+    pub ads_distance: f32,
+    pub ads_fov: f32,
+    pub ads_shoulder_offset: Vec3,
+    // how quickly the framing moves between hip and ads
+    pub ads_smoothing: f32,
+    // 0 at the hip, 1 fully aimed
+    #[property(ignore)]
+    pub ads_blend: f32,
+    pub camera_mode: CameraMode,
+    pub head_offset: Vec3,
+    // camera space, x to the right of the view. swapping shoulders mirrors x
+    pub shoulder_offset: Vec3,
+    #[property(ignore)]
+    pub camera_entity: Option<Entity>,
+    // when set, the orbit camera circles this entity instead of the player
+    #[property(ignore)]
+    pub camera_focus: Option<Entity>,
+    // when set, the body faces this entity and the camera is pulled round to keep it in view
+    #[property(ignore)]
+    pub lock_target: Option<Entity>,
+    // the shake offset added to the camera last frame, taken back off before smoothing
+    #[property(ignore)]
+    pub camera_shake_offset: Vec3,
+    // world offset from the player to the point the strategy camera looks at
+    #[property(ignore)]
+    pub strategy_pan: Vec3,
+}
+
+impl Default for MMOPlayer {
+    fn default() -> Self {
+        MMOPlayer {
+            input_source: InputSource::KeyboardMouse,
+            yaw: 0.,
+            move_speed: 10.,
+            sprint_multiplier: 1.8,
+            sprinting: false,
+            crouch_speed_multiplier: 0.5,
+            crouch_height_offset: 2.5,
+            crouching: false,
+            velocity: Vec2::zero(),
+            acceleration: 80.,
+            friction: 60.,
+            move_target: None,
+            waypoints: VecDeque::new(),
+            arrival_radius: 0.5,
+            vertical_velocity: 0.,
+            grounded: true,
+            time_since_grounded: 0.,
+            jump_buffer_timer: 0.,
+            jump_speed: 12.,
+            gravity: 30.,
+            coyote_time: 0.1,
+            jump_buffer: 0.15,
+            max_walkable_slope: 45f32.to_radians(),
+            slide_acceleration: 25.,
+            swimming: false,
+            swim_depth: 1.5,
+            swim_speed_multiplier: 0.5,
+            swim_gravity: 5.,
+            buoyancy: 10.,
+            water_drag: 3.,
+            landing_speed: None,
+
+            camera_yaw: 0.,
+            turn_speed: 2.5,
+            camera_distance: 20.,
+            target_camera_distance: 20.,
+            camera_pitch: 30.0f32.to_radians(),
+            camera_smoothing: 12.,
+            base_fov: 45f32.to_radians(),
+            sprint_fov: 55f32.to_radians(),
+            ads: false,
+            ads_distance: 8.,
+            ads_fov: 38f32.to_radians(),
+            ads_shoulder_offset: Vec3::new(1.2, 0.5, 0.),
+            ads_smoothing: 12.,
+            ads_blend: 0.,
+            camera_mode: CameraMode::ThirdPerson,
+            head_offset: Vec3::new(0., 6.5, 0.),
+            shoulder_offset: Vec3::new(2., 1., 0.),
+            camera_entity: None,
+            camera_focus: None,
+            lock_target: None,
+            camera_shake_offset: Vec3::zero(),
+            strategy_pan: Vec3::zero(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSensitivity {
+    // radians per pixel the mouse moves
+    pub yaw: f32,
+    pub pitch: f32,
+    pub invert_y: bool,
+    // seconds of mouse motion the look is averaged over, 0 for raw input
+    pub mouse_smoothing: f32,
+    // the button that has to be held for the mouse to turn the camera
+    pub rotate_button: RotateButton,
+}
+
+impl Default for CameraSensitivity {
+    fn default() -> Self {
+        CameraSensitivity {
+            yaw: 0.005,
+            pitch: 0.005,
+            invert_y: false,
+            mouse_smoothing: 0.02,
+            rotate_button: RotateButton::Right,
+        }
+    }
+}
+
+// always turns the camera on any mouse movement, which leaves no free cursor for the ui
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RotateButton {
+    Left,
+    Right,
+    Middle,
+    Always,
+}
+
+impl RotateButton {
+    fn mouse_button(self) -> Option<MouseButton> {
+        match self {
+            RotateButton::Left => Some(MouseButton::Left),
+            RotateButton::Right => Some(MouseButton::Right),
+            RotateButton::Middle => Some(MouseButton::Middle),
+            RotateButton::Always => None,
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    // tap sprint to turn it on and again to turn it off, instead of holding it
+    pub sprint_toggle: bool,
+    pub crouch_toggle: bool,
+    // a mouse button that aims down sights as well as the aim key. every mouse button already
+    // does something by default so it starts unbound, and it's ignored if set to the rotate button
+    pub aim_button: Option<MouseButton>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ZoomSettings {
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    pub initial: f32,
+    pub invert: bool,
+    // how quickly the distance eases towards where the wheel left it, raise it for instant zoom
+    pub smoothing: f32,
+}
+
+impl Default for ZoomSettings {
+    fn default() -> Self {
+        ZoomSettings {
+            min: 5.,
+            max: 30.,
+            step: 1.,
+            initial: 20.,
+            invert: false,
+            smoothing: 10.,
+        }
+    }
+}
+
+impl MMOPlayer {
+    pub fn movement_state(&self, position: Vec3) -> MovementState {
+        MovementState {
+            position,
+            velocity: self.velocity,
+            vertical_velocity: self.vertical_velocity,
+            grounded: self.grounded,
+            time_since_grounded: self.time_since_grounded,
+            jump_buffer_timer: self.jump_buffer_timer,
+            swimming: self.swimming,
+        }
+    }
+
+    pub fn set_movement_state(&mut self, state: &MovementState) {
+        self.velocity = state.velocity;
+        self.vertical_velocity = state.vertical_velocity;
+        self.grounded = state.grounded;
+        self.time_since_grounded = state.time_since_grounded;
+        self.jump_buffer_timer = state.jump_buffer_timer;
+        self.swimming = state.swimming;
+    }
+}
+
+// the part of a player that a movement step integrates
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MovementState {
+    pub position: Vec3,
+    pub velocity: Vec2,
+    pub vertical_velocity: f32,
+    pub grounded: bool,
+    pub time_since_grounded: f32,
+    pub jump_buffer_timer: f32,
+    pub swimming: bool,
+}
+
+// sent for a local player that was moved somewhere without walking there, by respawning,
+// loading a save and the like. the server has to hear about it or it corrects them back
+pub struct Teleported {
+    pub entity: Entity,
+    pub state: MovementState,
+}
+
+impl MovementState {
+    // standing still at position, for teleports and respawns
+    pub fn at_rest(position: Vec3, grounded: bool) -> Self {
+        MovementState {
+            position,
+            velocity: Vec2::zero(),
+            vertical_velocity: 0.,
+            grounded,
+            time_since_grounded: 0.,
+            jump_buffer_timer: 0.,
+            swimming: false,
+        }
+    }
+}
+
+// one fixed step of input with steering and facing already worked out, so replaying
+// the same steps from the same state always lands in the same place
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct StepInput {
+    pub movement: Vec2,
+    // local velocity that replaces the movement for the step while dashing
+    pub dash: Option<Vec2>,
+    // local velocity added on top of the movement, from being hit
+    pub knockback: Vec2,
+    // with modifiers and sprinting already applied
+    pub move_speed: f32,
+    pub jump: bool,
+    pub yaw: f32,
+}
+
+// world space velocity over the last fixed step, measured from how far the player actually
+// moved, so it includes jumps, falls and anything else that displaced them
+#[derive(Default)]
+pub struct Velocity(pub Vec3);
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub jump: KeyCode,
+    pub sprint: KeyCode,
+    pub crouch: KeyCode,
+    pub free_look: KeyCode,
+    pub toggle_camera_mode: KeyCode,
+    pub toggle_strategy_camera: KeyCode,
+    pub swap_shoulder: KeyCode,
+    // held to aim down sights
+    pub aim: KeyCode,
+    pub save_settings: KeyCode,
+    pub save_game: KeyCode,
+    pub load_game: KeyCode,
+    pub toggle_hud: KeyCode,
+    pub toggle_gizmos: KeyCode,
+    pub toggle_wireframe: KeyCode,
+    pub toggle_diagnostics: KeyCode,
+    pub toggle_fullscreen: KeyCode,
+    pub toggle_console: KeyCode,
+    pub lock_on: KeyCode,
+    pub clear_lock: KeyCode,
+    pub attack: KeyCode,
+    pub interact: KeyCode,
+    pub dash: KeyCode,
+    pub ranged_attack: KeyCode,
+    // held while clicking to queue a waypoint or add to the selection
+    pub queue_modifier: KeyCode,
+    pub pause: KeyCode,
+    // set in the settings file, the controls menu only rebinds the first player's keys
+    pub second_player: SecondKeyboardBindings,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            forward: KeyCode::W,
+            back: KeyCode::S,
+            left: KeyCode::A,
+            right: KeyCode::D,
+            jump: KeyCode::Space,
+            sprint: KeyCode::LShift,
+            crouch: KeyCode::LControl,
+            free_look: KeyCode::LAlt,
+            toggle_camera_mode: KeyCode::V,
+            toggle_strategy_camera: KeyCode::B,
+            swap_shoulder: KeyCode::Q,
+            aim: KeyCode::G,
+            save_settings: KeyCode::F9,
+            save_game: KeyCode::F7,
+            load_game: KeyCode::F8,
+            toggle_hud: KeyCode::F3,
+            toggle_gizmos: KeyCode::F4,
+            toggle_wireframe: KeyCode::F5,
+            toggle_diagnostics: KeyCode::F6,
+            toggle_fullscreen: KeyCode::F11,
+            toggle_console: KeyCode::Grave,
+            lock_on: KeyCode::Tab,
+            clear_lock: KeyCode::Escape,
+            attack: KeyCode::F,
+            interact: KeyCode::E,
+            dash: KeyCode::X,
+            ranged_attack: KeyCode::R,
+            queue_modifier: KeyCode::Z,
+            pause: KeyCode::Escape,
+            second_player: SecondKeyboardBindings::default(),
+        }
+    }
+}
+
+impl KeyBindings {
+    // every rebindable action with the name it's shown under, in the order the
+    // controls menu lists them
+    pub fn actions_mut(&mut self) -> Vec<(&'static str, &mut KeyCode)> {
+        vec![
+            ("Forward", &mut self.forward),
+            ("Back", &mut self.back),
+            ("Left", &mut self.left),
+            ("Right", &mut self.right),
+            ("Jump", &mut self.jump),
+            ("Sprint", &mut self.sprint),
+            ("Crouch", &mut self.crouch),
+            ("Dash", &mut self.dash),
+            ("Free look", &mut self.free_look),
+            ("Camera mode", &mut self.toggle_camera_mode),
+            ("Strategy camera", &mut self.toggle_strategy_camera),
+            ("Swap shoulder", &mut self.swap_shoulder),
+            ("Aim", &mut self.aim),
+            ("Attack", &mut self.attack),
+            ("Ranged attack", &mut self.ranged_attack),
+            ("Interact", &mut self.interact),
+            ("Queue orders", &mut self.queue_modifier),
+            ("Lock on", &mut self.lock_on),
+            ("Clear lock", &mut self.clear_lock),
+            ("Pause", &mut self.pause),
+            ("Console", &mut self.toggle_console),
+            ("Save game", &mut self.save_game),
+            ("Load game", &mut self.load_game),
+            ("Save settings", &mut self.save_settings),
+            ("Toggle hud", &mut self.toggle_hud),
+            ("Toggle gizmos", &mut self.toggle_gizmos),
+            ("Toggle wireframe", &mut self.toggle_wireframe),
+            ("Toggle diagnostics", &mut self.toggle_diagnostics),
+            ("Fullscreen", &mut self.toggle_fullscreen),
+        ]
+    }
+
+    pub fn actions(&self) -> Vec<(&'static str, KeyCode)> {
+        self.clone().actions_mut().into_iter().map(|(name, key)| (name, *key)).collect()
+    }
+}
+
+// one frame of intent for a single player, whatever device it came from
+#[derive(Clone, Copy, Default)]
+pub struct PlayerInput {
+    pub movement: Vec2,
+    pub sprint: bool,
+    pub toggle_sprint: bool,
+    pub crouch: bool,
+    pub toggle_crouch: bool,
+    pub jump: bool,
+    pub dash: bool,
+    pub free_look: bool,
+    pub toggle_camera_mode: bool,
+    pub toggle_strategy_camera: bool,
+    pub swap_shoulder: bool,
+    pub aim: bool,
+    // -1 turning left to 1 turning right, from keys rather than the mouse
+    pub turn: f32,
+}
+
+impl PlayerInput {
+    fn from_keyboard(keyboard_input: &Input<KeyCode>, bindings: &KeyBindings, captured: &KeyboardCaptured) -> Self {
+        if captured.0 { return PlayerInput::default(); }
+
+        let mut movement = Vec2::zero();
+        if keyboard_input.pressed(bindings.forward) { *movement.y_mut() += 1.; }
+        if keyboard_input.pressed(bindings.back) { *movement.y_mut() -= 1.; }
+        if keyboard_input.pressed(bindings.right) { *movement.x_mut() += 1.; }
+        if keyboard_input.pressed(bindings.left) { *movement.x_mut() -= 1.; }
+
+        if movement != Vec2::zero() { movement = movement.normalize(); }
+
+        PlayerInput {
+            movement,
+            sprint: keyboard_input.pressed(bindings.sprint),
+            toggle_sprint: keyboard_input.just_pressed(bindings.sprint),
+            crouch: keyboard_input.pressed(bindings.crouch),
+            toggle_crouch: keyboard_input.just_pressed(bindings.crouch),
+            jump: keyboard_input.just_pressed(bindings.jump),
+            dash: keyboard_input.just_pressed(bindings.dash),
+            free_look: keyboard_input.pressed(bindings.free_look),
+            toggle_camera_mode: keyboard_input.just_pressed(bindings.toggle_camera_mode),
+            toggle_strategy_camera: keyboard_input.just_pressed(bindings.toggle_strategy_camera),
+            swap_shoulder: keyboard_input.just_pressed(bindings.swap_shoulder),
+            aim: keyboard_input.pressed(bindings.aim),
+            turn: 0.,
+        }
+    }
+
+    // no mouse, so left and right turn instead of strafing
+    fn from_second_keyboard(keyboard_input: &Input<KeyCode>, bindings: &SecondKeyboardBindings, captured: &KeyboardCaptured) -> Self {
+        if captured.0 { return PlayerInput::default(); }
+
+        let mut movement = Vec2::zero();
+        if keyboard_input.pressed(bindings.forward) { *movement.y_mut() += 1.; }
+        if keyboard_input.pressed(bindings.back) { *movement.y_mut() -= 1.; }
+        let mut turn = 0.;
+        if keyboard_input.pressed(bindings.turn_right) { turn += 1.; }
+        if keyboard_input.pressed(bindings.turn_left) { turn -= 1.; }
+
+        PlayerInput {
+            movement,
+            sprint: keyboard_input.pressed(bindings.sprint),
+            toggle_sprint: keyboard_input.just_pressed(bindings.sprint),
+            crouch: keyboard_input.pressed(bindings.crouch),
+            toggle_crouch: keyboard_input.just_pressed(bindings.crouch),
+            jump: keyboard_input.just_pressed(bindings.jump),
+            dash: keyboard_input.just_pressed(bindings.dash),
+            turn,
+            ..Default::default()
+        }
+    }
+}
+
+// the second local player's keys, around the arrows so both players fit at one keyboard.
+// only movement, everything else stays with the first player
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SecondKeyboardBindings {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub turn_left: KeyCode,
+    pub turn_right: KeyCode,
+    pub jump: KeyCode,
+    pub sprint: KeyCode,
+    pub crouch: KeyCode,
+    pub dash: KeyCode,
+}
+
+impl Default for SecondKeyboardBindings {
+    fn default() -> Self {
+        SecondKeyboardBindings {
+            forward: KeyCode::Up,
+            back: KeyCode::Down,
+            turn_left: KeyCode::Left,
+            turn_right: KeyCode::Right,
+            jump: KeyCode::RControl,
+            sprint: KeyCode::Slash,
+            crouch: KeyCode::Period,
+            dash: KeyCode::Comma,
+        }
+    }
+}
+
+// set while something else, like the console, is taking typed keys, so they don't
+// also move the player
+#[derive(Default)]
+pub struct KeyboardCaptured(pub bool);
+
+// the mesh and material local players were spawned with, for spawning other players to match
+pub struct PlayerAppearance {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+// followed by how many players share this machine, e.g. --local-players 2
+const LOCAL_PLAYERS_FLAG: &str = "--local-players";
+// one per input source, the keyboard and mouse and the second set of keys
+const MAX_LOCAL_PLAYERS: usize = 2;
+// the second player's view is a window of its own, bevy 0.1 can't split one window
+const SECOND_VIEW_CAMERA: &str = "SecondView";
+
+pub struct LocalPlayers {
+    pub count: usize,
+}
+
+impl Default for LocalPlayers {
+    fn default() -> Self {
+        LocalPlayers { count: 1 }
+    }
+}
+
+impl LocalPlayers {
+    fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let index = match args.iter().position(|arg| arg == LOCAL_PLAYERS_FLAG) {
+            Some(index) => index,
+            None => return LocalPlayers::default(),
+        };
+        match args.get(index + 1).map(|count| count.parse::<usize>()) {
+            Some(Ok(count)) if (1..=MAX_LOCAL_PLAYERS).contains(&count) => LocalPlayers { count },
+            _ => {
+                eprintln!("{} takes 1 to {} players, e.g. {} 2", LOCAL_PLAYERS_FLAG, MAX_LOCAL_PLAYERS, LOCAL_PLAYERS_FLAG);
+                LocalPlayers::default()
+            }
+        }
+    }
+}
+
+// the window the second local player's camera draws into, once it's been opened
+#[derive(Default)]
+pub struct SecondView {
+    pub window: Option<WindowId>,
+}
+
+// where a player's camera draws. players without one still get a camera, it just renders nothing
+#[derive(Clone, Copy)]
+pub enum PlayerView {
+    Primary,
+    Second(WindowId),
+}
+
+impl PlayerView {
+    fn for_source(source: InputSource, second_view: &SecondView) -> Option<PlayerView> {
+        match source {
+            InputSource::KeyboardMouse => Some(PlayerView::Primary),
+            InputSource::SecondKeyboard => second_view.window.map(PlayerView::Second),
+            InputSource::None => None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LookCapture {
+    pub captured: bool,
+}
+
+#[derive(Default)]
+struct CursorGrab {
+    grabbed: bool,
+    // where the cursor was when the grab started
+    restore_to: Option<Vec2>,
+}
+
+#[derive(Default)]
+struct State {
+    mouse_motion_event_reader: EventReader<MouseMotion>,
+    mouse_wheel_event_reader: EventReader<MouseWheel>,
+    smoothed_look: Vec2,
+}
+
+// a player's frame goes input, then movement, then camera, each a stage of its own so
+// none of it hangs on the order systems happen to be added in:
+//  - PLAYER_INPUT_STAGE, after PRE_UPDATE: mouse look, zoom and the keyboard are read,
+//    once the console and pause menu have had their say over who gets the keys
+//  - UPDATE: the player moves, along with anything else that acts on this frame's input
+//  - PLAYER_CAMERA_STAGE, after UPDATE: the camera is placed from wherever the player
+//    ended up, before POST_UPDATE turns it into transforms for drawing
+// new systems that read devices belong in the first, anything that places the camera
+// off the player in the last
+pub const PLAYER_INPUT_STAGE: &str = "player_input";
+pub const PLAYER_CAMERA_STAGE: &str = "player_camera";
+
+// with the settings main already loaded for the window, so the file is only read once
+pub struct PlayerPlugin {
+    pub settings: Settings,
+}
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let settings = &self.settings;
+
+        app.add_plugin(ObjPlugin)
+            .init_resource::<State>()
+            .add_resource(settings.zoom.clone())
+            .init_resource::<LookCapture>()
+            .add_resource(LocalPlayers::from_args())
+            .init_resource::<SecondView>()
+            .init_resource::<CameraShake>()
+            .init_resource::<CameraPath>()
+            .add_startup_system(validate_zoom_settings.system())
+            .add_startup_system(load_player_appearance.system())
+            .add_startup_system(open_second_view.system())
+            .add_system(spawn_local_players.system())
+            .add_system(attach_missing_player_cameras.system())
+            .add_system(replace_failed_player_mesh.system())
+            .add_system(replace_failed_player_texture.system())
+            .add_plugin(PlayerSimulationPlugin)
+            // after the simulation plugin, which only puts in the defaults for servers
+            .add_resource(settings.input.clone())
+            .add_resource(settings.bindings.clone())
+            .add_resource(settings.sensitivity.clone())
+            .add_event::<settings::SaveSettings>()
+            // look capture first, so a click that starts dragging turns the camera the same frame
+            .add_system_to_stage(PLAYER_INPUT_STAGE, update_look_capture.system())
+            .add_system_to_stage(PLAYER_INPUT_STAGE, grab_cursor_while_looking.system())
+            .add_system_to_stage(PLAYER_INPUT_STAGE, process_mouse_events.system())
+            .add_stage_after(stage::UPDATE, PLAYER_CAMERA_STAGE)
+            .add_system_to_stage(PLAYER_CAMERA_STAGE, update_player_camera.system())
+            .add_system_to_stage(PLAYER_CAMERA_STAGE, update_camera_fov.system())
+            .add_system_to_stage(stage::LAST, settings::save_settings.system());
+    }
+}
+
+// just the movement integration, which is all a headless server runs. PlayerPlugin
+// adds it itself, so only add this directly when there is no window or renderer
+pub struct PlayerSimulationPlugin;
+
+impl Plugin for PlayerSimulationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.register_component::<MMOPlayer>()
+            .register_property::<CameraMode>()
+            .register_property::<InputSource>()
+            .add_resource(KeyBindings::default())
+            .init_resource::<KeyboardCaptured>()
+            .init_resource::<InputSettings>()
+            .init_resource::<CameraSensitivity>()
+            .init_resource::<KeyboardPlayerInput>()
+            .init_resource::<TerrainHeight>()
+            .init_resource::<WaterLevel>()
+            .init_resource::<PredictionHistory>()
+            .init_resource::<KnockbackSettings>()
+            .add_event::<EnteredWater>()
+            .add_event::<LeftWater>()
+            .add_event::<Teleported>()
+            .add_stage_after(stage::PRE_UPDATE, PLAYER_INPUT_STAGE)
+            .add_system_to_stage(PLAYER_INPUT_STAGE, read_keyboard_input.system())
+            .add_system(water::send_water_events.system())
+            .add_system(attach_missing_fixed_positions.system())
+            .add_system(speed_modifiers::tick_speed_modifiers.system())
+            .add_system(update_player_movement.system());
+    }
+}
+
+fn validate_zoom_settings(mut zoom: ResMut<ZoomSettings>) {
+    if zoom.min > zoom.max {
+        eprintln!("zoom min {} is greater than max {}, swapping them", zoom.min, zoom.max);
+        let min = zoom.max;
+        zoom.max = zoom.min;
+        zoom.min = min;
+    }
+}
+
+// picks the loader from the extension, bevy would otherwise only say it has no handler
+pub fn load_mesh(asset_server: &AssetServer, path: &str) -> Result<Handle<Mesh>, String> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        // ObjPlugin and bevy's own GltfPlugin
+        Some("obj") | Some("gltf") => asset_server.load(path).map_err(|err| err.to_string()),
+        // bevy 0.1's gltf loader can't read the binary chunk glb files keep their buffers in
+        Some("glb") => Err("binary .glb isn't supported, export it as .gltf with embedded buffers".to_string()),
+        Some(extension) => Err(format!("unsupported mesh format '.{}', use .obj or .gltf", extension)),
+        None => Err("no file extension to pick a loader from".to_string()),
+    }
+}
+
+// the mesh loads in the background while the loading screen is up.
+// to see the fallback, run the binary from a directory without res/, which logs
+// `no usable player mesh in [...], using fallback cube`, or overwrite res/person.obj
+// with junk, which logs `failed to parse the player mesh, using fallback cube` once
+// it's loaded. either way the players are cubes and the game carries on
+fn load_player_appearance(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut mesh = None;
+    for path in PLAYER_MESH_PATHS.iter().filter(|path| Path::new(path).exists()) {
+        match load_mesh(&asset_server, path) {
+            Ok(handle) => {
+                mesh = Some(handle);
+                break;
+            }
+            Err(err) => eprintln!("failed to load player mesh '{}': {}", path, err),
+        }
+    }
+    let mesh = mesh.unwrap_or_else(|| {
+        eprintln!("no usable player mesh in {:?}, using fallback cube", PLAYER_MESH_PATHS);
+        fallback_player_mesh(&mut meshes)
+    });
+
+    // editing any loaded asset on disk reloads it in place, so the texture can be
+    // painted on while the game is running
+    if let Err(err) = asset_server.watch_for_changes() {
+        eprintln!("failed to watch assets for changes: {:?}, they won't hot reload", err);
+    }
+    let texture = if Path::new(PLAYER_TEXTURE_PATH).exists() {
+        match asset_server.load(PLAYER_TEXTURE_PATH) {
+            Ok(texture) => Some(texture),
+            Err(err) => {
+                eprintln!("failed to load player texture '{}': {}, using a solid colour", PLAYER_TEXTURE_PATH, err);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let material = materials.add(StandardMaterial {
+        // the colour tints the texture, so it's only used without one
+        albedo: if texture.is_some() { Color::WHITE } else { PLAYER_COLOR },
+        albedo_texture: texture,
+        ..Default::default()
+    });
+    commands.insert_resource(PlayerAppearance { mesh, material });
+}
+
+// the texture can exist and still fail to decode, which only shows up once it's loaded
+fn replace_failed_player_texture(
+    asset_server: Res<AssetServer>,
+    appearance: Res<PlayerAppearance>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // only borrowed mutably when it changes, get_mut marks the material modified
+    let failed = materials
+        .get(&appearance.material)
+        .and_then(|material| material.albedo_texture)
+        .is_some_and(|texture| matches!(asset_server.get_load_state(texture), Some(LoadState::Failed(_))));
+    if !failed { return; }
+
+    eprintln!("failed to parse player texture '{}', using a solid colour", PLAYER_TEXTURE_PATH);
+    if let Some(material) = materials.get_mut(&appearance.material) {
+        material.albedo_texture = None;
+        material.albedo = PLAYER_COLOR;
+    }
+}
+
+// bevy 0.1 can't draw one window through several viewports, so the second player's split
+// is a second window, with its own pass drawing everything the main pass does through
+// the second player's camera. set up the same way as bevy's multiple_windows example
+#[allow(clippy::too_many_arguments)]
+fn open_second_view(
+    local_players: Res<LocalPlayers>,
+    window_settings: Res<WindowSettings>,
+    clear_color: Res<ClearColor>,
+    msaa: Res<Msaa>,
+    mut second_view: ResMut<SecondView>,
+    mut create_window_events: ResMut<Events<CreateWindow>>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    mut render_graph: ResMut<RenderGraph>,
+) {
+    if local_players.count < 2 { return; }
+
+    let window = WindowId::new();
+    create_window_events.send(CreateWindow {
+        id: window,
+        descriptor: WindowDescriptor {
+            width: window_settings.width,
+            height: window_settings.height,
+            vsync: window_settings.vsync,
+            title: format!("{} - player 2", WINDOW_TITLE),
+            ..Default::default()
+        },
+    });
+
+    render_graph.add_node("second_view_swap_chain", WindowSwapChainNode::new(window));
+    render_graph.add_node(
+        "second_view_depth_texture",
+        WindowTextureNode::new(window, TextureDescriptor {
+            format: TextureFormat::Depth32Float,
+            usage: TextureUsage::OUTPUT_ATTACHMENT,
+            sample_count: msaa.samples,
+            ..Default::default()
+        }),
+    );
+    render_graph.add_system_node("second_view_camera", CameraNode::new(SECOND_VIEW_CAMERA));
+
+    let mut pass = PassNode::<&MainPass>::new(PassDescriptor {
+        color_attachments: vec![msaa.color_attachment_descriptor(
+            TextureAttachment::Input("color_attachment".to_string()),
+            TextureAttachment::Input("color_resolve_target".to_string()),
+            Operations { load: LoadOp::Clear(clear_color.0), store: true },
+        )],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachmentDescriptor {
+            attachment: TextureAttachment::Input("depth".to_string()),
+            depth_ops: Some(Operations { load: LoadOp::Clear(1.0), store: true }),
+            stencil_ops: None,
+        }),
+        sample_count: msaa.samples,
+    });
+    pass.add_camera(SECOND_VIEW_CAMERA);
+    active_cameras.add(SECOND_VIEW_CAMERA);
+    render_graph.add_node("second_view_pass", pass);
+
+    let color_slot = if msaa.samples > 1 { "color_resolve_target" } else { "color_attachment" };
+    render_graph
+        .add_slot_edge("second_view_swap_chain", WindowSwapChainNode::OUT_TEXTURE, "second_view_pass", color_slot)
+        .unwrap();
+    render_graph
+        .add_slot_edge("second_view_depth_texture", WindowTextureNode::OUT_TEXTURE, "second_view_pass", "depth")
+        .unwrap();
+    render_graph.add_node_edge("second_view_camera", "second_view_pass").unwrap();
+    if msaa.samples > 1 {
+        render_graph.add_node(
+            "second_view_multisampled_color",
+            WindowTextureNode::new(window, TextureDescriptor {
+                size: Extent3d { depth: 1, width: 1, height: 1 },
+                mip_level_count: 1,
+                sample_count: msaa.samples,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Bgra8UnormSrgb,
+                usage: TextureUsage::OUTPUT_ATTACHMENT,
+            }),
+        );
+        render_graph
+            .add_slot_edge("second_view_multisampled_color", WindowTextureNode::OUT_TEXTURE, "second_view_pass", "color_attachment")
+            .unwrap();
+    }
+    second_view.window = Some(window);
+}
+
+// the first player is on the keyboard and mouse in the main window, the second on
+// their own keys in the second window
+#[allow(clippy::too_many_arguments)]
+fn spawn_local_players(
+    mut commands: Commands,
+    mut game_started_reader: Local<EventReader<GameStarted>>,
+    game_started_events: Res<Events<GameStarted>>,
+    appearance: Res<PlayerAppearance>,
+    zoom: Res<ZoomSettings>,
+    local_players: Res<LocalPlayers>,
+    second_view: Res<SecondView>,
+    spawn: Res<SpawnConfig>,
+) {
+    if game_started_reader.iter(&game_started_events).last().is_none() { return; }
+
+    let camera_distance = spawn.camera_distance.unwrap_or(zoom.initial).clamp(zoom.min, zoom.max);
+    for index in 0..local_players.count {
+        let input_source = match index {
+            0 => InputSource::KeyboardMouse,
+            1 => InputSource::SecondKeyboard,
+            _ => InputSource::None,
+        };
+        let player_entity = spawn_player(
+            &mut commands,
+            appearance.mesh,
+            appearance.material,
+            spawn.position + Vec3::new(index as f32 * 5., 0., 0.),
+            PlayerView::for_source(input_source, &second_view),
+            MMOPlayer {
+                input_source,
+                yaw: spawn.yaw,
+                camera_yaw: spawn.yaw,
+                camera_pitch: spawn.camera_pitch,
+                camera_distance,
+                target_camera_distance: camera_distance,
+                ..Default::default()
+            },
+        );
+        commands.insert_one(player_entity, PlayerName(format!("Player {}", index + 1)));
+    }
+}
+
+pub fn spawn_player(
+    commands: &mut Commands,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    position: Vec3,
+    view: Option<PlayerView>,
+    player: MMOPlayer,
+) -> Entity {
+    let camera_entity = commands
+        .spawn(player_camera(view, &player))
+        .current_entity();
+
+    let player_entity = commands
+        .spawn(PbrComponents {
+            mesh,
+            material,
+            translation: Translation(position),
+            rotation: Rotation(Quat::from_rotation_y(-player.yaw)),
+            ..Default::default()
+        }).with(MMOPlayer {
+            camera_entity,
+            ..player
+        })
+        .with(FixedPosition::new(position))
+        .with(Velocity::default())
+        .with(Inventory::default())
+        .with(Health::default())
+        .with(Stamina::default())
+        .with(AttackCooldown::default())
+        .with(Experience::default())
+        .with(SpeedModifiers::default())
+        .with(Buffs::default())
+        .with(Dash::default())
+        .with(KnockbackImpulse::default())
+        .with(Focusable { radius: PLAYER_FOCUS_RADIUS })
+        .current_entity().unwrap();
+
+    commands
+        .push_children(player_entity, &[camera_entity.unwrap()]);
+    player_entity
+}
+
+// starts where the third person camera comes to rest, so the first frame is already
+// framed instead of the camera sliding out of the player
+fn player_camera(view: Option<PlayerView>, player: &MMOPlayer) -> Camera3dComponents {
+    let offset = camera_orbit(player) * player.camera_distance;
+    let shoulder = look_rotation(offset, Vec3::zero()) * player.shoulder_offset;
+    let mut camera = Camera3dComponents {
+        translation: Translation(offset + shoulder),
+        rotation: Rotation(look_rotation(offset + shoulder, shoulder)),
+        ..Default::default()
+    };
+    match view {
+        Some(PlayerView::Primary) => {}
+        Some(PlayerView::Second(window)) => {
+            camera.camera.name = Some(SECOND_VIEW_CAMERA.to_string());
+            camera.camera.window = window;
+        }
+        None => camera.camera.name = None,
+    }
+    camera
+}
+
+// the direction from the player out to the orbit camera, in the player's frame. the
+// camera is parented to the body, so only the yaw on top of the body's is applied
+fn camera_orbit(player: &MMOPlayer) -> Vec3 {
+    let orbit = Vec3::new(0., player.camera_pitch.cos(), -player.camera_pitch.sin()).normalize();
+    Quat::from_rotation_y(player.yaw - player.camera_yaw) * orbit
+}
+
+fn attach_missing_player_cameras(
+    mut commands: Commands,
+    second_view: Res<SecondView>,
+    mut query: Query<Without<RemotePlayer, (Entity, &mut MMOPlayer)>>,
+) {
+    for (entity, mut player) in &mut query.iter() {
+        if player.camera_entity.is_some() { continue; }
+
+        let view = PlayerView::for_source(player.input_source, &second_view);
+        let camera_entity = commands
+            .spawn(player_camera(view, &player))
+            .current_entity()
+            .unwrap();
+        commands.push_children(entity, &[camera_entity]);
+        player.camera_entity = Some(camera_entity);
+    }
+}
+
+// players loaded from scenes only carry their reflected fields, so pick up
+// the simulation from wherever they were placed
+#[allow(clippy::type_complexity)]
+fn attach_missing_fixed_positions(
+    mut commands: Commands,
+    mut query: Query<Without<RemotePlayer, Without<FixedPosition, (Entity, &MMOPlayer, &Translation)>>>,
+) {
+    for (entity, _player, translation) in &mut query.iter() {
+        commands.insert(entity, (
+            FixedPosition::new(translation.0),
+            Velocity::default(),
+            Inventory::default(),
+            Health::default(),
+            Stamina::default(),
+            AttackCooldown::default(),
+            Experience::default(),
+            SpeedModifiers::default(),
+            Buffs::default(),
+            Dash::default(),
+            KnockbackImpulse::default(),
+        ));
+    }
+}
+
+// stand-in for the player model when it is missing or fails to parse,
+// run the binary without the res/ folder present to see it in place
+fn fallback_player_mesh(meshes: &mut Assets<Mesh>) -> Handle<Mesh> {
+    meshes.add(Mesh::from(shape::Cube { size: 2.0 }))
+}
+
+fn replace_failed_player_mesh(
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut query: Query<(&MMOPlayer, &mut Handle<Mesh>)>,
+) {
+    for (_player, mut mesh) in &mut query.iter() {
+        if let Some(LoadState::Failed(_)) = asset_server.get_load_state(*mesh) {
+            eprintln!("failed to parse the player mesh, using fallback cube");
+            *mesh = fallback_player_mesh(&mut meshes);
+        }
+    }
+}
+
+// escape always releases it, even when it only let go of a lock and play carries on. it
+// then stays released until the next click, or an always-on capture would take it straight back
+fn update_look_capture(
+    mut released: Local<bool>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    bindings: Res<KeyBindings>,
+    game_state: Res<GameState>,
+    sensitivity: Res<CameraSensitivity>,
+    mut capture: ResMut<LookCapture>,
+) {
+    if keyboard_input.just_pressed(bindings.pause) { *released = true; }
+    let clicked = [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+        .iter()
+        .any(|&button| mouse_button_input.just_pressed(button));
+    if clicked { *released = false; }
+
+    capture.captured = match sensitivity.rotate_button.mouse_button() {
+        Some(button) => mouse_button_input.pressed(button),
+        None => true,
+    };
+    if *released || *game_state != GameState::Playing { capture.captured = false; }
+}
+
+// bevy 0.1's window has no cursor settings, so this goes through winit. the cursor is
+// hidden and held in the window while it turns the camera, then put back where it was
+fn grab_cursor_while_looking(
+    mut grab: Local<CursorGrab>,
+    capture: Res<LookCapture>,
+    cursor: Res<CursorPosition>,
+    winit_windows: Res<WinitWindows>,
+) {
+    if capture.captured == grab.grabbed { return; }
+    let window = match winit_windows.get_window(WindowId::primary()) {
+        Some(window) => window,
+        None => return,
+    };
+
+    grab.grabbed = capture.captured;
+    if grab.grabbed {
+        grab.restore_to = cursor.0;
+        if let Err(err) = window.set_cursor_grab(true) {
+            eprintln!("couldn't grab the cursor: {}, it can leave the window while looking", err);
+        }
+        window.set_cursor_visible(false);
+        return;
+    }
+
+    if let Err(err) = window.set_cursor_grab(false) { eprintln!("couldn't release the cursor: {}", err); }
+    window.set_cursor_visible(true);
+    // the tracked position is from the bottom left, winit's from the top left
+    if let Some(position) = grab.restore_to.take() {
+        let height = window.inner_size().height as f32;
+        let position = PhysicalPosition::new(position.x() as f64, (height - position.y()) as f64);
+        if let Err(err) = window.set_cursor_position(position) { eprintln!("couldn't put the cursor back: {}", err); }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_mouse_events(
+    time: Res<Time>,
+    mut state: ResMut<State>, 
+    sensitivity: Res<CameraSensitivity>,
+    zoom: Res<ZoomSettings>,
+    capture: Res<LookCapture>,
+    game_state: Res<GameState>,
+    mouse_motion_events: Res<Events<MouseMotion>>,
+    mouse_wheel_events: Res<Events<MouseWheel>>,
+    mut query: Query<&mut MMOPlayer>,
+) {
+    let mut look = total_mouse_motion(&mut state.mouse_motion_event_reader, &mouse_motion_events);
+    if !capture.captured { look = Vec2::zero(); }
+
+    // exponential smoothing, the window is roughly how long a single jolt takes to fade out
+    let window = sensitivity.mouse_smoothing.max(0.);
+    let blend = if window > 0. { 1. - (-time.delta_seconds / window).exp() } else { 1. };
+    state.smoothed_look = state.smoothed_look.lerp(look, blend);
+    let look = state.smoothed_look;
+
+    // same for the wheel, a fast spin sends a burst of notches in one frame
+    let mut zoom_delta = 0.;
+    for event in state.mouse_wheel_event_reader.iter(&mouse_wheel_events) {
+        zoom_delta += event.y;
+    }
+    // the events above are still drained while paused, so nothing piles up for the unpause
+    if *game_state != GameState::Playing { return; }
+
+    let zoom_sign = if zoom.invert { -1.0 } else { 1.0 };
+
+    for mut player in &mut query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        apply_look(&mut player, look, &sensitivity);
+        player.target_camera_distance -= zoom_sign * zoom_delta * zoom.step;
+    }
+}
+
+// high polling rate mice report several times a frame, all of it counts
+fn total_mouse_motion(reader: &mut EventReader<MouseMotion>, events: &Events<MouseMotion>) -> Vec2 {
+    reader.iter(events).fold(Vec2::zero(), |total, event| total + event.delta)
+}
+
+// mouse y grows downwards, so pushing the mouse away raises the view unless inverted.
+// look is in pixels, not scaled by the frame time, so the same hand movement turns the
+// camera the same amount whatever the frame rate
+fn apply_look(player: &mut MMOPlayer, look: Vec2, sensitivity: &CameraSensitivity) {
+    let pitch_sign = if sensitivity.invert_y { 1.0 } else { -1.0 };
+    player.camera_yaw += look.x() * sensitivity.yaw;
+    player.camera_pitch += pitch_sign * look.y() * sensitivity.pitch;
+}
+
+// the camera offset is relative to the player, so ground_height is too.
+// offsets that would dip below it slide along the ground towards the player instead,
+// which shrinks the effective distance continuously as pitch increases
+fn keep_camera_above_ground(offset: Vec3, ground_height: f32) -> Vec3 {
+    let min_y = ground_height + CAMERA_GROUND_CLEARANCE;
+    if offset.y() < min_y {
+        Vec3::new(offset.x(), min_y, offset.z())
+    } else {
+        offset
+    }
+}
+
+// face_toward degenerates when the view direction is parallel to the up vector,
+// so when looking (nearly) straight up or down, use the player's forward axis as up
+pub fn look_rotation(eye: Vec3, target: Vec3) -> Quat {
+    let up = Vec3::unit_y();
+    let dir = (target - eye).normalize();
+    let up = if dir.dot(up).abs() > 0.999 { Vec3::unit_z() } else { up };
+    Mat4::face_toward(eye, target, up).to_scale_rotation_translation().1
+}
+
+fn move_towards(current: Vec2, target: Vec2, max_delta: f32) -> Vec2 {
+    let delta = target - current;
+    let distance = delta.length();
+    if distance <= max_delta || distance == 0. {
+        target
+    } else {
+        current + delta / distance * max_delta
+    }
+}
+
+// the horizontal direction from position to target, or none once within arrival_radius of it
+pub fn direction_towards(position: Vec3, target: Vec3, arrival_radius: f32) -> Option<Vec3> {
+    let mut to_target = target - position;
+    *to_target.y_mut() = 0.;
+    if to_target.length() <= arrival_radius { return None; }
+    Some(to_target.normalize())
+}
+
+// the yaw that faces the body along a horizontal direction, the inverse of how
+// movement turns yaw into a forward vector
+pub fn yaw_towards(direction: Vec3) -> f32 {
+    (-direction.x()).atan2(direction.z())
+}
+
+// turns a world space move target into local movement input, the same frame as
+// keyboard input. once the player is close enough the next queued waypoint takes over,
+// or with none left the target is cleared
+fn steer_towards_target(player: &mut MMOPlayer, position: Vec3, forward: Vec3, right: Vec3) -> Option<Vec2> {
+    let direction = loop {
+        let target = player.move_target?;
+        match direction_towards(position, target, player.arrival_radius) {
+            Some(direction) => break direction,
+            None => player.move_target = player.waypoints.pop_front(),
+        }
+    };
+
+    let steering = Vec2::new(direction.dot(right), direction.dot(forward));
+    if steering == Vec2::zero() { None } else { Some(steering.normalize()) }
+}
+
+#[derive(Default)]
+struct PendingPresses {
+    jump: bool,
+    dash: bool,
+}
+
+// each local player's input for this frame, read ahead of the movement system
+#[derive(Default)]
+struct KeyboardPlayerInput {
+    keyboard_mouse: PlayerInput,
+    second_keyboard: PlayerInput,
+}
+
+impl KeyboardPlayerInput {
+    fn get(&self, source: InputSource) -> PlayerInput {
+        match source {
+            InputSource::KeyboardMouse => self.keyboard_mouse,
+            InputSource::SecondKeyboard => self.second_keyboard,
+            InputSource::None => PlayerInput::default(),
+        }
+    }
+}
+
+// what tapping has turned on, for the holds that are set to toggle instead
+#[derive(Default)]
+struct ToggledInputs {
+    sprint: bool,
+    crouch: bool,
+}
+
+// movement is integrated in fixed steps so it plays out the same at any frame rate,
+// then the rendered translation is interpolated between the last two steps
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn update_player_movement(
+    time: Res<Time>,
+    fixed: Res<FixedTimestep>,
+    game_state: Res<GameState>,
+    keyboard_input: Res<KeyboardPlayerInput>,
+    terrain: Res<TerrainHeight>,
+    water: Res<WaterLevel>,
+    knockback: Res<KnockbackSettings>,
+    mut history: ResMut<PredictionHistory>,
+    mut pending: Local<[PendingPresses; 2]>,
+    mut player_query: Query<Without<RemotePlayer, (
+        &mut MMOPlayer,
+        &Stamina,
+        &SpeedModifiers,
+        &mut Dash,
+        &mut KnockbackImpulse,
+        &mut FixedPosition,
+        &mut Velocity,
+        &mut Translation,
+        &mut Rotation,
+    )>>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    // presses are only seen for one frame, so hold them for frames that run no steps
+    let mut inputs = [keyboard_input.keyboard_mouse, keyboard_input.second_keyboard];
+    for (input, pending) in inputs.iter_mut().zip(pending.iter_mut()) {
+        input.jump |= pending.jump;
+        input.dash |= pending.dash;
+        pending.jump = input.jump && fixed.steps() == 0;
+        pending.dash = input.dash && fixed.steps() == 0;
+    }
+
+    for (mut player, stamina, modifiers, mut dash, mut impulse, mut position, mut velocity, mut translation, mut rotation) in &mut player_query.iter() {
+        let mut input = match player.input_source {
+            // the movement keys pan the strategy camera instead
+            InputSource::KeyboardMouse if player.camera_mode == CameraMode::Strategy => PlayerInput { movement: Vec2::zero(), ..inputs[0] },
+            InputSource::KeyboardMouse => inputs[0],
+            InputSource::SecondKeyboard => inputs[1],
+            InputSource::None => PlayerInput::default(),
+        };
+        input.sprint &= stamina.can_sprint();
+        // turned by the frame rather than the step, like the mouse
+        player.camera_yaw += input.turn * player.turn_speed * time.delta_seconds;
+        for _ in 0..fixed.steps() {
+            let step = resolve_step_input(&mut player, modifiers, &mut dash, &mut impulse, &knockback, position.current, &input, fixed.step);
+            // gravity is applied before the landing is found, so this is the speed it hit at
+            let falling = !player.grounded && !player.swimming;
+            let impact_speed = player.gravity * fixed.step - player.vertical_velocity;
+            let state = step_movement(player.movement_state(position.current), &step, &player, &terrain, &water, fixed.step);
+            player.set_movement_state(&state);
+            if falling && state.grounded { player.landing_speed = Some(impact_speed); }
+            position.previous = position.current;
+            position.current = state.position;
+
+            // the keyboard player's steps are kept for replaying on top of server corrections
+            if player.input_source == InputSource::KeyboardMouse { history.record(step); }
+            input.jump = false;
+            input.dash = false;
+        }
+        if fixed.steps() > 0 { velocity.0 = (position.current - position.previous) / fixed.step; }
+
+        let ease = (-CORRECTION_SMOOTHING * time.delta_seconds).exp();
+        position.correction *= ease;
+        translation.0 = position.interpolated(fixed.alpha());
+        rotation.0 = Quat::from_rotation_y(-player.yaw);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn read_keyboard_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    bindings: Res<KeyBindings>,
+    captured: Res<KeyboardCaptured>,
+    settings: Res<InputSettings>,
+    sensitivity: Res<CameraSensitivity>,
+    mut toggled: Local<[ToggledInputs; 2]>,
+    mut input: ResMut<KeyboardPlayerInput>,
+) {
+    input.keyboard_mouse = PlayerInput::from_keyboard(&keyboard_input, &bindings, &captured);
+    input.keyboard_mouse.aim |= settings
+        .aim_button
+        .filter(|&button| sensitivity.rotate_button.mouse_button() != Some(button))
+        .is_some_and(|button| mouse_button_input.pressed(button));
+    input.second_keyboard = PlayerInput::from_second_keyboard(&keyboard_input, &bindings.second_player, &captured);
+    apply_toggles(&mut input.keyboard_mouse, &mut toggled[0], &settings);
+    apply_toggles(&mut input.second_keyboard, &mut toggled[1], &settings);
+}
+
+// the toggle stays on with no movement keys held, sprinting only speeds up actual movement
+fn apply_toggles(input: &mut PlayerInput, toggled: &mut ToggledInputs, settings: &InputSettings) {
+    if settings.sprint_toggle {
+        if input.toggle_sprint { toggled.sprint = !toggled.sprint; }
+        input.sprint = toggled.sprint;
+    }
+    if settings.crouch_toggle {
+        if input.toggle_crouch { toggled.crouch = !toggled.crouch; }
+        input.crouch = toggled.crouch;
+    }
+}
+
+// settles everything about a step that depends on more than the movement state,
+// so the step itself can be replayed exactly
+#[allow(clippy::too_many_arguments)]
+fn resolve_step_input(
+    player: &mut MMOPlayer,
+    modifiers: &SpeedModifiers,
+    dash: &mut Dash,
+    impulse: &mut KnockbackImpulse,
+    knockback: &KnockbackSettings,
+    position: Vec3,
+    input: &PlayerInput,
+    dt: f32,
+) -> StepInput {
+    // the body follows the camera unless free look holds it in place,
+    // releasing free look snaps it back in line with the camera. a lock on sets the yaw itself
+    if !input.free_look && player.lock_target.is_none() { player.yaw = player.camera_yaw; }
+    let facing = Quat::from_rotation_y(-player.yaw);
+    let forward = facing * Vec3::unit_z();
+    let right = facing * -Vec3::unit_x();
+
+    let mut movement = input.movement;
+    if movement != Vec2::zero() {
+        player.move_target = None;
+        player.waypoints.clear();
+    } else if let Some(steering) = steer_towards_target(player, position, forward, right) {
+        movement = steering;
+    }
+    // a crouch can't start in the air, and jumping out of one stands back up
+    player.crouching = input.crouch && player.grounded && !player.swimming && !input.jump;
+    player.sprinting = movement != Vec2::zero() && input.sprint && !player.crouching;
+    let mut move_speed = modifiers.apply(player.move_speed);
+    if player.sprinting { move_speed *= player.sprint_multiplier; }
+    if player.crouching { move_speed *= player.crouch_speed_multiplier; }
+
+    if input.dash {
+        let local = if movement != Vec2::zero() { movement.normalize() } else { Vec2::new(0., 1.) };
+        dash.try_start(forward * local.y() + right * local.x());
+    }
+    let dash_velocity = if dash.dashing() {
+        let velocity = dash.direction * dash.speed;
+        Some(Vec2::new(velocity.dot(right), velocity.dot(forward)))
+    } else {
+        None
+    };
+    dash.tick(dt);
+    // decays in world space, so turning while being pushed doesn't change where it goes
+    let knockback_velocity = Vec2::new(impulse.velocity.dot(right), impulse.velocity.dot(forward));
+    impulse.tick(knockback, dt);
+
+    StepInput {
+        movement,
+        dash: dash_velocity,
+        knockback: knockback_velocity,
+        move_speed,
+        jump: input.jump,
+        yaw: player.yaw,
+    }
+}
+
+// the downhill direction in the player's local frame, when the ground under position
+// is too steep to walk on
+fn steep_slope_downhill(terrain: &TerrainHeight, position: Vec3, forward: Vec3, right: Vec3, player: &MMOPlayer) -> Option<Vec2> {
+    let normal = terrain.normal_at(position.x(), position.z());
+    if normal.y().min(1.).acos() <= player.max_walkable_slope { return None; }
+
+    // the normal leans away from the hill, so its flat part points downhill
+    let downhill = Vec2::new(normal.dot(right), normal.dot(forward));
+    if downhill == Vec2::zero() { None } else { Some(downhill.normalize()) }
+}
+
+// a pure function of the state and input, the player is only read for its tuning
+pub fn step_movement(
+    state: MovementState,
+    input: &StepInput,
+    player: &MMOPlayer,
+    terrain: &TerrainHeight,
+    water: &WaterLevel,
+    dt: f32,
+) -> MovementState {
+    let mut state = state;
+    let facing = Quat::from_rotation_y(-input.yaw);
+    let forward = facing * Vec3::unit_z();
+    let right = facing * -Vec3::unit_x();
+
+    let moving = input.movement != Vec2::zero();
+
+    // velocity is in the player's local frame, x right and y forward, like the input
+    let rate = if moving { player.acceleration } else { player.friction };
+    let mut target = input.movement * input.move_speed;
+    if state.swimming { target *= player.swim_speed_multiplier; }
+    let slope = if state.grounded { steep_slope_downhill(terrain, state.position, forward, right, player) } else { None };
+    state.velocity = match (input.dash, slope) {
+        (Some(dash), _) => dash,
+        // on a steep slope only the sideways part of the velocity is steered, downhill it
+        // just keeps picking up speed and input can't push back up the hill
+        (None, Some(downhill)) => {
+            let along = state.velocity.dot(downhill).max(0.);
+            let across = state.velocity - downhill * state.velocity.dot(downhill);
+            let target_across = target - downhill * target.dot(downhill);
+            move_towards(across, target_across, rate * dt) + downhill * (along + player.slide_acceleration * dt)
+        }
+        (None, None) => move_towards(state.velocity, target, rate * dt),
+    };
+    // kept out of the velocity, so friction and steering don't eat into the push
+    let movement = (state.velocity + input.knockback) * dt;
+
+    let mut current = state.position + forward * movement.y() + right * movement.x();
+
+    let ground = terrain.height_at(current.x(), current.z());
+    // a press is held for a moment so one just before landing still jumps, and the ground
+    // is remembered for a moment so one just after walking off an edge does too
+    if input.jump { state.jump_buffer_timer = player.jump_buffer; }
+    // swimmers can only jump out once they've floated up near the surface
+    let can_jump = if state.swimming {
+        water.depth(current.y()) <= player.swim_depth
+    } else {
+        state.grounded || state.time_since_grounded < player.coyote_time
+    };
+    if can_jump && state.jump_buffer_timer > 0. {
+        state.vertical_velocity = player.jump_speed;
+        state.grounded = false;
+        state.swimming = false;
+        state.jump_buffer_timer = 0.;
+        // so the coyote window can't be used for a second jump
+        state.time_since_grounded = player.coyote_time;
+    }
+
+    if state.swimming {
+        let depth = water.depth(current.y());
+        let lift = if depth > 0. { player.buoyancy * (depth / player.swim_depth).min(1.) } else { 0. };
+        state.vertical_velocity += (lift - player.swim_gravity) * dt;
+        state.vertical_velocity *= (1. - player.water_drag * dt).max(0.);
+        *current.y_mut() += state.vertical_velocity * dt;
+        if current.y() < ground {
+            *current.y_mut() = ground;
+            state.vertical_velocity = state.vertical_velocity.max(0.);
+        }
+    // grounded players stick to the ground when walking down slopes, instead
+    // of launching off every crest and falling back onto it
+    } else if state.grounded && current.y() - ground <= GROUND_SNAP_DISTANCE {
+        *current.y_mut() = ground;
+        state.vertical_velocity = 0.0;
+    } else {
+        state.vertical_velocity -= player.gravity * dt;
+        *current.y_mut() += state.vertical_velocity * dt;
+        state.grounded = current.y() <= ground;
+        if state.grounded {
+            *current.y_mut() = ground;
+            state.vertical_velocity = 0.0;
+        }
+    }
+
+    // swimming starts deeper than it stops, and floating settles between the two, so
+    // bobbing at the surface doesn't flip between them. stopping is wading out or a jump
+    let depth = water.depth(current.y());
+    if !state.swimming && depth > player.swim_depth {
+        state.swimming = true;
+        state.grounded = false;
+    } else if state.swimming && depth < player.swim_depth * 0.25 {
+        state.swimming = false;
+    }
+
+    if state.grounded {
+        state.time_since_grounded = 0.;
+    } else if state.swimming {
+        state.time_since_grounded = player.coyote_time;
+    } else {
+        state.time_since_grounded += dt;
+    }
+    state.jump_buffer_timer = (state.jump_buffer_timer - dt).max(0.);
+
+    state.position = current;
+    state
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_player_camera(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    keyboard_input: Res<KeyboardPlayerInput>,
+    zoom: Res<ZoomSettings>,
+    terrain: Res<TerrainHeight>,
+    shake: Res<CameraShake>,
+    camera_path: Res<CameraPath>,
+    mut player_query: Query<Without<RemotePlayer, (&mut MMOPlayer, &Translation, &Rotation)>>,
+    camera_query: Query<(&mut Translation, &mut Rotation)>,
+    focus_query: Query<&Transform>,
+) {
+    // the camera holds wherever it was when the game paused, and a cinematic path drives it itself
+    if *game_state != GameState::Playing || camera_path.playing { return; }
+
+    for (mut player, translation, rotation) in &mut player_query.iter() {
+        let input = keyboard_input.get(player.input_source);
+
+        player.camera_pitch = player.camera_pitch.max(1f32.to_radians()).min(179f32.to_radians());
+        player.target_camera_distance = player.target_camera_distance.clamp(zoom.min, zoom.max);
+        let ease = 1. - (-zoom.smoothing * time.delta_seconds).exp();
+        player.camera_distance += (player.target_camera_distance - player.camera_distance) * ease;
+        player.camera_distance = player.camera_distance.clamp(zoom.min, zoom.max);
+
+        if input.toggle_camera_mode {
+            player.camera_mode = match player.camera_mode {
+                CameraMode::ThirdPerson => CameraMode::FirstPerson,
+                CameraMode::FirstPerson => CameraMode::ThirdPerson,
+                CameraMode::Strategy => CameraMode::Strategy,
+            };
+        }
+        // the pan starts over above the player every time, and leaving glides back to
+        // following them through the same smoothing as any other camera move
+        if input.toggle_strategy_camera {
+            player.camera_mode = match player.camera_mode {
+                CameraMode::Strategy => CameraMode::ThirdPerson,
+                _ => CameraMode::Strategy,
+            };
+            player.strategy_pan = Vec3::zero();
+        }
+        // the camera eases towards its new position below, so the swap is smoothed for free
+        if input.swap_shoulder { *player.shoulder_offset.x_mut() *= -1.; }
+        // smoothed the same way as the shoulder swap
+        let crouch = Vec3::unit_y() * if player.crouching { player.crouch_height_offset } else { 0. };
+        // the overhead view has nothing to aim with
+        player.ads = input.aim && player.camera_mode != CameraMode::Strategy;
+        let ads_target = if player.ads { 1. } else { 0. };
+        let ads_ease = 1. - (-player.ads_smoothing * time.delta_seconds).exp();
+        player.ads_blend += (ads_target - player.ads_blend) * ads_ease;
+        // laid over the zoom rather than written into it, so the wheel still sets the hip
+        // distance while aiming and letting go goes back to exactly that
+        let ads_distance = player.ads_distance.min(player.camera_distance);
+        let distance = player.camera_distance + (ads_distance - player.camera_distance) * player.ads_blend;
+        let mut ads_shoulder = player.ads_shoulder_offset;
+        // aims over whichever shoulder the camera is on
+        *ads_shoulder.x_mut() *= player.shoulder_offset.x().signum();
+        let shoulder_offset = player.shoulder_offset.lerp(ads_shoulder, player.ads_blend);
+
+        if let Some(camera_entity) = player.camera_entity {
+            // exponential smoothing, so the follow rate doesn't depend on frame rate
+            let follow = 1. - (-player.camera_smoothing * time.delta_seconds).exp();
+
+            let orbit = camera_orbit(&player);
+            let focus = player.camera_focus.map(|focus| focus_query.get::<Transform>(focus));
+            let (cam_pos, cam_target) = match (player.camera_mode, focus) {
+                // the focus was despawned, so go back to following the player
+                (_, Some(Err(_))) => {
+                    player.camera_focus = None;
+                    continue;
+                }
+                // looks down at the panned point from a fixed angle, ignoring any focus
+                (CameraMode::Strategy, _) => {
+                    let look_at = translation.0 + player.strategy_pan;
+                    let camera_world = look_at + strategy_camera_offset(player.camera_distance);
+                    let to_local = rotation.0.conjugate();
+                    (to_local * (camera_world - translation.0), to_local * (look_at - translation.0))
+                }
+                // the camera still hangs off the player, so the orbit is worked out in world
+                // space around the focus and then brought back into the player's frame
+                (_, Some(Ok(focus))) => {
+                    let focus = Vec3::from(focus.value.w_axis().truncate());
+                    let offset = rotation.0 * orbit * player.camera_distance;
+                    let ground = terrain.height_at(focus.x() + offset.x(), focus.z() + offset.z()) - focus.y();
+                    let camera_world = focus + keep_camera_above_ground(offset, ground);
+                    let to_local = rotation.0.conjugate();
+                    (to_local * (camera_world - translation.0), to_local * (focus - translation.0))
+                }
+                (CameraMode::ThirdPerson, None) => {
+                    // shifting the eye and target together frames the player off to one side
+                    // without changing where the camera looks
+                    let offset = orbit * distance;
+                    let shoulder = look_rotation(offset, Vec3::zero()) * shoulder_offset;
+                    let offset = offset + shoulder;
+                    let camera_world = translation.0 + rotation.0 * offset;
+                    let ground = terrain.height_at(camera_world.x(), camera_world.z()) - translation.0.y();
+                    (keep_camera_above_ground(offset - crouch, ground), shoulder - crouch)
+                }
+                // look from the head along the direction the orbit camera would be facing
+                (CameraMode::FirstPerson, None) => {
+                    let head = player.head_offset - crouch;
+                    (head, head - orbit)
+                }
+            };
+            // one lookup for both components, this runs for every player every frame
+            let mut camera = match camera_query.entity(camera_entity) {
+                Ok(camera) => camera,
+                Err(_) => continue,
+            };
+            if let Some((mut cam_trans, mut camera_rotation)) = camera.get() {
+                // smooth the unshaken position so the shake never feeds back into the follow
+                let base = cam_trans.0 - player.camera_shake_offset;
+                cam_trans.0 = base.lerp(cam_pos, follow) + shake.offset;
+                player.camera_shake_offset = shake.offset;
+                camera_rotation.0 = camera_rotation.0.slerp(look_rotation(cam_pos, cam_target), follow);
+            }
+        }
+    }
+}
+
+// the camera system only rebuilds the projection matrix on window resize,
+// so it has to be refreshed here whenever the fov moves
+fn update_camera_fov(
+    time: Res<Time>,
+    mut player_query: Query<&MMOPlayer>,
+    camera_query: Query<(&mut Camera, &mut PerspectiveProjection)>,
+) {
+    for player in &mut player_query.iter() {
+        let camera_entity = match player.camera_entity {
+            Some(camera_entity) => camera_entity,
+            None => continue,
+        };
+
+        if let Ok(mut projection) = camera_query.get_mut::<PerspectiveProjection>(camera_entity) {
+            let target = if player.sprinting { player.sprint_fov } else { player.base_fov };
+            let target = target + (player.ads_fov - target) * player.ads_blend;
+            let follow = 1. - (-player.camera_smoothing * time.delta_seconds).exp();
+            projection.fov += (target - projection.fov) * follow;
+
+            if let Ok(mut camera) = camera_query.get_mut::<Camera>(camera_entity) {
+                camera.projection_matrix = projection.get_projection_matrix();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1. / 60.;
+
+    fn mouse_motion(delta: Vec2) -> Events<MouseMotion> {
+        let mut events = Events::<MouseMotion>::default();
+        events.send(MouseMotion { delta });
+        events
+    }
+
+    #[test]
+    fn pitch_follows_invert_y() {
+        let events = mouse_motion(Vec2::new(0., 10.));
+        let look = total_mouse_motion(&mut events.get_reader(), &events);
+
+        for &(invert_y, rises) in [(false, false), (true, true)].iter() {
+            let sensitivity = CameraSensitivity { invert_y, ..Default::default() };
+            let mut player = MMOPlayer::default();
+            let pitch = player.camera_pitch;
+            apply_look(&mut player, look, &sensitivity);
+            assert_eq!(player.camera_pitch > pitch, rises, "invert_y {}", invert_y);
+            assert_eq!(player.camera_yaw, 0.);
+        }
+    }
+
+    #[test]
+    fn look_is_the_same_however_many_frames_the_motion_arrives_in() {
+        let deltas = [Vec2::new(12., -3.), Vec2::new(5., 8.), Vec2::new(-2., 1.)];
+        let sensitivity = CameraSensitivity::default();
+
+        let mut events = Events::<MouseMotion>::default();
+        for &delta in deltas.iter() { events.send(MouseMotion { delta }); }
+        let mut one_frame = MMOPlayer::default();
+        apply_look(&mut one_frame, total_mouse_motion(&mut events.get_reader(), &events), &sensitivity);
+
+        let mut many_frames = MMOPlayer::default();
+        for &delta in deltas.iter() {
+            let events = mouse_motion(delta);
+            apply_look(&mut many_frames, total_mouse_motion(&mut events.get_reader(), &events), &sensitivity);
+        }
+
+        assert!((one_frame.camera_yaw - 15. * sensitivity.yaw).abs() < 1e-6);
+        assert!((one_frame.camera_yaw - many_frames.camera_yaw).abs() < 1e-6);
+        assert!((one_frame.camera_pitch - many_frames.camera_pitch).abs() < 1e-6);
+    }
+
+    // a second holding the keys, long enough to reach full speed and cruise
+    fn walk(keys: &[KeyCode]) -> Vec3 {
+        let mut keyboard_input = Input::<KeyCode>::default();
+        for &key in keys.iter() { keyboard_input.press(key); }
+        let input = PlayerInput::from_keyboard(&keyboard_input, &KeyBindings::default(), &KeyboardCaptured(false));
+        let player = MMOPlayer::default();
+        let step = StepInput { movement: input.movement, move_speed: player.move_speed, ..Default::default() };
+
+        let mut state = MovementState::at_rest(Vec3::zero(), true);
+        for _ in 0..60 {
+            state = step_movement(state, &step, &player, &TerrainHeight::flat(), &WaterLevel(None), DT);
+        }
+        state.position
+    }
+
+    #[test]
+    fn diagonal_is_as_fast_as_straight() {
+        let straight = walk(&[KeyCode::W]).length();
+        let diagonal = walk(&[KeyCode::W, KeyCode::D]);
+        assert!(straight > 0.);
+        assert!((diagonal.length() - straight).abs() < 1e-3, "{} vs {}", diagonal.length(), straight);
+        assert!(diagonal.x().abs() > 0. && diagonal.z().abs() > 0.);
+    }
+
+    fn step_once(state: MovementState, jump: bool, player: &MMOPlayer) -> MovementState {
+        let input = StepInput { jump, ..Default::default() };
+        step_movement(state, &input, player, &TerrainHeight::flat(), &WaterLevel(None), DT)
+    }
+
+    // jump pressed the given number of steps after walking off a ledge, high enough up
+    // that the player is still falling
+    fn jumps_after_walking_off(steps: usize, player: &MMOPlayer) -> bool {
+        let mut state = MovementState::at_rest(Vec3::new(0., 100., 0.), false);
+        for _ in 0..steps { state = step_once(state, false, player); }
+        step_once(state, true, player).vertical_velocity > 0.
+    }
+
+    #[test]
+    fn coyote_time_ends_at_the_edge_of_its_window() {
+        let player = MMOPlayer::default();
+        let window = (player.coyote_time / DT).round() as usize;
+        assert!(jumps_after_walking_off(0, &player));
+        assert!(jumps_after_walking_off(window - 1, &player));
+        assert!(!jumps_after_walking_off(window + 1, &player));
+    }
+
+    // jump pressed in mid air the given number of steps before landing
+    fn jumps_on_landing(steps: usize, player: &MMOPlayer) -> bool {
+        let falling = MovementState { time_since_grounded: player.coyote_time, ..MovementState::at_rest(Vec3::new(0., 100., 0.), false) };
+        let mut state = step_once(falling, true, player);
+        assert!(state.vertical_velocity <= 0., "jumped in mid air");
+        for _ in 1..steps { state = step_once(state, false, player); }
+
+        let landed = MovementState { jump_buffer_timer: state.jump_buffer_timer, ..MovementState::at_rest(Vec3::zero(), true) };
+        step_once(landed, false, player).vertical_velocity > 0.
+    }
+
+    #[test]
+    fn jump_buffer_ends_at_the_edge_of_its_window() {
+        let player = MMOPlayer::default();
+        let window = (player.jump_buffer / DT).round() as usize;
+        assert!(jumps_on_landing(1, &player));
+        assert!(jumps_on_landing(window - 1, &player));
+        assert!(!jumps_on_landing(window + 1, &player));
+    }
+
+    #[test]
+    fn camera_never_goes_under_the_ground() {
+        for &ground in [-2., 0., 3.].iter() {
+            for degrees in 1..180 {
+                let player = MMOPlayer { camera_pitch: (degrees as f32).to_radians(), ..Default::default() };
+                let offset = keep_camera_above_ground(camera_orbit(&player) * player.camera_distance, ground);
+                assert!(offset.y() >= ground + CAMERA_GROUND_CLEARANCE - 1e-4, "pitch {} ground {}", degrees, ground);
+            }
+        }
+    }
+
+    // through the poles, where the view lines up with the up vector, in tenths of a degree
+    #[test]
+    fn look_rotation_has_no_nan_near_vertical() {
+        for tenths in 0..=1800 {
+            let pitch = (tenths as f32 / 10.).to_radians();
+            let eye = Vec3::new(0., pitch.cos(), -pitch.sin()) * 10.;
+            let rotation = look_rotation(eye, Vec3::zero());
+            let (x, y, z, w) = (rotation.x(), rotation.y(), rotation.z(), rotation.w());
+            assert!(!(x.is_nan() || y.is_nan() || z.is_nan() || w.is_nan()), "pitch {}", tenths as f32 / 10.);
+        }
+    }
+
+    #[test]
+    fn each_local_player_only_reads_their_own_keys() {
+        let bindings = KeyBindings::default();
+        let captured = KeyboardCaptured(false);
+        let mut keyboard_input = Input::<KeyCode>::default();
+        keyboard_input.press(bindings.forward);
+        assert_eq!(PlayerInput::from_keyboard(&keyboard_input, &bindings, &captured).movement, Vec2::new(0., 1.));
+        assert_eq!(PlayerInput::from_second_keyboard(&keyboard_input, &bindings.second_player, &captured).movement, Vec2::zero());
+
+        let mut keyboard_input = Input::<KeyCode>::default();
+        keyboard_input.press(bindings.second_player.forward);
+        keyboard_input.press(bindings.second_player.turn_left);
+        let second = PlayerInput::from_second_keyboard(&keyboard_input, &bindings.second_player, &captured);
+        assert_eq!(second.movement, Vec2::new(0., 1.));
+        assert_eq!(second.turn, -1.);
+        assert_eq!(PlayerInput::from_keyboard(&keyboard_input, &bindings, &captured).movement, Vec2::zero());
+    }
+
+    #[test]
+    fn default_bindings_only_share_a_key_on_purpose() {
+        let actions = KeyBindings::default().actions();
+        for (index, (name, key)) in actions.iter().enumerate() {
+            for (other, other_key) in &actions[index + 1..] {
+                // escape lets go of a lock before it pauses
+                if (*name, *other) == ("Clear lock", "Pause") { continue; }
+                assert!(key != other_key, "{} and {} are both {:?}", name, other, key);
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use crate::network::{Network, RemotePlayer};
+use crate::player::{step_movement, InputSource, MMOPlayer, MovementState, StepInput, Teleported};
+use crate::terrain::TerrainHeight;
+use crate::water::WaterLevel;
+use crate::timestep::{FixedPosition, FixedTimestep};
+
+pub type InputSequence = u32;
+
+// a few seconds of steps, older ones will never be acknowledged and are dropped
+const MAX_PENDING_INPUTS: usize = 256;
+// corrections smaller than this are eased out, anything bigger is snapped to
+const SNAP_DISTANCE: f32 = 3.0;
+pub const CORRECTION_SMOOTHING: f32 = 10.0;
+
+// the keyboard player's steps the server hasn't confirmed yet, oldest first
+#[derive(Default)]
+pub struct PredictionHistory {
+    next_sequence: InputSequence,
+    pending: VecDeque<(InputSequence, StepInput)>,
+    // the first step after the last teleport, anything the server says about earlier
+    // ones started from somewhere the player no longer is
+    teleported_at: Option<InputSequence>,
+}
+
+impl PredictionHistory {
+    pub fn record(&mut self, input: StepInput) -> InputSequence {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.pending.push_back((sequence, input));
+        if self.pending.len() > MAX_PENDING_INPUTS { self.pending.pop_front(); }
+        sequence
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = &(InputSequence, StepInput)> {
+        self.pending.iter()
+    }
+
+    // the steps so far led up to where the player was before, so they're dropped rather
+    // than sent or replayed. what's returned is the first step from the new position
+    pub fn teleport(&mut self) -> InputSequence {
+        self.pending.clear();
+        self.teleported_at = Some(self.next_sequence);
+        self.next_sequence
+    }
+
+    fn before_teleport(&self, sequence: InputSequence) -> bool {
+        self.teleported_at.is_some_and(|teleported_at| (sequence.wrapping_sub(teleported_at) as i32) < 0)
+    }
+
+    fn acknowledge(&mut self, sequence: InputSequence) {
+        while let Some(&(pending, _)) = self.pending.front() {
+            if pending.wrapping_sub(sequence) as i32 > 0 { break; }
+            self.pending.pop_front();
+        }
+    }
+}
+
+// the server's view of the keyboard player after it applied last_input
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct AuthoritativeState {
+    pub last_input: InputSequence,
+    pub state: MovementState,
+}
+
+pub struct PredictionPlugin;
+
+impl Plugin for PredictionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(send_teleports.system())
+            .add_system(send_pending_inputs.system())
+            .add_system(reconcile_local_player.system());
+    }
+}
+
+fn send_teleports(
+    mut teleported_reader: Local<EventReader<Teleported>>,
+    teleported_events: Res<Events<Teleported>>,
+    mut network: ResMut<Network>,
+    mut history: ResMut<PredictionHistory>,
+    player_query: Query<Without<RemotePlayer, &MMOPlayer>>,
+) {
+    for teleported in teleported_reader.iter(&teleported_events) {
+        match player_query.get::<MMOPlayer>(teleported.entity) {
+            Ok(player) if player.input_source == InputSource::KeyboardMouse => {}
+            _ => continue,
+        }
+        let sequence = history.teleport();
+        network.transport.send_teleport(sequence, teleported.state);
+    }
+}
+
+fn send_pending_inputs(
+    mut network: ResMut<Network>,
+    history: Res<PredictionHistory>,
+    mut last_sent: Local<Option<InputSequence>>,
+) {
+    for &(sequence, input) in history.pending() {
+        if let Some(last_sent) = *last_sent {
+            if sequence.wrapping_sub(last_sent) as i32 <= 0 { continue; }
+        }
+        network.transport.send_input(sequence, input);
+        *last_sent = Some(sequence);
+    }
+}
+
+// the server's state with the steps it hasn't seen yet replayed on top, so a correction
+// keeps everything pressed since. none when the state is from before the last teleport
+fn reconcile(
+    history: &mut PredictionHistory,
+    authoritative: AuthoritativeState,
+    player: &MMOPlayer,
+    terrain: &TerrainHeight,
+    water: &WaterLevel,
+    step: f32,
+) -> Option<MovementState> {
+    if history.before_teleport(authoritative.last_input) { return None; }
+    history.acknowledge(authoritative.last_input);
+
+    let mut state = authoritative.state;
+    for (_, input) in history.pending() {
+        state = step_movement(state, input, player, terrain, water, step);
+    }
+    Some(state)
+}
+
+// small errors are eased out visually, large ones are snapped so the player isn't
+// dragged across the map
+fn reconcile_local_player(
+    mut network: ResMut<Network>,
+    fixed: Res<FixedTimestep>,
+    terrain: Res<TerrainHeight>,
+    water: Res<WaterLevel>,
+    mut history: ResMut<PredictionHistory>,
+    mut query: Query<Without<RemotePlayer, (&mut MMOPlayer, &mut FixedPosition)>>,
+) {
+    let authoritative = match network.transport.receive_authoritative() {
+        Some(authoritative) => authoritative,
+        None => return,
+    };
+
+    for (mut player, mut position) in &mut query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let state = match reconcile(&mut history, authoritative, &player, &terrain, &water, fixed.step) {
+            Some(state) => state,
+            None => return,
+        };
+
+        if (position.current - state.position).length() > SNAP_DISTANCE {
+            position.teleport(state.position);
+        } else {
+            position.correct(state.position);
+        }
+        player.set_movement_state(&state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1. / 60.;
+
+    #[test]
+    fn a_teleport_survives_reconciling() {
+        let player = MMOPlayer::default();
+        let (terrain, water) = (TerrainHeight::flat(), WaterLevel(None));
+        let forward = StepInput { movement: Vec2::new(0., 1.), move_speed: player.move_speed, ..Default::default() };
+        let step = |state| step_movement(state, &forward, &player, &terrain, &water, DT);
+
+        // two steps walking, the server only hears about the first before the teleport
+        let mut history = PredictionHistory::default();
+        let start = MovementState::at_rest(Vec3::zero(), true);
+        let first = history.record(forward);
+        history.record(forward);
+
+        let teleported = MovementState::at_rest(Vec3::new(500., 0., 500.), true);
+        let sequence = history.teleport();
+        assert_eq!(history.pending().count(), 0);
+        let mut client = teleported;
+        for _ in 0..3 {
+            history.record(forward);
+            client = step(client);
+        }
+
+        // correcting to where the old steps left the player would undo the teleport
+        let stale = AuthoritativeState { last_input: first, state: step(start) };
+        assert!(reconcile(&mut history, stale, &player, &terrain, &water, DT).is_none());
+
+        // the server has stepped once from the teleport, the other two are replayed on top
+        let fresh = AuthoritativeState { last_input: sequence, state: step(teleported) };
+        let state = reconcile(&mut history, fresh, &player, &terrain, &water, DT).unwrap();
+        assert!((state.position - client.position).length() < 1e-4, "{:?} vs {:?}", state.position, client.position);
+        assert_eq!(history.pending().count(), 2);
+    }
+}
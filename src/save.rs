@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fs, io};
+use crate::experience::{apply_level_speed, Experience, Leveling};
+use crate::game_state::GameState;
+use crate::health::Health;
+use crate::inventory::{Inventory, ItemStack};
+use crate::network::RemotePlayer;
+use crate::player::{InputSource, KeyBindings, KeyboardCaptured, MMOPlayer, MovementState, Teleported};
+use crate::speed_modifiers::SpeedModifiers;
+use crate::timestep::FixedPosition;
+use crate::zones::ZoneManager;
+
+pub const SAVE_PATH: &str = "save.toml";
+
+// everything about the local player that outlives a session
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    // the position alone doesn't say which zone's terrain it's on when it's between zones
+    #[serde(default)]
+    pub zone: Option<String>,
+    pub position: Vec3,
+    pub yaw: f32,
+    pub camera_yaw: f32,
+    pub camera_pitch: f32,
+    pub camera_distance: f32,
+    pub health: f32,
+    pub max_health: f32,
+    pub level: u32,
+    pub experience: u32,
+    pub inventory: Vec<ItemStack>,
+}
+
+pub fn save_game(path: &str, save: &SaveGame) -> Result<(), String> {
+    let contents = toml::to_string(save).map_err(|err| err.to_string())?;
+    fs::write(path, contents).map_err(|err| err.to_string())
+}
+
+pub fn load_game(path: &str) -> Result<SaveGame, String> {
+    let contents = fs::read_to_string(path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => "there is no save".to_string(),
+        _ => err.to_string(),
+    })?;
+    toml::from_str(&contents).map_err(|err| format!("the save is corrupt, {}", err))
+}
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(save_game_on_key.system())
+            .add_system(load_game_on_key.system());
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn save_game_on_key(
+    game_state: Res<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    captured: Res<KeyboardCaptured>,
+    zones: Res<ZoneManager>,
+    mut player_query: Query<Without<RemotePlayer, (&MMOPlayer, &FixedPosition, &Health, &Experience, &Inventory)>>,
+) {
+    if *game_state != GameState::Playing || captured.0 || !keyboard_input.just_pressed(bindings.save_game) { return; }
+
+    for (player, position, health, experience, inventory) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let save = SaveGame {
+            zone: zones.current_zone().map(|zone| zone.name.clone()),
+            position: position.current,
+            yaw: player.yaw,
+            camera_yaw: player.camera_yaw,
+            camera_pitch: player.camera_pitch,
+            camera_distance: player.target_camera_distance,
+            health: health.current,
+            max_health: health.max,
+            level: experience.level,
+            experience: experience.current,
+            inventory: inventory.stacks.clone(),
+        };
+        match save_game(SAVE_PATH, &save) {
+            Ok(()) => println!("saved the game to '{}'", SAVE_PATH),
+            Err(err) => eprintln!("failed to save the game to '{}': {}", SAVE_PATH, err),
+        }
+        break;
+    }
+}
+
+// a save that can't be read leaves the game as it is, so a fresh game just carries on
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn load_game_on_key(
+    game_state: Res<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    captured: Res<KeyboardCaptured>,
+    leveling: Res<Leveling>,
+    mut zones: ResMut<ZoneManager>,
+    mut teleported_events: ResMut<Events<Teleported>>,
+    mut player_query: Query<
+        Without<RemotePlayer, (
+            Entity,
+            &mut MMOPlayer,
+            &mut FixedPosition,
+            &mut Translation,
+            &mut Rotation,
+            &mut Health,
+            &mut Experience,
+            &mut Inventory,
+            &mut SpeedModifiers,
+        )>,
+    >,
+) {
+    if *game_state != GameState::Playing || captured.0 || !keyboard_input.just_pressed(bindings.load_game) { return; }
+
+    let save = match load_game(SAVE_PATH) {
+        Ok(save) => save,
+        Err(err) => {
+            eprintln!("failed to load the game from '{}': {}, carrying on without it", SAVE_PATH, err);
+            return;
+        }
+    };
+
+    for (entity, mut player, mut position, mut translation, mut rotation, mut health, mut experience, mut inventory, mut modifiers) in
+        &mut player_query.iter()
+    {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let restored = save.position;
+        // the zone loads its terrain around the player, rather than sending them to its entry
+        match save.zone.as_ref().map(|name| (name, zones.zone_named(name))) {
+            Some((_, Some(zone))) => zones.arrive_in(zone),
+            Some((name, None)) => eprintln!("the save is in zone '{}', which no longer exists", name),
+            None => {}
+        }
+        // teleported so the fixed step doesn't draw the player sliding over from where they were
+        let state = MovementState::at_rest(restored, false);
+        position.teleport(restored);
+        translation.0 = restored;
+        player.set_movement_state(&state);
+        player.move_target = None;
+        player.waypoints.clear();
+        teleported_events.send(Teleported { entity, state });
+
+        // the camera hangs off the player and is placed from these, so it comes back
+        // facing the way it was saved. the distance is set outright rather than zoomed to
+        player.yaw = save.yaw;
+        rotation.0 = Quat::from_rotation_y(-save.yaw);
+        player.camera_yaw = save.camera_yaw;
+        player.camera_pitch = save.camera_pitch;
+        player.camera_distance = save.camera_distance;
+        player.target_camera_distance = save.camera_distance;
+
+        health.max = save.max_health;
+        health.current = save.health.min(save.max_health);
+        health.since_damage = 0.;
+        experience.level = save.level.max(1);
+        experience.current = save.experience;
+        apply_level_speed(&leveling, experience.level, &mut modifiers);
+        inventory.stacks = save.inventory.clone();
+        println!("loaded the game from '{}'", SAVE_PATH);
+        break;
+    }
+}
@@ -0,0 +1,274 @@
+use bevy::{
+    input::mouse::MouseMotion,
+    prelude::*,
+    render::camera::Camera,
+};
+use crate::cursor::{CursorPosition, Ray};
+use crate::formation::{self, Formation};
+use crate::game_state::GameState;
+use crate::gizmos::line_mesh;
+use crate::nameplate::local_camera_view;
+use crate::network::RemotePlayer;
+use crate::player::{KeyBindings, MMOPlayer};
+use crate::terrain::TerrainHeight;
+
+// a press that moves less than this is a click, anything further is a box drag
+pub const DRAG_THRESHOLD: f32 = 6.;
+// box corners above the horizon never reach the ground, so they stop this far out instead
+const MAX_SELECT_DISTANCE: f32 = 200.;
+const RING_RADIUS: f32 = 1.2;
+const RING_SEGMENTS: usize = 24;
+const RING_HEIGHT: f32 = 0.05;
+
+// units the group orders go to. the ring is a separate entity that follows the unit around
+pub struct Selected {
+    ring: Entity,
+}
+
+struct SelectionRing {
+    unit: Entity,
+}
+
+struct DragBox;
+
+// where the left button went down, kept after release so other systems can tell a drag from a click
+#[derive(Default)]
+pub struct BoxSelect {
+    pub start: Option<Vec2>,
+}
+
+impl BoxSelect {
+    // the dragged rectangle, min and max corners in window pixels. none for a plain click
+    pub fn drag_rect(&self, cursor: &CursorPosition) -> Option<(Vec2, Vec2)> {
+        let (start, cursor) = (self.start?, cursor.0?);
+        if (cursor - start).length() < DRAG_THRESHOLD { return None; }
+        Some((start.min(cursor), start.max(cursor)))
+    }
+}
+
+// how far the mouse has moved since the right button went down. the cursor is held still
+// while the same button turns the camera, so the motion is what tells a click from a turn
+#[derive(Default)]
+struct OrderClick {
+    mouse_motion_event_reader: EventReader<MouseMotion>,
+    dragged: f32,
+}
+
+struct SelectionAppearance {
+    ring_mesh: Handle<Mesh>,
+    ring_material: Handle<StandardMaterial>,
+}
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<BoxSelect>()
+            .init_resource::<Formation>()
+            .add_startup_system(setup_selection.system())
+            .add_system(box_select.system())
+            .add_system(order_selected.system())
+            .add_system(update_selection_rings.system())
+            .add_system(update_drag_box.system());
+    }
+}
+
+fn setup_selection(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut color_materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let ring: Vec<Vec3> = (0..RING_SEGMENTS)
+        .flat_map(|segment| {
+            let angle = |i: usize| i as f32 / RING_SEGMENTS as f32 * std::f32::consts::PI * 2.;
+            let point = |i: usize| Vec3::new(angle(i).cos(), 0., angle(i).sin()) * RING_RADIUS;
+            vec![point(segment), point(segment + 1)]
+        })
+        .collect();
+    commands.insert_resource(SelectionAppearance {
+        ring_mesh: meshes.add(line_mesh(&ring)),
+        ring_material: materials.add(StandardMaterial {
+            albedo: Color::rgb(0.3, 1.0, 0.4),
+            shaded: false,
+            ..Default::default()
+        }),
+    });
+
+    let mut drag_box = NodeComponents {
+        style: Style {
+            position_type: PositionType::Absolute,
+            ..Default::default()
+        },
+        material: color_materials.add(Color::rgba(0.3, 1.0, 0.4, 0.2).into()),
+        ..Default::default()
+    };
+    drag_box.draw.is_visible = false;
+    commands.spawn(drag_box).with(DragBox);
+}
+
+// the ground point under a window position, seen through the local camera
+fn ground_point(screen: Vec2, window_size: Vec2, view: &(Mat4, Mat4), terrain: &TerrainHeight) -> Vec3 {
+    let ray = Ray::from_cursor(screen, window_size, &view.0, &view.1);
+    ray.intersect_terrain(terrain).unwrap_or_else(|| {
+        let far = ray.origin + ray.direction * MAX_SELECT_DISTANCE;
+        Vec3::new(far.x(), terrain.height_at(far.x(), far.z()), far.z())
+    })
+}
+
+// the box projects onto the ground as a convex quad, a point is inside when it is on
+// the same side of all four edges
+fn inside_quad(quad: &[Vec3; 4], point: Vec3) -> bool {
+    let side = |a: Vec3, b: Vec3| (b.x() - a.x()) * (point.z() - a.z()) - (b.z() - a.z()) * (point.x() - a.x());
+    let sides: Vec<f32> = (0..4).map(|i| side(quad[i], quad[(i + 1) % 4])).collect();
+    sides.iter().all(|&s| s >= 0.) || sides.iter().all(|&s| s <= 0.)
+}
+
+fn window_size(windows: &Windows) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    Some(Vec2::new(window.width as f32, window.height as f32))
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn box_select(
+    mut commands: Commands,
+    mouse_button_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    game_state: Res<GameState>,
+    cursor: Res<CursorPosition>,
+    windows: Res<Windows>,
+    terrain: Res<TerrainHeight>,
+    appearance: Res<SelectionAppearance>,
+    mut box_select: ResMut<BoxSelect>,
+    mut player_query: Query<&MMOPlayer>,
+    camera_query: Query<(&Transform, &Camera)>,
+    mut unit_query: Query<Without<RemotePlayer, (Entity, &MMOPlayer, &Translation, Option<&Selected>)>>,
+) {
+    if *game_state != GameState::Playing { return; }
+    if mouse_button_input.just_pressed(MouseButton::Left) { box_select.start = cursor.0; }
+    if !mouse_button_input.just_released(MouseButton::Left) { return; }
+
+    // the queue modifier adds to the selection, otherwise any click or drag starts it over
+    let additive = keyboard_input.pressed(bindings.queue_modifier);
+    let quad = match (box_select.drag_rect(&cursor), window_size(&windows), local_camera_view(&mut player_query, &camera_query)) {
+        (Some((min, max)), Some(window_size), Some(view)) => Some([
+            ground_point(min, window_size, &view, &terrain),
+            ground_point(Vec2::new(max.x(), min.y()), window_size, &view, &terrain),
+            ground_point(max, window_size, &view, &terrain),
+            ground_point(Vec2::new(min.x(), max.y()), window_size, &view, &terrain),
+        ]),
+        _ => None,
+    };
+
+    for (entity, _, translation, selected) in &mut unit_query.iter() {
+        let in_box = quad.is_some_and(|quad| inside_quad(&quad, translation.0));
+        match selected {
+            Some(selected) if !in_box && !additive => {
+                commands.despawn(selected.ring);
+                commands.remove_one::<Selected>(entity);
+            }
+            None if in_box => {
+                let ring = commands
+                    .spawn(PbrComponents {
+                        mesh: appearance.ring_mesh,
+                        material: appearance.ring_material,
+                        ..Default::default()
+                    })
+                    .with(SelectionRing { unit: entity })
+                    .current_entity()
+                    .unwrap();
+                commands.insert_one(entity, Selected { ring });
+            }
+            _ => {}
+        }
+    }
+}
+
+// right click sends the selected units to the ground point under the cursor, laid out in
+// formation facing the way the group has to travel to get there. dragging with it held
+// turns the camera instead and sends nobody anywhere
+#[allow(clippy::too_many_arguments)]
+fn order_selected(
+    mut click: Local<OrderClick>,
+    mouse_button_input: Res<Input<MouseButton>>,
+    mouse_motion_events: Res<Events<MouseMotion>>,
+    game_state: Res<GameState>,
+    formation: Res<Formation>,
+    cursor: Res<CursorPosition>,
+    windows: Res<Windows>,
+    terrain: Res<TerrainHeight>,
+    mut player_query: Query<&MMOPlayer>,
+    camera_query: Query<(&Transform, &Camera)>,
+    mut selected_query: Query<With<Selected, (Entity, &MMOPlayer, &Translation)>>,
+    unit_query: Query<&mut MMOPlayer>,
+) {
+    let motion: f32 = click.mouse_motion_event_reader.iter(&mouse_motion_events).map(|event| event.delta.length()).sum();
+    click.dragged += motion;
+    if mouse_button_input.just_pressed(MouseButton::Right) { click.dragged = 0.; }
+
+    if *game_state != GameState::Playing || !mouse_button_input.just_released(MouseButton::Right) { return; }
+    if click.dragged >= DRAG_THRESHOLD { return; }
+    let (cursor, window_size) = match (cursor.0, window_size(&windows)) {
+        (Some(cursor), Some(window_size)) => (cursor, window_size),
+        _ => return,
+    };
+    let view = match local_camera_view(&mut player_query, &camera_query) {
+        Some(view) => view,
+        None => return,
+    };
+
+    let ray = Ray::from_cursor(cursor, window_size, &view.0, &view.1);
+    let target = match ray.intersect_terrain(&terrain) {
+        Some(target) => target,
+        None => return,
+    };
+    let (units, positions): (Vec<Entity>, Vec<Vec3>) = selected_query.iter().iter()
+        .map(|(entity, _, translation)| (entity, translation.0))
+        .unzip();
+    if units.is_empty() { return; }
+
+    let centre = positions.iter().fold(Vec3::zero(), |sum, &position| sum + position) / units.len() as f32;
+    let slots = formation.slots(target, target - centre, units.len());
+    for (&unit, slot) in units.iter().zip(formation::assign_slots(&positions, slots)) {
+        if let Ok(mut player) = unit_query.get_mut::<MMOPlayer>(unit) {
+            player.waypoints.clear();
+            player.move_target = Some(slot);
+        }
+    }
+}
+
+fn update_selection_rings(
+    mut commands: Commands,
+    mut ring_query: Query<(Entity, &SelectionRing, &mut Translation)>,
+    unit_query: Query<(&Selected, &Translation)>,
+) {
+    for (ring_entity, ring, mut translation) in &mut ring_query.iter() {
+        match unit_query.get::<Translation>(ring.unit) {
+            // ring the unit's feet, just above the ground so it isn't hidden in it
+            Ok(unit) => translation.0 = unit.0 + Vec3::new(0., RING_HEIGHT, 0.),
+            // the unit was despawned while selected
+            Err(_) => { commands.despawn(ring_entity); }
+        }
+    }
+}
+
+fn update_drag_box(
+    mouse_button_input: Res<Input<MouseButton>>,
+    cursor: Res<CursorPosition>,
+    windows: Res<Windows>,
+    box_select: Res<BoxSelect>,
+    mut query: Query<With<DragBox, (&mut Style, &mut Draw)>>,
+) {
+    let rect = box_select.drag_rect(&cursor).filter(|_| mouse_button_input.pressed(MouseButton::Left));
+    let window_height = windows.get_primary().map_or(0., |window| window.height as f32);
+    for (mut style, mut draw) in &mut query.iter() {
+        draw.is_visible = rect.is_some();
+        if let Some((min, max)) = rect {
+            // the cursor counts from the bottom left, ui positions from the top left
+            style.position.left = Val::Px(min.x());
+            style.position.top = Val::Px(window_height - max.y());
+            style.size = Size::new(Val::Px(max.x() - min.x()), Val::Px(max.y() - min.y()));
+        }
+    }
+}
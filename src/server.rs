@@ -0,0 +1,356 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use crate::delta::{SnapshotDecoder, SnapshotEncoder};
+use crate::nameplate::PlayerName;
+use crate::network::{ConnectionSettings, NetworkId, PlayerDisconnected, PlayerId, PlayerSnapshot, RemotePlayer};
+use crate::player::{step_movement, InputSource, MMOPlayer};
+use crate::prediction::{AuthoritativeState, InputSequence};
+use crate::terrain::{self, TerrainHeight};
+use crate::timestep::FIXED_TIMESTEP;
+use crate::transport::{Connection, Message, PROTOCOL_VERSION};
+use crate::water::WaterLevel;
+use crate::zones::{self, ZoneManager, ZoneTerrains, ZONES_PATH};
+
+// followed by the address to accept clients on
+const LISTEN_FLAG: &str = "--listen";
+const DEFAULT_LISTEN_ADDRESS: &str = "0.0.0.0:7777";
+// the same for browser clients, which connect over websockets
+const LISTEN_WEBSOCKET_FLAG: &str = "--listen-websocket";
+const DEFAULT_WEBSOCKET_ADDRESS: &str = "0.0.0.0:7778";
+const MAX_PLAYERS: usize = 64;
+// a connection that hasn't said hello by then is dropped
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+enum ClientState {
+    AwaitingHello { connected: Instant },
+    Joined {
+        id: NetworkId,
+        name: String,
+        entity: Entity,
+        decoder: SnapshotDecoder,
+        // one per other player, each delta compressed against what this client last acked of them
+        encoders: HashMap<NetworkId, SnapshotEncoder>,
+        // the first snapshot puts the player wherever the client spawned them, after that
+        // only their inputs and teleports move them
+        placed: bool,
+        // inputs from before the last teleport were from where the player used to be
+        teleported_at: Option<InputSequence>,
+        // the zone whose terrain they're stepped over, which like on the client only changes
+        // when they're put somewhere, never as they walk over the border
+        zone: Option<usize>,
+    },
+}
+
+struct Client {
+    connection: Connection,
+    state: ClientState,
+    last_heard: Instant,
+    // set once the client is to be dropped, after anything queued for it has been flushed
+    closing: Option<String>,
+}
+
+impl Client {
+    fn id(&self) -> Option<NetworkId> {
+        match self.state {
+            ClientState::Joined { id, .. } => Some(id),
+            ClientState::AwaitingHello { .. } => None,
+        }
+    }
+
+    fn reject(&mut self, reason: String) {
+        self.connection.queue(&Message::Rejected { reason: reason.clone() });
+        self.closing = Some(format!("rejected, {}", reason));
+    }
+}
+
+// every connected player is a RemotePlayer here too, moved by stepping the inputs its client
+// sends and told where that left them, so the client can correct its prediction
+pub struct Server {
+    // none if the address couldn't be bound, the server then runs with nobody able to join
+    // that way. both carry the same messages, only how they're framed differs
+    listener: Option<TcpListener>,
+    websocket_listener: Option<TcpListener>,
+    clients: Vec<Client>,
+    // ids of players who have left, handed out again before any new ones
+    free_ids: Vec<NetworkId>,
+    next_id: NetworkId,
+    last_heartbeat: Instant,
+}
+
+pub struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let args: Vec<String> = std::env::args().collect();
+        let address = |flag: &str, default: &'static str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+                .map_or(default.to_string(), |address| address.clone())
+        };
+        let listener = listen(&address(LISTEN_FLAG, DEFAULT_LISTEN_ADDRESS), "players");
+        let websocket_listener = listen(&address(LISTEN_WEBSOCKET_FLAG, DEFAULT_WEBSOCKET_ADDRESS), "browser players");
+
+        app.add_resource(Server {
+                listener,
+                websocket_listener,
+                clients: Vec::new(),
+                free_ids: Vec::new(),
+                next_id: 1,
+                last_heartbeat: Instant::now(),
+            })
+            .init_resource::<ConnectionSettings>()
+            .add_event::<PlayerDisconnected>()
+            .add_resource(ZoneManager::load(ZONES_PATH))
+            .init_resource::<ZoneTerrains>()
+            .add_startup_system(terrain::load_terrain_heights.system())
+            .add_startup_system(zones::load_zone_terrains.system())
+            .add_system(accept_connections.system())
+            .add_system(receive_client_messages.system())
+            .add_system(send_snapshots.system());
+    }
+}
+
+fn listen(address: &str, who: &str) -> Option<TcpListener> {
+    match TcpListener::bind(address).and_then(|listener| listener.set_nonblocking(true).map(|_| listener)) {
+        Ok(listener) => {
+            println!("listening for {} on {}", who, address);
+            Some(listener)
+        }
+        Err(err) => {
+            eprintln!("couldn't listen on {}, no {} will be able to join: {}", address, who, err);
+            None
+        }
+    }
+}
+
+fn accept(listener: Option<&TcpListener>) -> Vec<TcpStream> {
+    let mut accepted = Vec::new();
+    if let Some(listener) = listener {
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => accepted.push(stream),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    eprintln!("couldn't accept a connection: {}", err);
+                    break;
+                }
+            }
+        }
+    }
+    accepted
+}
+
+fn accept_connections(mut server: ResMut<Server>) {
+    let connections: Vec<Result<Connection, String>> = accept(server.listener.as_ref())
+        .into_iter()
+        .map(Connection::new)
+        .chain(accept(server.websocket_listener.as_ref()).into_iter().map(Connection::websocket))
+        .collect();
+    for connection in connections {
+        match connection {
+            Ok(connection) => server.clients.push(Client {
+                connection,
+                state: ClientState::AwaitingHello { connected: Instant::now() },
+                last_heard: Instant::now(),
+                closing: None,
+            }),
+            Err(err) => eprintln!("couldn't set up a connection: {}", err),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn receive_client_messages(
+    mut commands: Commands,
+    settings: Res<ConnectionSettings>,
+    mut server: ResMut<Server>,
+    terrain: Res<TerrainHeight>,
+    water: Res<WaterLevel>,
+    zones: Res<ZoneManager>,
+    zone_terrains: Res<ZoneTerrains>,
+    mut disconnected_events: ResMut<Events<PlayerDisconnected>>,
+    player_query: Query<With<RemotePlayer, (&mut MMOPlayer, &mut Translation, &mut Rotation)>>,
+) {
+    let server = &mut *server;
+    let mut joined = server.clients.iter().filter(|client| client.id().is_some()).count();
+    let mut names: Vec<String> = server
+        .clients
+        .iter()
+        .filter_map(|client| match &client.state {
+            ClientState::Joined { name, .. } => Some(name.clone()),
+            ClientState::AwaitingHello { .. } => None,
+        })
+        .collect();
+
+    for client in server.clients.iter_mut() {
+        let messages = client.connection.receive();
+        if !messages.is_empty() { client.last_heard = Instant::now(); }
+        // only where the newest input left the player is worth sending back
+        let mut authoritative = None;
+        for message in messages {
+            if client.closing.is_some() { break; }
+            match (&mut client.state, message) {
+                (ClientState::AwaitingHello { .. }, Message::Hello { protocol, name }) => {
+                    let name = name.trim().to_string();
+                    if protocol != PROTOCOL_VERSION {
+                        client.reject(format!("the server speaks protocol {}, not {}", PROTOCOL_VERSION, protocol));
+                    } else if joined >= MAX_PLAYERS {
+                        client.reject("the server is full".to_string());
+                    } else if !name.is_empty() && names.contains(&name) {
+                        client.reject(format!("someone called {} is already connected", name));
+                    } else {
+                        let id = match server.free_ids.pop() {
+                            Some(id) => id,
+                            None => {
+                                server.next_id += 1;
+                                server.next_id - 1
+                            }
+                        };
+                        let name = if name.is_empty() { format!("Player #{}", id) } else { name };
+                        let entity = commands
+                            .spawn((
+                                MMOPlayer { input_source: InputSource::None, ..Default::default() },
+                                RemotePlayer { id },
+                                PlayerId(id),
+                                PlayerName(name.clone()),
+                                Translation::default(),
+                                Rotation::default(),
+                            ))
+                            .current_entity()
+                            .unwrap();
+                        println!("{} joined as player #{}", name, id);
+                        client.connection.queue(&Message::Welcome { id });
+                        names.push(name.clone());
+                        joined += 1;
+                        client.state = ClientState::Joined { id, name, entity, decoder: SnapshotDecoder::default(), encoders: HashMap::new(), placed: false, teleported_at: None, zone: None };
+                    }
+                }
+                (ClientState::AwaitingHello { .. }, _) => client.reject("expected a hello first".to_string()),
+                // a second hello on the same connection changes nothing
+                (ClientState::Joined { id, .. }, Message::Hello { .. }) => eprintln!("player #{} said hello twice", id),
+                (ClientState::Joined { id, entity, decoder, placed, zone, .. }, Message::Snapshot { bytes, .. }) => match decoder.decode(&bytes) {
+                    Ok((sequence, snapshot)) => {
+                        client.connection.queue(&Message::Ack { id: *id, sequence });
+                        if *placed { continue; }
+                        if let Ok(mut position) = player_query.get_mut::<Translation>(*entity) {
+                            position.0 = snapshot.pos;
+                        }
+                        if let Ok(mut rotation) = player_query.get_mut::<Rotation>(*entity) {
+                            rotation.0 = Quat::from_rotation_y(-snapshot.yaw);
+                        }
+                        if let Ok(mut player) = player_query.get_mut::<MMOPlayer>(*entity) {
+                            player.yaw = snapshot.yaw;
+                        }
+                        *zone = zones.zone_at(snapshot.pos);
+                        *placed = true;
+                    }
+                    Err(err) => eprintln!("dropping snapshot from player #{}: {}", id, err),
+                },
+                (ClientState::Joined { encoders, .. }, Message::Ack { id, sequence }) => {
+                    if let Some(encoder) = encoders.get_mut(&id) { encoder.acknowledge(sequence); }
+                }
+                // inputs from before the player was placed would move them from the wrong spot,
+                // the client replays them on top of the first correction anyway
+                (ClientState::Joined { entity, placed: true, teleported_at, zone, .. }, Message::Input { sequence, input }) => {
+                    if teleported_at.is_some_and(|teleported_at| (sequence.wrapping_sub(teleported_at) as i32) < 0) { continue; }
+                    let (mut player, mut translation) = match (player_query.get_mut::<MMOPlayer>(*entity), player_query.get_mut::<Translation>(*entity)) {
+                        (Ok(player), Ok(translation)) => (player, translation),
+                        _ => continue,
+                    };
+                    let (terrain, water) = zone_terrains.get(*zone).unwrap_or((&terrain, &water));
+                    let state = step_movement(player.movement_state(translation.0), &input, &player, terrain, water, FIXED_TIMESTEP);
+                    player.set_movement_state(&state);
+                    player.yaw = input.yaw;
+                    translation.0 = state.position;
+                    if let Ok(mut rotation) = player_query.get_mut::<Rotation>(*entity) {
+                        rotation.0 = Quat::from_rotation_y(-input.yaw);
+                    }
+                    authoritative = Some(AuthoritativeState { last_input: sequence, state });
+                }
+                (ClientState::Joined { entity, placed, teleported_at, zone, .. }, Message::Teleport { sequence, state }) => {
+                    if let Ok(mut player) = player_query.get_mut::<MMOPlayer>(*entity) { player.set_movement_state(&state); }
+                    if let Ok(mut translation) = player_query.get_mut::<Translation>(*entity) { translation.0 = state.position; }
+                    if let Some(landed_in) = zones.zone_at(state.position) { *zone = Some(landed_in); }
+                    *placed = true;
+                    *teleported_at = Some(sequence);
+                    // where the steps before it left the player is no use to the client now
+                    authoritative = None;
+                }
+                (ClientState::Joined { .. }, Message::Input { .. }) | (ClientState::Joined { .. }, Message::Heartbeat) => {}
+                (ClientState::Joined { .. }, _) => client.closing = Some("sent a server message".to_string()),
+            }
+        }
+
+        if let Some(authoritative) = authoritative { client.connection.queue(&Message::Authoritative(authoritative)); }
+
+        if let ClientState::AwaitingHello { connected } = client.state {
+            if client.closing.is_none() && connected.elapsed() > HELLO_TIMEOUT { client.closing = Some("never said hello".to_string()); }
+        }
+        let silence = client.last_heard.elapsed().as_secs_f32();
+        if client.closing.is_none() && silence > settings.timeout {
+            client.closing = Some(format!("nothing heard for {:.0} seconds", silence));
+        }
+        client.connection.flush();
+        if client.closing.is_none() { client.closing = client.connection.error.clone(); }
+    }
+
+    // everyone else is told, so they can take the player out of their world
+    let mut departed = Vec::new();
+    server.clients.retain(|client| {
+        let reason = match &client.closing {
+            Some(reason) => reason,
+            None => return true,
+        };
+        if let ClientState::Joined { id, name, entity, .. } = &client.state {
+            println!("{} (player #{}) left: {}", name, id, reason);
+            commands.despawn(*entity);
+            departed.push(*id);
+        }
+        false
+    });
+    for &id in departed.iter() {
+        server.free_ids.push(id);
+        disconnected_events.send(PlayerDisconnected { id });
+    }
+    for client in server.clients.iter_mut() {
+        for &id in departed.iter() {
+            if let ClientState::Joined { encoders, .. } = &mut client.state { encoders.remove(&id); }
+            client.connection.queue(&Message::Left { id });
+        }
+    }
+}
+
+// every joined client gets every other player each frame, and a heartbeat every so often
+// in case there are no other players
+fn send_snapshots(
+    settings: Res<ConnectionSettings>,
+    mut server: ResMut<Server>,
+    mut player_query: Query<(&PlayerId, &MMOPlayer, &Translation)>,
+) {
+    // a player who left this frame isn't despawned until the end of it, and mustn't
+    // come back to life on the clients that were just told they left
+    let joined: Vec<NetworkId> = server.clients.iter().filter_map(Client::id).collect();
+    let mut snapshots = Vec::new();
+    for (id, player, translation) in &mut player_query.iter() {
+        if !joined.contains(&id.0) { continue; }
+        snapshots.push((id.0, PlayerSnapshot { pos: translation.0, yaw: player.yaw }));
+    }
+
+    let heartbeat = server.last_heartbeat.elapsed().as_secs_f32() >= settings.heartbeat_interval;
+    if heartbeat { server.last_heartbeat = Instant::now(); }
+    for client in server.clients.iter_mut() {
+        if heartbeat && client.id().is_some() { client.connection.queue(&Message::Heartbeat); }
+        if let ClientState::Joined { id: own_id, encoders, .. } = &mut client.state {
+            for &(id, snapshot) in snapshots.iter() {
+                if id == *own_id { continue; }
+                let bytes = encoders.entry(id).or_default().encode(snapshot);
+                client.connection.queue(&Message::Snapshot { id, bytes });
+            }
+        }
+        client.connection.flush();
+    }
+}
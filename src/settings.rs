@@ -0,0 +1,96 @@
+use bevy::{
+    prelude::*,
+    app::AppExit,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, io};
+use crate::display::WindowSettings;
+use crate::minimap::MinimapSettings;
+use crate::network::RemotePlayer;
+use crate::player::{CameraSensitivity, InputSettings, InputSource, KeyBindings, MMOPlayer, ZoomSettings};
+
+pub const SETTINGS_PATH: &str = "settings.toml";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub sensitivity: CameraSensitivity,
+    pub zoom: ZoomSettings,
+    pub input: InputSettings,
+    pub bindings: KeyBindings,
+    pub window: WindowSettings,
+    pub minimap: MinimapSettings,
+}
+
+impl Settings {
+    // a missing file is expected on first run, anything else is worth reporting,
+    // but never fatal since the defaults are always usable
+    pub fn load(path: &str) -> Settings {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    eprintln!("failed to read settings '{}': {}, using defaults", path, err);
+                }
+                return Settings::default();
+            }
+        };
+
+        toml::from_str(&contents).unwrap_or_else(|err| {
+            eprintln!("failed to parse settings '{}': {}, using defaults", path, err);
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self, path: &str) {
+        let result = toml::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| fs::write(path, contents).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            eprintln!("failed to save settings '{}': {}", path, err);
+        }
+    }
+}
+
+// for saving from somewhere other than the hotkey, like leaving the controls menu
+pub struct SaveSettings;
+
+#[derive(Default)]
+pub struct SaveSettingsState {
+    app_exit_event_reader: EventReader<AppExit>,
+    save_settings_event_reader: EventReader<SaveSettings>,
+}
+
+// runs last so an exit requested earlier in the frame is seen before the app shuts down
+#[allow(clippy::too_many_arguments)]
+pub fn save_settings(
+    mut state: Local<SaveSettingsState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    app_exit_events: Res<Events<AppExit>>,
+    save_settings_events: Res<Events<SaveSettings>>,
+    sensitivity: Res<CameraSensitivity>,
+    zoom: Res<ZoomSettings>,
+    input: Res<InputSettings>,
+    window: Res<WindowSettings>,
+    minimap: Res<MinimapSettings>,
+    mut query: Query<Without<RemotePlayer, &MMOPlayer>>,
+) {
+    let exiting = state.app_exit_event_reader.iter(&app_exit_events).next().is_some();
+    let requested = state.save_settings_event_reader.iter(&save_settings_events).next().is_some();
+    if !exiting && !requested && !keyboard_input.just_pressed(bindings.save_settings) { return; }
+
+    let mut settings = Settings {
+        sensitivity: sensitivity.clone(),
+        zoom: zoom.clone(),
+        input: input.clone(),
+        bindings: bindings.clone(),
+        window: window.clone(),
+        minimap: minimap.clone(),
+    };
+    // the zoom is the keyboard player's, not a second player's or a bot's
+    if let Some(player) = query.iter().iter().find(|player| player.input_source == InputSource::KeyboardMouse) {
+        settings.zoom.initial = player.target_camera_distance;
+    }
+    settings.save(SETTINGS_PATH);
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use crate::npc::Npc;
+use crate::player::MMOPlayer;
+use crate::terrain::TerrainHeight;
+
+// sits just above the ground so it doesn't flicker against it
+const SHADOW_LIFT: f32 = 0.05;
+const SHADOW_THICKNESS: f32 = 0.01;
+const SHADOW_COLOR: Color = Color::rgba(0.05, 0.05, 0.06, 0.8);
+
+// bevy 0.1's renderer has no shadow maps, so players and npcs get a dark disc on the
+// ground under them instead. it shrinks and fades out of the way as they rise, which is
+// most of what a real shadow does for judging where something will land
+pub struct ShadowSettings {
+    pub enabled: bool,
+    pub radius: f32,
+    // at this height above the ground the disc is at its smallest and has faded out
+    pub fade_height: f32,
+    // how round the disc is, each one is four times the triangles of the last
+    pub subdivisions: usize,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            enabled: true,
+            radius: 1.2,
+            fade_height: 12.,
+            subdivisions: 3,
+        }
+    }
+}
+
+struct BlobShadow {
+    owner: Entity,
+}
+
+// marks a player or npc that already has its shadow
+struct HasBlobShadow;
+
+struct ShadowAppearance {
+    mesh: Handle<Mesh>,
+}
+
+pub struct ShadowPlugin;
+
+impl Plugin for ShadowPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ShadowSettings>()
+            .add_startup_system(load_shadow_appearance.system())
+            .add_system(spawn_blob_shadows.system())
+            .add_system(update_blob_shadows.system());
+    }
+}
+
+fn load_shadow_appearance(mut commands: Commands, settings: Res<ShadowSettings>, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(ShadowAppearance {
+        mesh: meshes.add(Mesh::from(shape::Icosphere { radius: 1., subdivisions: settings.subdivisions })),
+    });
+}
+
+#[allow(clippy::type_complexity)]
+fn spawn_blob_shadows(
+    mut commands: Commands,
+    settings: Res<ShadowSettings>,
+    appearance: Res<ShadowAppearance>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut player_query: Query<Without<HasBlobShadow, With<MMOPlayer, Entity>>>,
+    mut npc_query: Query<Without<HasBlobShadow, With<Npc, Entity>>>,
+) {
+    if !settings.enabled { return; }
+
+    let owners: Vec<Entity> = player_query.iter().iter().chain(npc_query.iter().iter()).collect();
+    for owner in owners {
+        // each disc fades on its own, so each has its own material. unshaded, so the sun
+        // going down doesn't light it up from below
+        let material = materials.add(StandardMaterial { albedo: SHADOW_COLOR, shaded: false, ..Default::default() });
+        let shadow = commands
+            .spawn(PbrComponents {
+                mesh: appearance.mesh,
+                material,
+                draw: Draw { is_transparent: true, ..Default::default() },
+                ..Default::default()
+            })
+            .with(NonUniformScale(Vec3::zero()))
+            .with(BlobShadow { owner })
+            .current_entity()
+            .unwrap();
+        // bevy 0.1 only builds the transform from one of the two scales, and ignores
+        // an entity that has both
+        commands.remove_one::<Scale>(shadow);
+        commands.insert_one(owner, HasBlobShadow);
+    }
+}
+
+// npcs float their height above the ground, so their feet are found from that
+#[allow(clippy::type_complexity)]
+fn update_blob_shadows(
+    mut commands: Commands,
+    settings: Res<ShadowSettings>,
+    terrain: Res<TerrainHeight>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    owner_query: Query<(&Transform, Option<&Npc>)>,
+    mut shadow_query: Query<(Entity, &BlobShadow, &Handle<StandardMaterial>, &mut Translation, &mut NonUniformScale, &mut Draw)>,
+) {
+    for (entity, shadow, material, mut translation, mut scale, mut draw) in &mut shadow_query.iter() {
+        let owner = match owner_query.get::<Transform>(shadow.owner) {
+            Ok(owner) => Vec3::from(owner.value.w_axis().truncate()),
+            Err(_) => {
+                materials.remove(material);
+                commands.despawn(entity);
+                continue;
+            }
+        };
+        draw.is_visible = settings.enabled;
+        if !settings.enabled { continue; }
+
+        let feet = owner.y() - owner_query.get::<Npc>(shadow.owner).map_or(0., |npc| npc.height);
+        let ground = terrain.height_at(owner.x(), owner.z());
+        let rise = ((feet - ground) / settings.fade_height.max(f32::EPSILON)).clamp(0., 1.);
+        let radius = settings.radius * (1. - rise * 0.7);
+        translation.0 = Vec3::new(owner.x(), ground + SHADOW_LIFT, owner.z());
+        scale.0 = Vec3::new(radius, SHADOW_THICKNESS, radius);
+        // only touched when it changes, a changed material is sent to the gpu again
+        let alpha = SHADOW_COLOR.a * (1. - rise);
+        if materials.get(material).is_some_and(|material| material.albedo.a != alpha) {
+            materials.get_mut(material).unwrap().albedo.a = alpha;
+        }
+    }
+}
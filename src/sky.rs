@@ -0,0 +1,147 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::Camera,
+        pipeline::{CullMode, PipelineDescriptor, RasterizationStateDescriptor, RenderPipeline},
+        render_graph::{base, AssetRenderResourcesNode, RenderGraph},
+        renderer::RenderResources,
+        shader::{ShaderStage, ShaderStages},
+    },
+};
+use crate::day_night::{mix, TimeOfDay};
+use crate::nameplate::local_camera_view;
+use crate::player::MMOPlayer;
+
+// inside the camera's far plane of 1000, so it's never clipped whichever way the camera looks
+const SKY_RADIUS: f32 = 900.;
+
+const DAY_ZENITH: Color = Color::rgb(0.22, 0.42, 0.78);
+const DAY_HORIZON: Color = Color::rgb(0.62, 0.78, 0.92);
+const NIGHT_ZENITH: Color = Color::rgb(0.0, 0.0, 0.01);
+const NIGHT_HORIZON: Color = Color::rgb(0.02, 0.03, 0.07);
+const DUSK_HORIZON: Color = Color::rgb(0.95, 0.5, 0.25);
+
+const SKY_MATERIAL_NODE: &str = "sky_material";
+
+const SKY_VERTEX_SHADER: &str = r#"
+#version 450
+layout(location = 0) in vec3 Vertex_Position;
+layout(location = 0) out vec3 v_Direction;
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 ViewProj;
+};
+layout(set = 1, binding = 0) uniform Transform {
+    mat4 Model;
+};
+void main() {
+    v_Direction = Vertex_Position;
+    gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
+}
+"#;
+
+// straight up is the zenith colour, anything level with or below the camera is the horizon's
+const SKY_FRAGMENT_SHADER: &str = r#"
+#version 450
+layout(location = 0) in vec3 v_Direction;
+layout(location = 0) out vec4 o_Target;
+layout(set = 1, binding = 1) uniform SkyMaterial_zenith {
+    vec4 zenith;
+};
+layout(set = 1, binding = 2) uniform SkyMaterial_horizon {
+    vec4 horizon;
+};
+void main() {
+    float height = clamp(normalize(v_Direction).y, 0.0, 1.0);
+    o_Target = mix(horizon, zenith, sqrt(height));
+}
+"#;
+
+// bevy 0.1 has no cube textures, so rather than a skybox the sky is a big sphere seen
+// from the inside, shaded from the horizon up to the zenith
+#[derive(RenderResources)]
+pub struct SkyMaterial {
+    pub zenith: Color,
+    pub horizon: Color,
+}
+
+struct SkyDome {
+    material: Handle<SkyMaterial>,
+}
+
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<SkyMaterial>()
+            .add_startup_system(spawn_sky.system())
+            .add_system(follow_camera.system())
+            .add_system(update_sky_colors.system());
+    }
+}
+
+fn spawn_sky(
+    mut commands: Commands,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+    mut render_graph: ResMut<RenderGraph>,
+) {
+    let mut pipeline = PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(ShaderStage::Vertex, SKY_VERTEX_SHADER)),
+        fragment: Some(shaders.add(Shader::from_glsl(ShaderStage::Fragment, SKY_FRAGMENT_SHADER))),
+    });
+    // the camera is inside the sphere, looking at the back of every face
+    pipeline.rasterization_state = Some(RasterizationStateDescriptor {
+        cull_mode: CullMode::None,
+        ..pipeline.rasterization_state.unwrap()
+    });
+    let pipeline = pipelines.add(pipeline);
+
+    render_graph.add_system_node(SKY_MATERIAL_NODE, AssetRenderResourcesNode::<SkyMaterial>::new(true));
+    render_graph.add_node_edge(SKY_MATERIAL_NODE, base::node::MAIN_PASS).unwrap();
+
+    let material = materials.add(SkyMaterial { zenith: DAY_ZENITH, horizon: DAY_HORIZON });
+    commands
+        .spawn(MeshComponents {
+            mesh: meshes.add(Mesh::from(shape::Icosphere { radius: SKY_RADIUS, subdivisions: 4 })),
+            render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(pipeline)]),
+            ..Default::default()
+        })
+        .with(material)
+        .with(SkyDome { material });
+}
+
+// centred on the camera, so the horizon stays at eye level and the sky never gets any closer
+fn follow_camera(
+    mut player_query: Query<&MMOPlayer>,
+    camera_query: Query<(&Transform, &Camera)>,
+    mut sky_query: Query<With<SkyDome, &mut Translation>>,
+) {
+    let (camera_transform, _) = match local_camera_view(&mut player_query, &camera_query) {
+        Some(view) => view,
+        None => return,
+    };
+    for mut translation in &mut sky_query.iter() {
+        translation.0 = camera_transform.w_axis().truncate().into();
+    }
+}
+
+fn update_sky_colors(
+    time_of_day: Res<TimeOfDay>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+    mut sky_query: Query<&SkyDome>,
+) {
+    let daylight = time_of_day.daylight();
+    // strongest halfway through sunrise and sunset, gone by full day or night
+    let dusk = 1. - (daylight * 2. - 1.).abs();
+    let zenith = mix(NIGHT_ZENITH, DAY_ZENITH, daylight);
+    let horizon = mix(mix(NIGHT_HORIZON, DAY_HORIZON, daylight), DUSK_HORIZON, dusk * 0.6);
+
+    for sky in &mut sky_query.iter() {
+        if let Some(material) = materials.get_mut(&sky.material) {
+            material.zenith = zenith;
+            material.horizon = horizon;
+        }
+    }
+}
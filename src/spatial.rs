@@ -0,0 +1,128 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+use crate::inventory::Item;
+use crate::player::MMOPlayer;
+
+// world units along x and z. about the largest radius anything asks for, so a query
+// rarely has to look past the cells around the one it starts in
+const CELL_SIZE: f32 = 16.;
+
+// players and items bucketed by where they stood at the start of the frame, so proximity
+// checks only look at the cells around them instead of every entity in the world
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(Entity, Vec3)>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        SpatialGrid {
+            cell_size: CELL_SIZE,
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl SpatialGrid {
+    fn cell(&self, position: Vec3) -> (i32, i32) {
+        ((position.x() / self.cell_size).floor() as i32, (position.z() / self.cell_size).floor() as i32)
+    }
+
+    pub fn clear(&mut self) {
+        // keeps each cell's allocation, the same cells tend to fill up again next frame
+        for entries in self.cells.values_mut() {
+            entries.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec3) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push((entity, position));
+    }
+
+    // everything indexed within radius of position, of any kind, so callers look the
+    // entities up in their own query to pick out the ones they care about
+    pub fn query_radius(&self, position: Vec3, radius: f32) -> Vec<Entity> {
+        let reach = Vec3::new(radius, 0., radius);
+        let (min_x, min_z) = self.cell(position - reach);
+        let (max_x, max_z) = self.cell(position + reach);
+        let mut found = Vec::new();
+        for x in min_x..=max_x {
+            for z in min_z..=max_z {
+                let entries = match self.cells.get(&(x, z)) {
+                    Some(entries) => entries,
+                    None => continue,
+                };
+                found.extend(
+                    entries
+                        .iter()
+                        .filter(|(_, other)| (*other - position).length() <= radius)
+                        .map(|&(entity, _)| entity),
+                );
+            }
+        }
+        found
+    }
+}
+
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SpatialGrid>()
+            // ahead of everything in update that queries it
+            .add_system_to_stage(stage::PRE_UPDATE, rebuild_spatial_grid.system());
+    }
+}
+
+fn rebuild_spatial_grid(
+    mut grid: ResMut<SpatialGrid>,
+    mut player_query: Query<With<MMOPlayer, (Entity, &Translation)>>,
+    mut item_query: Query<With<Item, (Entity, &Translation)>>,
+) {
+    grid.clear();
+    for (entity, translation) in &mut player_query.iter() {
+        grid.insert(entity, translation.0);
+    }
+    for (entity, translation) in &mut item_query.iter() {
+        grid.insert(entity, translation.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a spread of points either side of zero, so some land right on cell borders
+    fn scattered(count: u32) -> Vec<(Entity, Vec3)> {
+        let mut seed = 12345u32;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 8) as f32 / (1 << 24) as f32 * 100. - 50.
+        };
+        let mut points: Vec<(Entity, Vec3)> = (0..count).map(|id| (Entity::from_id(id), Vec3::new(next(), next(), next()))).collect();
+        points.push((Entity::from_id(count), Vec3::new(CELL_SIZE, 0., -CELL_SIZE)));
+        points
+    }
+
+    #[test]
+    fn query_radius_matches_checking_everything() {
+        let points = scattered(500);
+        let mut grid = SpatialGrid::default();
+        for &(entity, position) in points.iter() { grid.insert(entity, position); }
+
+        for &centre in [Vec3::zero(), Vec3::new(CELL_SIZE, 0., -CELL_SIZE), Vec3::new(-33.3, 5., 41.)].iter() {
+            for &radius in [0.5, 5., CELL_SIZE, 40.].iter() {
+                let mut found = grid.query_radius(centre, radius);
+                found.sort();
+                let mut expected: Vec<Entity> = points
+                    .iter()
+                    .filter(|(_, position)| (*position - centre).length() <= radius)
+                    .map(|&(entity, _)| entity)
+                    .collect();
+                expected.sort();
+                assert_eq!(found, expected, "centre {:?} radius {}", centre, radius);
+            }
+        }
+    }
+}
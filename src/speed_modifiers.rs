@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use crate::game_state::GameState;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SpeedModifierKind {
+    // added to the base speed before any multipliers
+    Add(f32),
+    Multiply(f32),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpeedModifier {
+    // what applied it, so it can be taken off again
+    pub source: &'static str,
+    pub kind: SpeedModifierKind,
+    // seconds left, none lasts until removed
+    pub remaining: Option<f32>,
+}
+
+// everything currently changing a player's move speed. the effective speed is
+// (base + every add) * every multiply, so the order they were added in doesn't matter
+#[derive(Default)]
+pub struct SpeedModifiers {
+    pub modifiers: Vec<SpeedModifier>,
+}
+
+impl SpeedModifiers {
+    pub fn add(&mut self, source: &'static str, kind: SpeedModifierKind) {
+        self.modifiers.push(SpeedModifier { source, kind, remaining: None });
+    }
+
+    // e.g. add_timed("frost", SpeedModifierKind::Multiply(0.5), 3.) for a three second slow
+    pub fn add_timed(&mut self, source: &'static str, kind: SpeedModifierKind, seconds: f32) {
+        self.modifiers.push(SpeedModifier { source, kind, remaining: Some(seconds) });
+    }
+
+    // takes off every modifier the source applied
+    pub fn remove(&mut self, source: &'static str) {
+        self.modifiers.retain(|modifier| modifier.source != source);
+    }
+
+    pub fn apply(&self, base: f32) -> f32 {
+        let mut added = 0.;
+        let mut multiplier = 1.;
+        for modifier in &self.modifiers {
+            match modifier.kind {
+                SpeedModifierKind::Add(amount) => added += amount,
+                SpeedModifierKind::Multiply(factor) => multiplier *= factor,
+            }
+        }
+        ((base + added) * multiplier).max(0.)
+    }
+
+    pub fn tick(&mut self, dt: f32) {
+        for modifier in &mut self.modifiers {
+            if let Some(remaining) = &mut modifier.remaining { *remaining -= dt; }
+        }
+        self.modifiers.retain(|modifier| modifier.remaining.is_none_or(|remaining| remaining > 0.));
+    }
+}
+
+pub fn tick_speed_modifiers(time: Res<Time>, game_state: Res<GameState>, mut query: Query<&mut SpeedModifiers>) {
+    if *game_state != GameState::Playing { return; }
+
+    for mut modifiers in &mut query.iter() {
+        modifiers.tick(time.delta_seconds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_come_before_multiplies_whatever_the_order() {
+        let mut modifiers = SpeedModifiers::default();
+        modifiers.add("haste", SpeedModifierKind::Multiply(1.5));
+        modifiers.add("level", SpeedModifierKind::Add(2.));
+        modifiers.add("frost", SpeedModifierKind::Multiply(0.5));
+        modifiers.add("boots", SpeedModifierKind::Add(1.));
+        // (5 + 2 + 1) * 1.5 * 0.5
+        assert!((modifiers.apply(5.) - 6.).abs() < 1e-5);
+
+        modifiers.remove("haste");
+        assert!((modifiers.apply(5.) - 4.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn speed_never_goes_negative() {
+        let mut modifiers = SpeedModifiers::default();
+        modifiers.add("curse", SpeedModifierKind::Add(-10.));
+        modifiers.add("haste", SpeedModifierKind::Multiply(2.));
+        assert_eq!(modifiers.apply(5.), 0.);
+    }
+}
@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use crate::player::MMOPlayer;
+
+// sprinting drains stamina and anything else lets it recover. running dry forces
+// sprinting off until it has climbed back past recovery_threshold, rather than
+// letting it flicker on and off right at zero
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    pub drain_per_second: f32,
+    pub regen_per_second: f32,
+    pub recovery_threshold: f32,
+    pub exhausted: bool,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Stamina {
+            current: 100.,
+            max: 100.,
+            drain_per_second: 20.,
+            regen_per_second: 15.,
+            recovery_threshold: 30.,
+            exhausted: false,
+        }
+    }
+}
+
+impl Stamina {
+    pub fn can_sprint(&self) -> bool {
+        !self.exhausted && self.current > 0.
+    }
+}
+
+#[allow(dead_code)]
+pub struct Exhausted {
+    pub entity: Entity,
+}
+
+#[allow(dead_code)]
+pub struct Recovered {
+    pub entity: Entity,
+}
+
+pub struct StaminaPlugin;
+
+impl Plugin for StaminaPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<Exhausted>()
+            .add_event::<Recovered>()
+            .add_system(update_stamina.system());
+    }
+}
+
+fn update_stamina(
+    time: Res<Time>,
+    mut exhausted_events: ResMut<Events<Exhausted>>,
+    mut recovered_events: ResMut<Events<Recovered>>,
+    mut query: Query<(Entity, &MMOPlayer, &mut Stamina)>,
+) {
+    for (entity, player, mut stamina) in &mut query.iter() {
+        if player.sprinting {
+            stamina.current = (stamina.current - stamina.drain_per_second * time.delta_seconds).max(0.);
+        } else {
+            stamina.current = (stamina.current + stamina.regen_per_second * time.delta_seconds).min(stamina.max);
+        }
+
+        if !stamina.exhausted && stamina.current <= 0. {
+            stamina.exhausted = true;
+            exhausted_events.send(Exhausted { entity });
+        } else if stamina.exhausted && stamina.current >= stamina.recovery_threshold {
+            stamina.exhausted = false;
+            recovered_events.send(Recovered { entity });
+        }
+    }
+}
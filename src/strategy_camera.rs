@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use crate::cursor::CursorPosition;
+use crate::game_state::GameState;
+use crate::network::RemotePlayer;
+use crate::player::{CameraMode, InputSource, KeyBindings, KeyboardCaptured, MMOPlayer};
+
+// the cursor starts panning this many pixels from the window edge, faster the closer it gets
+const EDGE_MARGIN: f32 = 30.;
+// world units per second per unit of camera distance, so zoomed out pans cover more ground
+const PAN_SPEED: f32 = 1.5;
+// the strategy camera hangs above and south of the point it looks at, always facing north
+const STRATEGY_HEIGHT: f32 = 2.;
+const STRATEGY_BACK: f32 = 1.;
+
+// where the strategy camera sits relative to the point it looks at, scaled by the zoom
+pub fn strategy_camera_offset(distance: f32) -> Vec3 {
+    Vec3::new(0., STRATEGY_HEIGHT, -STRATEGY_BACK) * distance
+}
+
+pub struct StrategyCameraPlugin;
+
+impl Plugin for StrategyCameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(pan_strategy_camera.system());
+    }
+}
+
+// 0 away from the edge, up to 1 right at it
+fn edge_push(position: f32, size: f32) -> f32 {
+    if position < EDGE_MARGIN {
+        -(1. - position.max(0.) / EDGE_MARGIN)
+    } else if position > size - EDGE_MARGIN {
+        1. - (size - position).max(0.) / EDGE_MARGIN
+    } else {
+        0.
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pan_strategy_camera(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    captured: Res<KeyboardCaptured>,
+    cursor: Res<CursorPosition>,
+    windows: Res<Windows>,
+    mut player_query: Query<Without<RemotePlayer, &mut MMOPlayer>>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    // screen space, x right and y up like the cursor
+    let mut pan = Vec2::zero();
+    if let (Some(cursor), Some(window)) = (cursor.0, windows.get_primary()) {
+        pan = Vec2::new(edge_push(cursor.x(), window.width as f32), edge_push(cursor.y(), window.height as f32));
+    }
+    if !captured.0 {
+        if keyboard_input.pressed(bindings.forward) { *pan.y_mut() += 1.; }
+        if keyboard_input.pressed(bindings.back) { *pan.y_mut() -= 1.; }
+        if keyboard_input.pressed(bindings.right) { *pan.x_mut() += 1.; }
+        if keyboard_input.pressed(bindings.left) { *pan.x_mut() -= 1.; }
+    }
+    if pan.length() > 1. { pan = pan.normalize(); }
+
+    for mut player in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse || player.camera_mode != CameraMode::Strategy { continue; }
+        // the camera faces +z, which puts the right of the screen towards -x
+        let speed = PAN_SPEED * player.camera_distance * time.delta_seconds;
+        player.strategy_pan += Vec3::new(-pan.x(), 0., pan.y()) * speed;
+    }
+}
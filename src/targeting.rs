@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use crate::focus::Focusable;
+use crate::game_state::{GameStarted, GameState};
+use crate::health::Health;
+use crate::network::RemotePlayer;
+use crate::player::{yaw_towards, InputSource, KeyBindings, KeyboardCaptured, MMOPlayer};
+
+const LOCK_RADIUS: f32 = 40.;
+// targets only drop off once they're well past where they could be picked up
+const LOCK_BREAK_RADIUS: f32 = 60.;
+// half angle either side of where the camera faces
+const LOCK_CONE: f32 = std::f32::consts::FRAC_PI_3;
+const LOCK_CAMERA_SMOOTHING: f32 = 5.;
+const DUMMY_SIZE: f32 = 1.5;
+
+// anything the player can lock on to
+pub struct Enemy;
+
+pub struct TargetingPlugin;
+
+impl Plugin for TargetingPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(spawn_training_dummies.system())
+            .add_system(update_lock_target.system())
+            .add_system(face_lock_target.system());
+    }
+}
+
+// something to lock on to until there are enemies that fight back
+fn spawn_training_dummies(
+    mut commands: Commands,
+    mut game_started_reader: Local<EventReader<GameStarted>>,
+    game_started_events: Res<Events<GameStarted>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if game_started_reader.iter(&game_started_events).last().is_none() { return; }
+
+    let mesh = meshes.add(Mesh::from(shape::Cube { size: DUMMY_SIZE }));
+    let material = materials.add(Color::rgb(0.8, 0.2, 0.2).into());
+    for &(x, z) in [(0., 25.), (-15., 20.), (15., 20.)].iter() {
+        commands
+            .spawn(PbrComponents {
+                mesh,
+                material,
+                translation: Translation::new(x, DUMMY_SIZE, z),
+                ..Default::default()
+            })
+            .with(Enemy)
+            .with(Health::default())
+            .with(Focusable { radius: DUMMY_SIZE * 1.5 });
+    }
+}
+
+fn wrap_angle(angle: f32) -> f32 {
+    let tau = std::f32::consts::PI * 2.;
+    (angle + std::f32::consts::PI).rem_euclid(tau) - std::f32::consts::PI
+}
+
+// tab picks the nearest enemy in front of the camera, pressing it again moves on to the
+// next nearest, and escape lets go
+#[allow(clippy::type_complexity)]
+fn update_lock_target(
+    game_state: Res<GameState>,
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    captured: Res<KeyboardCaptured>,
+    mut player_query: Query<Without<RemotePlayer, (&mut MMOPlayer, &Translation)>>,
+    mut enemy_query: Query<With<Enemy, (Entity, &Translation)>>,
+) {
+    if *game_state != GameState::Playing || captured.0 { return; }
+    let cycle = keyboard_input.just_pressed(bindings.lock_on);
+    let clear = keyboard_input.just_pressed(bindings.clear_lock);
+    if !cycle && !clear { return; }
+
+    for (mut player, translation) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        if clear {
+            player.lock_target = None;
+            continue;
+        }
+
+        let forward = Quat::from_rotation_y(-player.camera_yaw) * Vec3::unit_z();
+        let mut candidates = Vec::new();
+        for (entity, enemy_translation) in &mut enemy_query.iter() {
+            let mut offset = enemy_translation.0 - translation.0;
+            *offset.y_mut() = 0.;
+            let distance = offset.length();
+            if distance > LOCK_RADIUS { continue; }
+            if distance > 0. && forward.dot(offset / distance).acos() > LOCK_CONE { continue; }
+            candidates.push((entity, distance));
+        }
+        candidates.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+
+        // a lock that has left the cone is still cycled from, back to the nearest
+        let next = player
+            .lock_target
+            .and_then(|target| candidates.iter().position(|&(entity, _)| entity == target))
+            .map_or(0, |index| index + 1);
+        player.lock_target = candidates
+            .get(next)
+            .or_else(|| candidates.first())
+            .map(|&(entity, _)| entity);
+    }
+}
+
+fn face_lock_target(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    mut player_query: Query<Without<RemotePlayer, (&mut MMOPlayer, &Translation)>>,
+    target_query: Query<&Translation>,
+) {
+    if *game_state != GameState::Playing { return; }
+
+    for (mut player, translation) in &mut player_query.iter() {
+        let target = match player.lock_target {
+            Some(target) => target,
+            None => continue,
+        };
+        // the target was despawned, or got away
+        let target_translation = match target_query.get::<Translation>(target) {
+            Ok(target_translation) => target_translation.0,
+            Err(_) => {
+                player.lock_target = None;
+                continue;
+            }
+        };
+        let mut offset = target_translation - translation.0;
+        *offset.y_mut() = 0.;
+        if offset.length() > LOCK_BREAK_RADIUS {
+            player.lock_target = None;
+            continue;
+        }
+        if offset.length() < f32::EPSILON { continue; }
+
+        // the body snaps to the target, the camera swings round after it but can still be looked around
+        player.yaw = yaw_towards(offset);
+        let ease = 1. - (-LOCK_CAMERA_SMOOTHING * time.delta_seconds).exp();
+        player.camera_yaw += wrap_angle(player.yaw - player.camera_yaw) * ease;
+    }
+}
@@ -0,0 +1,272 @@
+use bevy::{
+    asset::AssetLoader,
+    prelude::*,
+    render::mesh::VertexAttribute,
+    render::texture::ImageTextureLoader,
+    render::pipeline::PrimitiveTopology,
+};
+use std::path::Path;
+use crate::water::WaterLevel;
+use crate::zones::ZoneManager;
+
+pub const HEIGHTMAP_PATH: &str = "res/heightmap.png";
+const TERRAIN_SIZE: f32 = 200.0;
+pub const TERRAIN_MAX_HEIGHT: f32 = 20.0;
+const TERRAIN_WATER_LEVEL: f32 = 4.0;
+// bevy 0.1 uploads mesh indices as u16, so the grid can't have more than 256 x 256 vertices
+const MAX_RESOLUTION: usize = 256;
+
+// a square grid of height samples centred on centre, the origin unless a zone moved it.
+// positions outside the grid sample the nearest edge, so walking off the terrain stays level
+pub struct TerrainHeight {
+    heights: Vec<f32>,
+    resolution: usize,
+    size: f32,
+    // x and z
+    centre: Vec2,
+}
+
+impl Default for TerrainHeight {
+    fn default() -> Self {
+        TerrainHeight::flat()
+    }
+}
+
+impl TerrainHeight {
+    pub fn flat() -> Self {
+        TerrainHeight {
+            heights: vec![0.],
+            resolution: 1,
+            size: 0.,
+            centre: Vec2::zero(),
+        }
+    }
+
+    // reads the first channel of each pixel, so any 8 bit per channel image works,
+    // greyscale or not. non-square images are cropped to their shorter side, and
+    // larger ones are point sampled down to MAX_RESOLUTION
+    pub fn from_texture(texture: &Texture, size: f32, max_height: f32) -> Option<Self> {
+        let width = texture.size.x() as usize;
+        let height = texture.size.y() as usize;
+        if width < 2 || height < 2 { return None; }
+
+        let stride = texture.data.len() / (width * height);
+        if stride == 0 { return None; }
+
+        let side = width.min(height);
+        let resolution = side.min(MAX_RESOLUTION);
+        let pixel = |index: usize| index * (side - 1) / (resolution - 1);
+        let mut heights = Vec::with_capacity(resolution * resolution);
+        for row in 0..resolution {
+            for column in 0..resolution {
+                let value = texture.data[(pixel(row) * width + pixel(column)) * stride];
+                heights.push(value as f32 / 255. * max_height);
+            }
+        }
+
+        Some(TerrainHeight { heights, resolution, size, centre: Vec2::zero() })
+    }
+
+    pub fn centred_at(self, centre: Vec2) -> Self {
+        TerrainHeight { centre, ..self }
+    }
+
+    fn step(&self) -> f32 {
+        self.size / (self.resolution - 1) as f32
+    }
+
+    fn sample(&self, column: usize, row: usize) -> f32 {
+        self.heights[row * self.resolution + column]
+    }
+
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        if self.resolution < 2 { return self.heights[0]; }
+
+        let max = (self.resolution - 1) as f32;
+        let grid_x = ((x - self.centre.x() + self.size / 2.) / self.step()).max(0.).min(max);
+        let grid_z = ((z - self.centre.y() + self.size / 2.) / self.step()).max(0.).min(max);
+
+        let column = (grid_x.floor() as usize).min(self.resolution - 2);
+        let row = (grid_z.floor() as usize).min(self.resolution - 2);
+        let tx = grid_x - column as f32;
+        let tz = grid_z - row as f32;
+
+        let near = self.sample(column, row) * (1. - tx) + self.sample(column + 1, row) * tx;
+        let far = self.sample(column, row + 1) * (1. - tx) + self.sample(column + 1, row + 1) * tx;
+        near * (1. - tz) + far * tz
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.resolution < 2
+    }
+
+    // how far along the ray it first meets the ground, marched at half the sample spacing
+    // then narrowed down. it starts where the ray drops below the highest sample, so a
+    // camera high above the terrain doesn't march all the way down to it
+    pub fn ray_distance(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<f32> {
+        let highest = self.heights.iter().cloned().fold(f32::MIN, f32::max);
+        let above = |distance: f32| {
+            let point = origin + direction * distance;
+            point.y() > self.height_at(point.x(), point.z())
+        };
+
+        let mut near = 0.;
+        if origin.y() > highest {
+            if direction.y() >= 0. { return None; }
+            near = (highest - origin.y()) / direction.y();
+        }
+        if !above(near) { return Some(near); }
+
+        let march = if self.is_flat() { max_distance } else { self.step() / 2. };
+        while near < max_distance {
+            let far = (near + march).min(max_distance);
+            if !above(far) {
+                let mut far = far;
+                for _ in 0..16 {
+                    let middle = (near + far) / 2.;
+                    if above(middle) { near = middle; } else { far = middle; }
+                }
+                return Some(far);
+            }
+            near = far;
+        }
+        None
+    }
+
+    pub fn normal_at(&self, x: f32, z: f32) -> Vec3 {
+        if self.resolution < 2 { return Vec3::unit_y(); }
+
+        let step = self.step();
+        let dx = self.height_at(x - step, z) - self.height_at(x + step, z);
+        let dz = self.height_at(x, z - step) - self.height_at(x, z + step);
+        Vec3::new(dx, 2. * step, dz).normalize()
+    }
+
+    // in the terrain's own space, around its centre rather than the world origin
+    fn mesh(&self) -> Mesh {
+        let half = self.size / 2.;
+        let step = self.step();
+        let max = (self.resolution - 1) as f32;
+
+        let mut positions = Vec::with_capacity(self.heights.len());
+        let mut normals = Vec::with_capacity(self.heights.len());
+        let mut uvs = Vec::with_capacity(self.heights.len());
+        for row in 0..self.resolution {
+            for column in 0..self.resolution {
+                let x = column as f32 * step - half;
+                let z = row as f32 * step - half;
+                positions.push([x, self.sample(column, row), z]);
+                normals.push(self.normal_at(x + self.centre.x(), z + self.centre.y()).into());
+                uvs.push([column as f32 / max, row as f32 / max]);
+            }
+        }
+
+        let mut indices = Vec::with_capacity((self.resolution - 1) * (self.resolution - 1) * 6);
+        let resolution = self.resolution as u32;
+        for row in 0..resolution - 1 {
+            for column in 0..resolution - 1 {
+                let corner = row * resolution + column;
+                indices.extend_from_slice(&[
+                    corner, corner + resolution, corner + 1,
+                    corner + 1, corner + resolution, corner + resolution + 1,
+                ]);
+            }
+        }
+
+        Mesh {
+            primitive_topology: PrimitiveTopology::TriangleList,
+            attributes: vec![
+                VertexAttribute::position(positions),
+                VertexAttribute::normal(normals),
+                VertexAttribute::uv(uvs),
+            ],
+            indices: Some(indices),
+        }
+    }
+}
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<TerrainHeight>()
+            .add_startup_system(load_terrain.system());
+    }
+}
+
+// the terrain is optional, without a heightmap the world stays flat. zones bring their
+// own, which the start zone loads the same way as every later one
+#[allow(clippy::too_many_arguments)]
+fn load_terrain(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    zones: Res<ZoneManager>,
+    mut terrain: ResMut<TerrainHeight>,
+    mut water: ResMut<WaterLevel>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !zones.zones.is_empty() || !Path::new(HEIGHTMAP_PATH).exists() { return; }
+
+    let heightmap = match asset_server.load_sync(&mut textures, HEIGHTMAP_PATH) {
+        Ok(heightmap) => heightmap,
+        Err(err) => {
+            eprintln!("failed to load heightmap '{}': {}, using flat ground", HEIGHTMAP_PATH, err);
+            return;
+        }
+    };
+
+    let heights = match heights_from(textures.get(&heightmap)) {
+        Some(heights) => heights,
+        None => return,
+    };
+
+    spawn_terrain(&mut commands, &mut meshes, &mut materials, &heights);
+    *terrain = heights;
+    // only hilly terrain has anywhere for water to sit
+    water.0 = Some(TERRAIN_WATER_LEVEL);
+}
+
+fn heights_from(heightmap: Option<&Texture>) -> Option<TerrainHeight> {
+    let heights = heightmap.and_then(|texture| TerrainHeight::from_texture(texture, TERRAIN_SIZE, TERRAIN_MAX_HEIGHT));
+    if heights.is_none() {
+        eprintln!("heightmap '{}' is too small to build terrain from, using flat ground", HEIGHTMAP_PATH);
+    }
+    heights
+}
+
+// the server has no asset server or meshes, but it steps players over the same ground
+// the clients see, so it reads the heightmap straight from the file
+pub fn load_terrain_heights(zones: Res<ZoneManager>, mut terrain: ResMut<TerrainHeight>, mut water: ResMut<WaterLevel>) {
+    if !zones.zones.is_empty() || !Path::new(HEIGHTMAP_PATH).exists() { return; }
+
+    let heightmap = match ImageTextureLoader.load_from_file(Path::new(HEIGHTMAP_PATH)) {
+        Ok(heightmap) => heightmap,
+        Err(err) => {
+            eprintln!("failed to load heightmap '{}': {}, using flat ground", HEIGHTMAP_PATH, err);
+            return;
+        }
+    };
+    if let Some(heights) = heights_from(Some(&heightmap)) {
+        *terrain = heights;
+        water.0 = Some(TERRAIN_WATER_LEVEL);
+    }
+}
+
+pub fn spawn_terrain(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    terrain: &TerrainHeight,
+) -> Entity {
+    commands
+        .spawn(PbrComponents {
+            mesh: meshes.add(terrain.mesh()),
+            material: materials.add(Color::rgb(0.45, 0.6, 0.35).into()),
+            translation: Translation::new(terrain.centre.x(), 0., terrain.centre.y()),
+            ..Default::default()
+        })
+        .current_entity()
+        .unwrap()
+}
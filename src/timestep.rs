@@ -0,0 +1,96 @@
+use bevy::prelude::*;
+use crate::game_state::GameState;
+
+pub const FIXED_TIMESTEP: f32 = 1. / 60.;
+// after a long hitch, drop the backlog rather than simulating it all at once and
+// falling further behind every frame
+const MAX_STEPS_PER_FRAME: u32 = 5;
+
+// gameplay integration runs in whole steps of FIXED_TIMESTEP, whatever the frame
+// rate. each frame the elapsed time is banked and split into the number of steps
+// to run this frame, with the remainder carried into the next
+pub struct FixedTimestep {
+    pub step: f32,
+    accumulator: f32,
+    steps: u32,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        FixedTimestep {
+            step: FIXED_TIMESTEP,
+            accumulator: 0.,
+            steps: 0,
+        }
+    }
+}
+
+impl FixedTimestep {
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    // how far the frame is between the last step and the next one, 0 to 1
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+}
+
+// the simulated position after the last two fixed steps. the rendered translation is
+// lerped between them by alpha, so motion stays smooth when the frame rate and step
+// rate don't line up, at the cost of drawing up to one step behind the simulation.
+// correction is a visual offset left behind when the simulation is corrected, which
+// is eased out over a few frames instead of popping
+pub struct FixedPosition {
+    pub previous: Vec3,
+    pub current: Vec3,
+    pub correction: Vec3,
+}
+
+impl FixedPosition {
+    pub fn new(position: Vec3) -> Self {
+        FixedPosition { previous: position, current: position, correction: Vec3::zero() }
+    }
+
+    pub fn teleport(&mut self, position: Vec3) {
+        *self = FixedPosition::new(position);
+    }
+
+    // moves the simulation without moving what is drawn, the difference eases out
+    pub fn correct(&mut self, position: Vec3) {
+        let error = self.current - position;
+        self.previous -= error;
+        self.current = position;
+        self.correction += error;
+    }
+
+    pub fn interpolated(&self, alpha: f32) -> Vec3 {
+        self.previous.lerp(self.current, alpha) + self.correction
+    }
+}
+
+pub struct FixedTimestepPlugin;
+
+impl Plugin for FixedTimestepPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<FixedTimestep>()
+            .add_system_to_stage(stage::PRE_UPDATE, advance_fixed_timestep.system());
+    }
+}
+
+// nothing is banked while paused, so unpausing doesn't replay the pause as a burst of steps
+fn advance_fixed_timestep(time: Res<Time>, state: Res<GameState>, mut fixed: ResMut<FixedTimestep>) {
+    if *state != GameState::Playing {
+        fixed.steps = 0;
+        return;
+    }
+
+    fixed.accumulator += time.delta_seconds;
+    let mut steps = (fixed.accumulator / fixed.step) as u32;
+    fixed.accumulator -= steps as f32 * fixed.step;
+    if steps > MAX_STEPS_PER_FRAME {
+        steps = MAX_STEPS_PER_FRAME;
+        fixed.accumulator = 0.;
+    }
+    fixed.steps = steps;
+}
@@ -0,0 +1,479 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{ErrorKind, Read, Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+use crate::delta::{SnapshotDecoder, SnapshotEncoder};
+use crate::network::{NetworkId, PlayerSnapshot, Transport};
+use crate::player::{MovementState, StepInput};
+use crate::prediction::{AuthoritativeState, InputSequence};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::websocket_server::{self, OPCODE_BINARY, OPCODE_CLOSE, OPCODE_CONTINUATION, OPCODE_PING, OPCODE_PONG};
+
+// anything bigger is garbage or hostile, and the connection is dropped
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+// bumped whenever Message changes, so mismatched builds are turned away at the handshake
+pub const PROTOCOL_VERSION: u32 = 3;
+
+// everything that goes over a connection, either way. snapshots are delta compressed
+// against the last one the other end acknowledged, see delta
+#[derive(Serialize, Deserialize)]
+pub enum Message {
+    // the first thing a client sends. an empty name is made up from the id
+    Hello { protocol: u32, name: String },
+    // the server's answer to a hello, its snapshots of this client go out under id
+    Welcome { id: NetworkId },
+    // the server closes the connection straight after
+    Rejected { reason: String },
+    // from a client the id is ignored, the server knows who sent it
+    Snapshot { id: NetworkId, bytes: Vec<u8> },
+    // acknowledges a snapshot from id, so later ones can be sent relative to it
+    Ack { id: NetworkId, sequence: u32 },
+    Input { sequence: InputSequence, input: StepInput },
+    // the client's player was moved to state without walking there, its inputs from
+    // sequence on start from there
+    Teleport { sequence: InputSequence, state: MovementState },
+    Authoritative(AuthoritativeState),
+    // id's connection has gone, and their player with it
+    Left { id: NetworkId },
+    // sent both ways when there is nothing else to send, so silence means the other end is gone
+    Heartbeat,
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("messages are always serializable")
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        bincode::deserialize(bytes).map_err(|err| err.to_string())
+    }
+}
+
+// the client side of a connection, the same whatever carries the bytes. incoming messages
+// are sorted here into what the Transport methods hand out, replies are left in outgoing.
+// nothing but the hello goes out until the server has welcomed us
+pub struct Session {
+    local_id: Option<NetworkId>,
+    encoder: SnapshotEncoder,
+    decoders: HashMap<NetworkId, SnapshotDecoder>,
+    snapshots: Vec<(NetworkId, PlayerSnapshot)>,
+    authoritative: Option<AuthoritativeState>,
+    departures: Vec<NetworkId>,
+    // whether anything has arrived since take_heard was last called
+    heard: bool,
+    pub outgoing: Vec<Message>,
+}
+
+impl Session {
+    pub fn new(name: &str) -> Self {
+        Session {
+            local_id: None,
+            encoder: SnapshotEncoder::default(),
+            decoders: HashMap::new(),
+            snapshots: Vec::new(),
+            authoritative: None,
+            departures: Vec::new(),
+            heard: false,
+            outgoing: vec![Message::Hello { protocol: PROTOCOL_VERSION, name: name.to_string() }],
+        }
+    }
+
+    pub fn local_id(&self) -> Option<NetworkId> {
+        self.local_id
+    }
+
+    pub fn snapshot(&mut self, snapshot: PlayerSnapshot) {
+        if self.local_id.is_none() { return; }
+        let bytes = self.encoder.encode(snapshot);
+        self.outgoing.push(Message::Snapshot { id: 0, bytes });
+    }
+
+    pub fn input(&mut self, sequence: InputSequence, input: StepInput) {
+        if self.local_id.is_none() { return; }
+        self.outgoing.push(Message::Input { sequence, input });
+    }
+
+    pub fn teleport(&mut self, sequence: InputSequence, state: MovementState) {
+        if self.local_id.is_none() { return; }
+        self.outgoing.push(Message::Teleport { sequence, state });
+    }
+
+    pub fn heartbeat(&mut self) {
+        if self.local_id.is_none() { return; }
+        self.outgoing.push(Message::Heartbeat);
+    }
+
+    // an error means the connection is over, with the reason why
+    pub fn handle(&mut self, message: Message) -> Result<(), String> {
+        self.heard = true;
+        match message {
+            Message::Welcome { id } => {
+                if self.local_id.is_some() { return Err("welcomed twice".to_string()); }
+                self.local_id = Some(id);
+            }
+            Message::Rejected { reason } => return Err(format!("rejected by the server, {}", reason)),
+            // our own player is never drawn from snapshots, whether or not the server relays them
+            Message::Snapshot { id, .. } if Some(id) == self.local_id => {}
+            Message::Snapshot { id, bytes } => match self.decoders.entry(id).or_default().decode(&bytes) {
+                Ok((sequence, snapshot)) => {
+                    self.snapshots.push((id, snapshot));
+                    self.outgoing.push(Message::Ack { id, sequence });
+                }
+                Err(err) => eprintln!("dropping snapshot from player #{}: {}", id, err),
+            },
+            // only our own snapshots are acknowledged back to us
+            Message::Ack { sequence, .. } => self.encoder.acknowledge(sequence),
+            // only the newest correction matters, replaying from an older one would be wasted
+            Message::Authoritative(state) => self.authoritative = Some(state),
+            Message::Left { id } => {
+                self.decoders.remove(&id);
+                self.departures.push(id);
+            }
+            Message::Heartbeat => {}
+            Message::Hello { .. } | Message::Input { .. } | Message::Teleport { .. } => {
+                return Err("the server sent a client message".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn take_snapshots(&mut self) -> Vec<(NetworkId, PlayerSnapshot)> {
+        std::mem::take(&mut self.snapshots)
+    }
+
+    pub fn take_authoritative(&mut self) -> Option<AuthoritativeState> {
+        self.authoritative.take()
+    }
+
+    pub fn take_departures(&mut self) -> Vec<NetworkId> {
+        std::mem::take(&mut self.departures)
+    }
+
+    pub fn take_heard(&mut self) -> bool {
+        std::mem::take(&mut self.heard)
+    }
+}
+
+// ws:// and wss:// addresses go over a websocket, anything else is a host:port for tcp
+pub fn connect(address: &str, name: &str) -> Result<Box<dyn Transport>, String> {
+    if address.starts_with("ws://") || address.starts_with("wss://") {
+        #[cfg(feature = "websocket")]
+        return Ok(Box::new(crate::websocket::WebSocketTransport::connect(address, name)?));
+        #[cfg(not(feature = "websocket"))]
+        return Err("websocket addresses need the websocket feature".to_string());
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    return Ok(Box::new(TcpTransport::connect(address, name)?));
+    #[cfg(target_arch = "wasm32")]
+    Err("browsers can only connect to ws:// or wss:// addresses".to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[cfg(not(target_arch = "wasm32"))]
+enum Framing {
+    // a little endian u32 length in front of each message
+    Length,
+    // a browser, which asks to upgrade over http and then sends websocket frames, one
+    // Message to each websocket message. partial holds one split across several frames
+    WebSocket { upgraded: bool, partial: Vec<u8> },
+}
+
+// a non-blocking tcp stream carrying messages, shared by the native client and the
+// server. once error is set the stream is finished
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Connection {
+    stream: TcpStream,
+    framing: Framing,
+    incoming: Vec<u8>,
+    unsent: Vec<u8>,
+    pub error: Option<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Connection {
+    pub fn new(stream: TcpStream) -> Result<Self, String> {
+        stream.set_nonblocking(true).map_err(|err| err.to_string())?;
+        // snapshots are tiny and go every frame, batching them up only adds latency
+        stream.set_nodelay(true).map_err(|err| err.to_string())?;
+        Ok(Connection {
+            stream,
+            framing: Framing::Length,
+            incoming: Vec::new(),
+            unsent: Vec::new(),
+            error: None,
+        })
+    }
+
+    // the server's end of a browser's connection, which starts with the upgrade handshake
+    pub fn websocket(stream: TcpStream) -> Result<Self, String> {
+        let mut connection = Connection::new(stream)?;
+        connection.framing = Framing::WebSocket { upgraded: false, partial: Vec::new() };
+        Ok(connection)
+    }
+
+    // blocks until connected or CONNECT_TIMEOUT, after that nothing does
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let address = address
+            .to_socket_addrs()
+            .map_err(|err| err.to_string())?
+            .next()
+            .ok_or_else(|| format!("{} doesn't resolve to anything", address))?;
+        Connection::new(TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(|err| err.to_string())?)
+    }
+
+    fn fail(&mut self, reason: String) {
+        self.error.get_or_insert(reason);
+    }
+
+    pub fn queue(&mut self, message: &Message) {
+        let bytes = message.encode();
+        match self.framing {
+            Framing::Length => {
+                self.unsent.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+                self.unsent.extend_from_slice(&bytes);
+            }
+            Framing::WebSocket { .. } => self.unsent.extend_from_slice(&websocket_server::encode_frame(OPCODE_BINARY, &bytes)),
+        }
+    }
+
+    // every message that has arrived whole. ones that came in just before the other end
+    // closed are still handed out, so a rejection can be read before the error is
+    pub fn receive(&mut self) -> Vec<Message> {
+        let mut buffer = [0; 4096];
+        while self.error.is_none() {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.fail("closed by the other end".to_string()),
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => self.fail(err.to_string()),
+            }
+        }
+
+        match self.framing {
+            Framing::Length => self.length_framed_messages(),
+            Framing::WebSocket { .. } => self.websocket_messages(),
+        }
+    }
+
+    fn length_framed_messages(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        while self.incoming.len() >= 4 {
+            let length = u32::from_le_bytes([self.incoming[0], self.incoming[1], self.incoming[2], self.incoming[3]]) as usize;
+            if length > MAX_MESSAGE_SIZE {
+                self.fail(format!("{} byte message is over the limit", length));
+                self.incoming.clear();
+                break;
+            }
+            if self.incoming.len() < 4 + length { break; }
+            let frame: Vec<u8> = self.incoming.drain(..4 + length).skip(4).collect();
+            if !self.decode_into(&frame, &mut messages) { break; }
+        }
+        messages
+    }
+
+    // pings are answered here, a close is the other end going like any other
+    fn websocket_messages(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        let (upgraded, mut partial) = match &mut self.framing {
+            Framing::WebSocket { upgraded, partial } => (*upgraded, std::mem::take(partial)),
+            Framing::Length => return messages,
+        };
+
+        if !upgraded {
+            let end = match self.incoming.windows(4).position(|window| window == b"\r\n\r\n") {
+                Some(end) => end + 4,
+                None => {
+                    if self.incoming.len() > websocket_server::MAX_UPGRADE_REQUEST_SIZE {
+                        self.fail("the websocket upgrade request is too long".to_string());
+                        self.incoming.clear();
+                    }
+                    return messages;
+                }
+            };
+            let request: Vec<u8> = self.incoming.drain(..end).collect();
+            match websocket_server::upgrade_response(&request) {
+                // ahead of anything queued, nothing else can have been
+                Ok(response) => self.unsent.extend_from_slice(&response),
+                Err(err) => {
+                    self.fail(err);
+                    self.incoming.clear();
+                    return messages;
+                }
+            }
+        }
+
+        loop {
+            let (frame, length) = match websocket_server::read_frame(&self.incoming, MAX_MESSAGE_SIZE) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(err) => {
+                    self.fail(err);
+                    self.incoming.clear();
+                    break;
+                }
+            };
+            self.incoming.drain(..length);
+            match frame.opcode {
+                OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    partial.extend_from_slice(&frame.payload);
+                    if partial.len() > MAX_MESSAGE_SIZE {
+                        self.fail(format!("{} byte message is over the limit", partial.len()));
+                        self.incoming.clear();
+                        break;
+                    }
+                    if frame.fin && !self.decode_into(&std::mem::take(&mut partial), &mut messages) { break; }
+                }
+                OPCODE_PING => self.unsent.extend_from_slice(&websocket_server::encode_frame(OPCODE_PONG, &frame.payload)),
+                OPCODE_PONG => {}
+                OPCODE_CLOSE => {
+                    self.fail("closed by the other end".to_string());
+                    break;
+                }
+                // text messages aren't part of the protocol
+                opcode => {
+                    self.fail(format!("unexpected websocket opcode {}", opcode));
+                    self.incoming.clear();
+                    break;
+                }
+            }
+        }
+        self.framing = Framing::WebSocket { upgraded: true, partial };
+        messages
+    }
+
+    // false once the stream is unusable, anything after an unreadable message is garbage
+    fn decode_into(&mut self, bytes: &[u8], messages: &mut Vec<Message>) -> bool {
+        match Message::decode(bytes) {
+            Ok(message) => {
+                messages.push(message);
+                true
+            }
+            Err(err) => {
+                self.fail(format!("unreadable message, {}", err));
+                self.incoming.clear();
+                false
+            }
+        }
+    }
+
+    // whatever the socket won't take now is held back for the next frame, in order
+    pub fn flush(&mut self) {
+        while !self.unsent.is_empty() && self.error.is_none() {
+            match self.stream.write(&self.unsent) {
+                Ok(0) => self.fail("closed by the other end".to_string()),
+                Ok(written) => { self.unsent.drain(..written); }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => self.fail(err.to_string()),
+            }
+        }
+    }
+}
+
+// the native client, a Connection to the server
+#[cfg(not(target_arch = "wasm32"))]
+pub struct TcpTransport {
+    // none once the connection has dropped, nothing is sent or received after that
+    connection: Option<Connection>,
+    session: Session,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TcpTransport {
+    pub fn connect(address: &str, name: &str) -> Result<Self, String> {
+        let mut transport = TcpTransport {
+            connection: Some(Connection::connect(address)?),
+            session: Session::new(name),
+        };
+        transport.flush();
+        Ok(transport)
+    }
+
+    fn disconnect(&mut self, reason: &str) {
+        if self.connection.take().is_some() { eprintln!("disconnected from server: {}", reason); }
+    }
+
+    fn poll(&mut self) {
+        let messages = match self.connection.as_mut() {
+            Some(connection) => connection.receive(),
+            None => return,
+        };
+        for message in messages {
+            if let Err(reason) = self.session.handle(message) { return self.disconnect(&reason); }
+        }
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        let connection = match self.connection.as_mut() {
+            Some(connection) => connection,
+            None => return,
+        };
+        for message in self.session.outgoing.drain(..) {
+            connection.queue(&message);
+        }
+        connection.flush();
+        if let Some(reason) = connection.error.clone() { self.disconnect(&reason); }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Transport for TcpTransport {
+    fn send(&mut self, snapshot: PlayerSnapshot) {
+        if self.connection.is_none() { return; }
+        self.session.snapshot(snapshot);
+        self.flush();
+    }
+
+    fn receive(&mut self) -> Vec<(NetworkId, PlayerSnapshot)> {
+        self.poll();
+        self.session.take_snapshots()
+    }
+
+    fn send_input(&mut self, sequence: InputSequence, input: StepInput) {
+        if self.connection.is_none() { return; }
+        self.session.input(sequence, input);
+        self.flush();
+    }
+
+    fn send_teleport(&mut self, sequence: InputSequence, state: MovementState) {
+        if self.connection.is_none() { return; }
+        self.session.teleport(sequence, state);
+        self.flush();
+    }
+
+    fn receive_authoritative(&mut self) -> Option<AuthoritativeState> {
+        self.poll();
+        self.session.take_authoritative()
+    }
+
+    fn receive_departures(&mut self) -> Vec<NetworkId> {
+        self.session.take_departures()
+    }
+
+    fn local_id(&self) -> Option<NetworkId> {
+        self.session.local_id()
+    }
+
+    fn send_heartbeat(&mut self) {
+        if self.connection.is_none() { return; }
+        self.session.heartbeat();
+        self.flush();
+    }
+
+    fn take_heard(&mut self) -> bool {
+        self.poll();
+        self.session.take_heard()
+    }
+
+    fn connected(&self) -> bool {
+        self.connection.is_some()
+    }
+}
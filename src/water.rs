@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+use std::collections::HashSet;
+use crate::player::MMOPlayer;
+
+const WATER_PLANE_SIZE: f32 = 1000.;
+
+// height of the water surface, none for a world without water
+#[derive(Default)]
+pub struct WaterLevel(pub Option<f32>);
+
+impl WaterLevel {
+    // how far below the surface y is, negative above it and when there's no water
+    pub fn depth(&self, y: f32) -> f32 {
+        self.0.map_or(f32::NEG_INFINITY, |level| level - y)
+    }
+}
+
+#[allow(dead_code)]
+pub struct EnteredWater {
+    pub entity: Entity,
+}
+
+#[allow(dead_code)]
+pub struct LeftWater {
+    pub entity: Entity,
+}
+
+struct WaterSurface;
+
+// just the visible surface, the swimming itself is part of the player simulation
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(update_water_surface.system());
+    }
+}
+
+// the level can be set or moved at any time, so the surface is spawned the first
+// time there is one and follows it after that
+fn update_water_surface(
+    mut commands: Commands,
+    water: Res<WaterLevel>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut surface_query: Query<(&WaterSurface, &mut Translation, &mut Draw)>,
+) {
+    let mut found = false;
+    for (_surface, mut translation, mut draw) in &mut surface_query.iter() {
+        found = true;
+        draw.is_visible = water.0.is_some();
+        if let Some(level) = water.0 { *translation.y_mut() = level; }
+    }
+
+    if let (false, Some(level)) = (found, water.0) {
+        commands
+            .spawn(PbrComponents {
+                mesh: meshes.add(Mesh::from(shape::Plane { size: WATER_PLANE_SIZE })),
+                material: materials.add(Color::rgb(0.15, 0.35, 0.6).into()),
+                translation: Translation::new(0., level, 0.),
+                ..Default::default()
+            })
+            .with(WaterSurface);
+    }
+}
+
+// players can go in and out several times over one frame's steps, only where they
+// ended up is reported
+pub fn send_water_events(
+    mut swimmers: Local<HashSet<Entity>>,
+    mut entered_water_events: ResMut<Events<EnteredWater>>,
+    mut left_water_events: ResMut<Events<LeftWater>>,
+    mut player_query: Query<(Entity, &MMOPlayer)>,
+) {
+    for (entity, player) in &mut player_query.iter() {
+        if player.swimming && swimmers.insert(entity) {
+            entered_water_events.send(EnteredWater { entity });
+        } else if !player.swimming && swimmers.remove(&entity) {
+            left_water_events.send(LeftWater { entity });
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::{Arc, Mutex};
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{BinaryType, CloseEvent, ErrorEvent, MessageEvent, WebSocket};
+use crate::network::{NetworkId, PlayerSnapshot, Transport};
+use crate::player::{MovementState, StepInput};
+use crate::prediction::{AuthoritativeState, InputSequence};
+use crate::transport::{Message, Session, MAX_MESSAGE_SIZE};
+
+// filled in by the socket's callbacks, which run between frames
+#[derive(Default)]
+struct Inbox {
+    messages: Vec<Vec<u8>>,
+    closed: Option<String>,
+}
+
+// the browser's websocket, for wasm builds. websocket messages are already framed, so
+// each one is a single Message with no length in front
+pub struct WebSocketTransport {
+    socket: WebSocket,
+    session: Session,
+    inbox: Arc<Mutex<Inbox>>,
+    disconnected: bool,
+    // the socket only holds them weakly, they have to live as long as it does
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+    _on_error: Closure<dyn FnMut(ErrorEvent)>,
+}
+
+// wasm runs the whole app on the browser's one thread, the js handles never cross threads
+unsafe impl Send for WebSocketTransport {}
+unsafe impl Sync for WebSocketTransport {}
+
+impl WebSocketTransport {
+    // returns straight away, anything sent before the socket opens waits for it
+    pub fn connect(url: &str, name: &str) -> Result<Self, String> {
+        let socket = WebSocket::new(url).map_err(|err| format!("{:?}", err))?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let inbox = Arc::new(Mutex::new(Inbox::default()));
+
+        let message_inbox = inbox.clone();
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            // text messages aren't part of the protocol
+            if let Ok(buffer) = event.data().dyn_into::<js_sys::ArrayBuffer>() {
+                message_inbox.lock().unwrap().messages.push(js_sys::Uint8Array::new(&buffer).to_vec());
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let close_inbox = inbox.clone();
+        let on_close = Closure::wrap(Box::new(move |event: CloseEvent| {
+            close_inbox.lock().unwrap().closed.get_or_insert(format!("closed by the server ({})", event.code()));
+        }) as Box<dyn FnMut(CloseEvent)>);
+        let error_inbox = inbox.clone();
+        let on_error = Closure::wrap(Box::new(move |event: ErrorEvent| {
+            error_inbox.lock().unwrap().closed.get_or_insert(event.message());
+        }) as Box<dyn FnMut(ErrorEvent)>);
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+        Ok(WebSocketTransport {
+            socket,
+            session: Session::new(name),
+            inbox,
+            disconnected: false,
+            _on_message: on_message,
+            _on_close: on_close,
+            _on_error: on_error,
+        })
+    }
+
+    fn disconnect(&mut self, reason: &str) {
+        if self.disconnected { return; }
+        self.disconnected = true;
+        self.session.outgoing.clear();
+        let _ = self.socket.close();
+        eprintln!("disconnected from server: {}", reason);
+    }
+
+    fn poll(&mut self) {
+        let (messages, closed) = {
+            let mut inbox = self.inbox.lock().unwrap();
+            (std::mem::take(&mut inbox.messages), inbox.closed.take())
+        };
+        for bytes in messages {
+            if self.disconnected { break; }
+            if bytes.len() > MAX_MESSAGE_SIZE {
+                self.disconnect(&format!("{} byte message is over the limit", bytes.len()));
+                break;
+            }
+            let handled = Message::decode(&bytes).map_err(|err| format!("unreadable message, {}", err)).and_then(|message| self.session.handle(message));
+            if let Err(reason) = handled { self.disconnect(&reason); }
+        }
+        if let Some(reason) = closed { self.disconnect(&reason); }
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        if self.disconnected || self.socket.ready_state() != WebSocket::OPEN { return; }
+        for message in std::mem::take(&mut self.session.outgoing) {
+            if let Err(err) = self.socket.send_with_u8_array(&message.encode()) {
+                return self.disconnect(&format!("{:?}", err));
+            }
+        }
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn send(&mut self, snapshot: PlayerSnapshot) {
+        if self.disconnected { return; }
+        self.session.snapshot(snapshot);
+        self.flush();
+    }
+
+    fn receive(&mut self) -> Vec<(NetworkId, PlayerSnapshot)> {
+        self.poll();
+        self.session.take_snapshots()
+    }
+
+    fn send_input(&mut self, sequence: InputSequence, input: StepInput) {
+        if self.disconnected { return; }
+        self.session.input(sequence, input);
+        self.flush();
+    }
+
+    fn send_teleport(&mut self, sequence: InputSequence, state: MovementState) {
+        if self.disconnected { return; }
+        self.session.teleport(sequence, state);
+        self.flush();
+    }
+
+    fn receive_authoritative(&mut self) -> Option<AuthoritativeState> {
+        self.poll();
+        self.session.take_authoritative()
+    }
+
+    fn receive_departures(&mut self) -> Vec<NetworkId> {
+        self.session.take_departures()
+    }
+
+    fn local_id(&self) -> Option<NetworkId> {
+        self.session.local_id()
+    }
+
+    fn send_heartbeat(&mut self) {
+        if self.disconnected { return; }
+        self.session.heartbeat();
+        self.flush();
+    }
+
+    fn take_heard(&mut self) -> bool {
+        self.poll();
+        self.session.take_heard()
+    }
+
+    // still connecting counts, a socket that never opens ends in a close event
+    fn connected(&self) -> bool {
+        !self.disconnected
+    }
+}
@@ -0,0 +1,210 @@
+// the server's end of rfc 6455, for browser clients on the websocket transport. only as
+// much as Connection needs: the http upgrade and unmasking the frames browsers send
+
+// a request any bigger than this isn't a browser asking to upgrade
+pub const MAX_UPGRADE_REQUEST_SIZE: usize = 8192;
+
+pub const OPCODE_CONTINUATION: u8 = 0x0;
+pub const OPCODE_BINARY: u8 = 0x2;
+pub const OPCODE_CLOSE: u8 = 0x8;
+pub const OPCODE_PING: u8 = 0x9;
+pub const OPCODE_PONG: u8 = 0xa;
+
+// every server picks the same one, it only proves the server understood the upgrade
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub struct Frame {
+    // false for all but the last frame of a message split across several
+    pub fin: bool,
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+// the reply to a browser's upgrade request, everything up to and including the blank line
+pub fn upgrade_response(request: &[u8]) -> Result<Vec<u8>, String> {
+    let request = std::str::from_utf8(request).map_err(|_| "the upgrade request isn't text".to_string())?;
+    let mut lines = request.split("\r\n");
+    if !lines.next().is_some_and(|line| line.starts_with("GET ")) {
+        return Err("the upgrade request isn't a GET".to_string());
+    }
+    let key = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-key"))
+        .map(|(_, value)| value.trim())
+        .ok_or("the upgrade request has no Sec-WebSocket-Key")?;
+
+    Ok(format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )
+    .into_bytes())
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+// a whole frame from the front of bytes and how many bytes it took up, none until all
+// of it has arrived. browsers always mask what they send, servers never do
+pub fn read_frame(bytes: &[u8], max_size: usize) -> Result<Option<(Frame, usize)>, String> {
+    if bytes.len() < 2 { return Ok(None); }
+    let fin = bytes[0] & 0x80 != 0;
+    let opcode = bytes[0] & 0x0f;
+    if bytes[1] & 0x80 == 0 { return Err("websocket frame from the client isn't masked".to_string()); }
+
+    let (length, header) = match bytes[1] & 0x7f {
+        126 => match bytes.get(2..4) {
+            Some(length) => (u16::from_be_bytes([length[0], length[1]]) as u64, 4),
+            None => return Ok(None),
+        },
+        127 => match bytes.get(2..10) {
+            Some(length) => {
+                let mut be_bytes = [0; 8];
+                be_bytes.copy_from_slice(length);
+                (u64::from_be_bytes(be_bytes), 10)
+            }
+            None => return Ok(None),
+        },
+        length => (length as u64, 2),
+    };
+    if length > max_size as u64 { return Err(format!("{} byte websocket frame is over the limit", length)); }
+    let length = length as usize;
+    if bytes.len() < header + 4 + length { return Ok(None); }
+
+    let mask = &bytes[header..header + 4];
+    let payload = bytes[header + 4..header + 4 + length]
+        .iter()
+        .zip(mask.iter().cycle())
+        .map(|(byte, mask)| byte ^ mask)
+        .collect();
+    Ok(Some((Frame { fin, opcode, payload }, header + 4 + length)))
+}
+
+pub fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length if length < 126 => frame.push(length as u8),
+        length if length <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476, 0xc3d2_e1f0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 { message.push(0); }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for index in 16..80 {
+            words[index] = (words[index - 3] ^ words[index - 8] ^ words[index - 14] ^ words[index - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (index, &word) in words.iter().enumerate() {
+            let (f, k) = match index {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let next = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = next;
+        }
+        for (state, value) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *state = state.wrapping_add(*value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(state.iter()) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - 6 * index)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what a browser would send, masked
+    fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frame = encode_frame(opcode, payload);
+        let header = frame.len() - payload.len();
+        if !fin { frame[0] &= 0x7f; }
+        frame[1] |= 0x80;
+        let masked: Vec<u8> = payload.iter().zip(mask.iter().cycle()).map(|(byte, mask)| byte ^ mask).collect();
+        frame.truncate(header);
+        frame.extend_from_slice(&mask);
+        frame.extend_from_slice(&masked);
+        frame
+    }
+
+    #[test]
+    fn accept_key_matches_the_rfc_example() {
+        assert_eq!(base64(&sha1(b"abc")), "qZk+NkcGgWq6PiVxeFDCbJzQ2J0=");
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        let request = b"GET /play HTTP/1.1\r\nHost: localhost:7778\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nsec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+        let response = String::from_utf8(upgrade_response(request).unwrap()).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101 "));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert!(upgrade_response(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn client_frames_are_unmasked_whatever_their_length() {
+        for &length in [0, 5, 125, 126, 300, 70_000].iter() {
+            let payload: Vec<u8> = (0..length).map(|index| index as u8).collect();
+            let frame = client_frame(true, OPCODE_BINARY, &payload);
+            let (read, used) = read_frame(&frame, 100_000).unwrap().unwrap();
+            assert_eq!((read.fin, read.opcode, used), (true, OPCODE_BINARY, frame.len()), "length {}", length);
+            assert_eq!(read.payload, payload, "length {}", length);
+
+            // nothing until the whole frame is in
+            assert!(read_frame(&frame[..frame.len() - 1], 100_000).unwrap().is_none(), "length {}", length);
+        }
+    }
+
+    #[test]
+    fn bad_client_frames_are_refused() {
+        assert!(read_frame(&encode_frame(OPCODE_BINARY, b"unmasked"), 1024).is_err());
+        assert!(read_frame(&client_frame(true, OPCODE_BINARY, &[0; 2000]), 1024).is_err());
+        let (read, _) = read_frame(&client_frame(false, OPCODE_BINARY, b"first half"), 1024).unwrap().unwrap();
+        assert!(!read.fin);
+    }
+}
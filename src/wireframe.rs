@@ -0,0 +1,140 @@
+use bevy::{
+    prelude::*,
+    render::mesh::VertexAttribute,
+    render::pipeline::PrimitiveTopology,
+};
+use std::collections::{HashMap, HashSet};
+use crate::player::KeyBindings;
+
+#[derive(Default)]
+pub struct WireframeSettings {
+    pub enabled: bool,
+}
+
+// bevy 0.1 has no wireframe mode, so each mesh gets a line list copy of its triangle
+// edges as a child while the real one is hidden. keeps whether it was drawn before,
+// so turning the wireframe off puts it back how it was
+struct Wireframed {
+    overlay: Option<Entity>,
+    was_visible: bool,
+}
+
+struct WireframeOverlay;
+
+#[derive(Default)]
+struct WireframeMeshes {
+    // line meshes built so far, by the mesh they were built from
+    lines: HashMap<Handle<Mesh>, Handle<Mesh>>,
+    material: Option<Handle<StandardMaterial>>,
+}
+
+pub struct WireframePlugin;
+
+impl Plugin for WireframePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<WireframeSettings>()
+            .add_system(toggle_wireframe.system())
+            .add_system(update_wireframes.system());
+    }
+}
+
+fn toggle_wireframe(
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    mut settings: ResMut<WireframeSettings>,
+) {
+    if keyboard_input.just_pressed(bindings.toggle_wireframe) { settings.enabled = !settings.enabled; }
+}
+
+// same vertices, with each triangle edge as a line. shared edges are only drawn once
+fn wireframe_mesh(mesh: &Mesh) -> Option<Mesh> {
+    if mesh.primitive_topology != PrimitiveTopology::TriangleList { return None; }
+    let indices = mesh.indices.as_ref()?;
+
+    let mut edges = HashSet::new();
+    let mut lines = Vec::with_capacity(indices.len() * 2);
+    for triangle in indices.chunks_exact(3) {
+        for &(a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])].iter() {
+            if edges.insert((a.min(b), a.max(b))) { lines.extend_from_slice(&[a, b]); }
+        }
+    }
+    if lines.is_empty() { return None; }
+
+    Some(Mesh {
+        primitive_topology: PrimitiveTopology::LineList,
+        // VertexAttribute itself isn't Clone in bevy 0.1
+        attributes: mesh
+            .attributes
+            .iter()
+            .map(|attribute| VertexAttribute { name: attribute.name.clone(), values: attribute.values.clone() })
+            .collect(),
+        indices: Some(lines),
+    })
+}
+
+// ui nodes have meshes too, only entities drawn with a standard material are 3d
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn update_wireframes(
+    mut commands: Commands,
+    mut cache: Local<WireframeMeshes>,
+    settings: Res<WireframeSettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut unwired_query: Query<Without<Wireframed, Without<WireframeOverlay, (Entity, &Handle<Mesh>, &Handle<StandardMaterial>, &mut Draw)>>>,
+    mut wired_query: Query<(Entity, &Wireframed, &mut Draw)>,
+    mut overlay_query: Query<With<WireframeOverlay, (Entity, &Parent)>>,
+) {
+    // bevy 0.1 doesn't despawn children with their parent
+    for (overlay, parent) in &mut overlay_query.iter() {
+        if wired_query.get::<Wireframed>(parent.0).is_err() { commands.despawn(overlay); }
+    }
+
+    if !settings.enabled {
+        for (entity, wireframed, mut draw) in &mut wired_query.iter() {
+            draw.is_visible = wireframed.was_visible;
+            if let Some(overlay) = wireframed.overlay { commands.despawn(overlay); }
+            commands.remove_one::<Wireframed>(entity);
+        }
+        return;
+    }
+
+    let material = *cache.material.get_or_insert_with(|| materials.add(StandardMaterial {
+        albedo: Color::rgb(0.2, 1., 0.4),
+        shaded: false,
+        ..Default::default()
+    }));
+
+    // runs every frame while enabled, so anything spawned in the meantime is picked up too
+    for (entity, mesh, _material, mut draw) in &mut unwired_query.iter() {
+        let lines = match cache.lines.get(mesh) {
+            Some(lines) => Some(*lines),
+            None => {
+                // still loading, try again next frame
+                let source = match meshes.get(mesh) {
+                    Some(source) => source,
+                    None => continue,
+                };
+                let lines = wireframe_mesh(source).map(|lines| meshes.add(lines));
+                if let Some(lines) = lines { cache.lines.insert(*mesh, lines); }
+                lines
+            }
+        };
+
+        // lines and points are left drawn as they are
+        let overlay = lines.map(|lines| {
+            commands
+                .spawn(PbrComponents {
+                    mesh: lines,
+                    material,
+                    draw: Draw { is_visible: draw.is_visible, ..Default::default() },
+                    ..Default::default()
+                })
+                .with(WireframeOverlay);
+            let overlay = commands.current_entity().unwrap();
+            commands.push_children(entity, &[overlay]);
+            overlay
+        });
+        commands.insert_one(entity, Wireframed { overlay, was_visible: draw.is_visible });
+        if overlay.is_some() { draw.is_visible = false; }
+    }
+}
@@ -0,0 +1,316 @@
+use bevy::{
+    prelude::*,
+    asset::{AssetLoader, LoadState},
+    render::texture::ImageTextureLoader,
+};
+use serde::Deserialize;
+use std::{fs, io, path::Path};
+use crate::game_state::GameState;
+use crate::network::RemotePlayer;
+use crate::player::{InputSource, MMOPlayer, MovementState, Teleported};
+use crate::terrain::{spawn_terrain, TerrainHeight, TERRAIN_MAX_HEIGHT};
+use crate::timestep::FixedPosition;
+use crate::water::WaterLevel;
+
+pub const ZONES_PATH: &str = "res/zones.toml";
+
+// a rectangle of the world on x and z, with its own terrain. zones are placed side by side,
+// walking out of one and into the next transfers the player to the new one's entry
+#[derive(Clone, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    // where a transferred player lands
+    pub entry: [f32; 3],
+    // flat ground without one, stretched over the zone's longer side
+    #[serde(default)]
+    pub heightmap: Option<String>,
+    #[serde(default)]
+    pub water_level: Option<f32>,
+}
+
+impl Zone {
+    fn contains(&self, position: Vec3) -> bool {
+        position.x() >= self.min[0] && position.x() < self.max[0] && position.z() >= self.min[1] && position.z() < self.max[1]
+    }
+
+    fn centre(&self) -> Vec2 {
+        Vec2::new(self.min[0] + self.max[0], self.min[1] + self.max[1]) / 2.
+    }
+}
+
+#[derive(Default, Deserialize)]
+struct ZoneFile {
+    #[serde(default, rename = "zone")]
+    zones: Vec<Zone>,
+}
+
+// marks what is unloaded when the player leaves a zone
+pub struct ZoneContent;
+
+// a transfer whose heightmap is still loading in the background
+struct Transfer {
+    zone: usize,
+    heightmap: Option<Handle<Texture>>,
+    // where the player is put once it's loaded, none leaves them where they are
+    landing: Option<Vec3>,
+}
+
+// without a zones file there are no zones, and the world is just the one terrain
+#[derive(Default)]
+pub struct ZoneManager {
+    pub zones: Vec<Zone>,
+    pub current: Option<usize>,
+    transfer: Option<Transfer>,
+    // the zone the next teleport lands in, when whoever sent it knew. without it, it's
+    // whichever zone the player landed inside
+    arriving: Option<usize>,
+}
+
+impl ZoneManager {
+    pub fn load(path: &str) -> ZoneManager {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    eprintln!("failed to read zones '{}': {}, the world is one zone", path, err);
+                }
+                return ZoneManager::default();
+            }
+        };
+        let file: ZoneFile = toml::from_str(&contents).unwrap_or_else(|err| {
+            eprintln!("failed to parse zones '{}': {}, the world is one zone", path, err);
+            ZoneFile::default()
+        });
+        let mut zones = file.zones;
+        // landing outside the zone would start another transfer straight away
+        for zone in zones.iter_mut() {
+            if zone.contains(Vec3::from(zone.entry)) { continue; }
+            eprintln!("entry for zone '{}' is outside it, using its centre", zone.name);
+            let centre = zone.centre();
+            zone.entry = [centre.x(), zone.entry[1], centre.y()];
+        }
+        ZoneManager { zones, ..Default::default() }
+    }
+
+    pub fn current_zone(&self) -> Option<&Zone> {
+        self.current.map(|index| &self.zones[index])
+    }
+
+    pub fn transferring(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    pub fn zone_at(&self, position: Vec3) -> Option<usize> {
+        self.zones.iter().position(|zone| zone.contains(position))
+    }
+
+    pub fn zone_named(&self, name: &str) -> Option<usize> {
+        self.zones.iter().position(|zone| zone.name == name)
+    }
+
+    pub fn arrive_in(&mut self, zone: usize) {
+        self.arriving = Some(zone);
+    }
+
+    // loads on bevy's asset threads, finish_zone_transfer picks it up once it's in
+    fn start_transfer(&mut self, asset_server: &AssetServer, zone: usize, landing: Option<Vec3>) {
+        let heightmap = self.zones[zone].heightmap.as_ref().and_then(|path| match asset_server.load(path) {
+            Ok(heightmap) => Some(heightmap),
+            Err(err) => {
+                eprintln!("failed to load heightmap '{}': {}, using flat ground", path, err);
+                None
+            }
+        });
+        self.transfer = Some(Transfer { zone, heightmap, landing });
+    }
+}
+
+// built the same way on clients and the server, so both step players over the same ground
+fn zone_terrain(zone: &Zone, heightmap: Option<&Texture>) -> TerrainHeight {
+    let size = (zone.max[0] - zone.min[0]).max(zone.max[1] - zone.min[1]);
+    let heightmap = match heightmap {
+        Some(heightmap) => heightmap,
+        None => return TerrainHeight::flat(),
+    };
+    match TerrainHeight::from_texture(heightmap, size, TERRAIN_MAX_HEIGHT) {
+        Some(heights) => heights.centred_at(zone.centre()),
+        None => {
+            eprintln!("heightmap for zone '{}' is too small to build terrain from, using flat ground", zone.name);
+            TerrainHeight::flat()
+        }
+    }
+}
+
+// the server has no asset server to load zones with as players reach them, so it reads
+// every zone's terrain up front, in the same order as the zones
+#[derive(Default)]
+pub struct ZoneTerrains(Vec<(TerrainHeight, WaterLevel)>);
+
+impl ZoneTerrains {
+    pub fn get(&self, zone: Option<usize>) -> Option<(&TerrainHeight, &WaterLevel)> {
+        zone.and_then(|zone| self.0.get(zone)).map(|(terrain, water)| (terrain, water))
+    }
+}
+
+pub fn load_zone_terrains(manager: Res<ZoneManager>, mut terrains: ResMut<ZoneTerrains>) {
+    terrains.0 = manager
+        .zones
+        .iter()
+        .map(|zone| {
+            let heightmap = zone.heightmap.as_ref().and_then(|path| match ImageTextureLoader.load_from_file(Path::new(path)) {
+                Ok(heightmap) => Some(heightmap),
+                Err(err) => {
+                    eprintln!("failed to load heightmap '{}': {}, using flat ground", path, err);
+                    None
+                }
+            });
+            (zone_terrain(zone, heightmap.as_ref()), WaterLevel(zone.water_level))
+        })
+        .collect();
+}
+
+#[allow(dead_code)]
+pub struct ZoneEntered {
+    pub zone: String,
+}
+
+#[allow(dead_code)]
+pub struct ZoneExited {
+    pub zone: String,
+}
+
+pub struct ZonePlugin;
+
+impl Plugin for ZonePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(ZoneManager::load(ZONES_PATH))
+            .add_event::<ZoneEntered>()
+            .add_event::<ZoneExited>()
+            .add_system(detect_zone_crossing.system())
+            .add_system(finish_zone_transfer.system());
+    }
+}
+
+// the zone the game starts in is loaded like any other, just without moving the player to
+// its entry. after that, stepping into another zone starts loading it, and the old one
+// stays until the new one is ready. teleports into another zone load it the same way, but
+// keep the player where they were put rather than sending them to its entry
+fn detect_zone_crossing(
+    game_state: Res<GameState>,
+    asset_server: Res<AssetServer>,
+    mut manager: ResMut<ZoneManager>,
+    mut exited_events: ResMut<Events<ZoneExited>>,
+    mut teleported_reader: Local<EventReader<Teleported>>,
+    teleported_events: Res<Events<Teleported>>,
+    mut player_query: Query<Without<RemotePlayer, (&MMOPlayer, &Translation)>>,
+) {
+    for event in teleported_reader.iter(&teleported_events) {
+        match player_query.get::<MMOPlayer>(event.entity) {
+            Ok(player) if player.input_source == InputSource::KeyboardMouse => {}
+            _ => continue,
+        }
+        let position = event.state.position;
+        let zone = match manager.arriving.take().or_else(|| manager.zone_at(position)) {
+            Some(zone) => zone,
+            // between zones, which is on whatever ground is loaded
+            None => continue,
+        };
+        let transferring_to = manager.transfer.as_ref().map(|transfer| transfer.zone);
+        if transferring_to == Some(zone) {
+            if let Some(transfer) = manager.transfer.as_mut() { transfer.landing = Some(position); }
+        } else if manager.current == Some(zone) {
+            // back in the zone they were leaving, or never left, which is still loaded
+            manager.transfer = None;
+        } else {
+            if let (None, Some(current)) = (transferring_to, manager.current_zone()) {
+                exited_events.send(ZoneExited { zone: current.name.clone() });
+            }
+            manager.start_transfer(&asset_server, zone, Some(position));
+        }
+    }
+
+    if *game_state != GameState::Playing || manager.transferring() { return; }
+
+    for (player, translation) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let zone = match manager.zone_at(translation.0) {
+            Some(zone) if Some(zone) != manager.current => zone,
+            // between zones or still in the same one
+            _ => continue,
+        };
+
+        let landing = match manager.current_zone() {
+            Some(current) => {
+                exited_events.send(ZoneExited { zone: current.name.clone() });
+                Some(Vec3::from(manager.zones[zone].entry))
+            }
+            None => None,
+        };
+        manager.start_transfer(&asset_server, zone, landing);
+    }
+}
+
+// swaps the old zone's content for the new one's in a single frame, once everything is
+// loaded. the camera hangs off the player, so it comes along without snapping
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn finish_zone_transfer(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut manager: ResMut<ZoneManager>,
+    mut terrain: ResMut<TerrainHeight>,
+    mut water: ResMut<WaterLevel>,
+    textures: Res<Assets<Texture>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut entered_events: ResMut<Events<ZoneEntered>>,
+    mut teleported_events: ResMut<Events<Teleported>>,
+    mut content_query: Query<With<ZoneContent, Entity>>,
+    mut player_query: Query<Without<RemotePlayer, (Entity, &mut MMOPlayer, &mut FixedPosition, &mut Translation)>>,
+) {
+    let (index, heightmap, landing) = match manager.transfer.as_ref() {
+        Some(transfer) => (transfer.zone, transfer.heightmap, transfer.landing),
+        None => return,
+    };
+    if let Some(heightmap) = heightmap {
+        if let Some(LoadState::Loading(_)) = asset_server.get_load_state(heightmap) { return; }
+    }
+    manager.transfer = None;
+    manager.current = Some(index);
+    let zone = manager.zones[index].clone();
+
+    for entity in &mut content_query.iter() {
+        commands.despawn(entity);
+    }
+
+    let texture = heightmap.and_then(|heightmap| textures.get(&heightmap));
+    if heightmap.is_some() && texture.is_none() {
+        eprintln!("heightmap for zone '{}' didn't load, using flat ground", zone.name);
+    }
+    let heights = zone_terrain(&zone, texture);
+    if !heights.is_flat() {
+        let entity = spawn_terrain(&mut commands, &mut meshes, &mut materials, &heights);
+        commands.insert_one(entity, ZoneContent);
+    }
+    *terrain = heights;
+    water.0 = zone.water_level;
+    entered_events.send(ZoneEntered { zone: zone.name });
+
+    // teleported rather than moved, so the fixed step doesn't draw it sliding across, and
+    // landing at rest with nowhere to go, whatever the player was doing in the old zone
+    let landing = match landing {
+        Some(landing) => landing,
+        None => return,
+    };
+    for (entity, mut player, mut position, mut translation) in &mut player_query.iter() {
+        if player.input_source != InputSource::KeyboardMouse { continue; }
+        let state = MovementState::at_rest(landing, false);
+        position.teleport(landing);
+        translation.0 = landing;
+        player.set_movement_state(&state);
+        player.move_target = None;
+        player.waypoints.clear();
+        teleported_events.send(Teleported { entity, state });
+    }
+}